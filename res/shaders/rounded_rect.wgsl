@@ -0,0 +1,34 @@
+// background of the board or the panel, in physical pixels
+struct RoundedRect {
+    center: vec2<f32>,
+    half_size: vec2<f32>,
+    radius: f32,
+    // how red the border is, 0 for none
+    warning: f32,
+};
+@group(0) @binding(0) var<uniform> rect: RoundedRect;
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position, 0.0, 1.0);
+}
+
+// signed distance to the edge of the rounded box centered at 0, negative inside.
+// With a zero radius it's the distance to a plain box.
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// width of the warning border inside the edge
+const BORDER_PX: f32 = 4.0;
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let d = rounded_box(frag.xy - rect.center, rect.half_size, rect.radius);
+    // one pixel of antialiasing along the edge
+    let coverage = clamp(0.5 - d, 0.0, 1.0);
+    let border = clamp(d + BORDER_PX + 0.5, 0.0, 1.0);
+    let color = mix(vec3<f32>(0.15, 0.1, 0.03), vec3<f32>(0.9, 0.05, 0.05), rect.warning * border);
+    return vec4<f32>(color, coverage);
+}
@@ -3,10 +3,43 @@ struct PushConstants {
 }
 var<push_constant> c: PushConstants;
 
+// a colour per shape type for the placed cells, in the order of `BaseShapeType`
+fn shape_color(state: u32) -> vec4<f32> {
+    switch state {
+        case 7u: { return vec4<f32>(0.64, 0.32, 0.76, 1.0); } // T
+        case 8u: { return vec4<f32>(0.88, 0.48, 0.12, 1.0); } // L
+        case 9u: { return vec4<f32>(0.24, 0.72, 0.84, 1.0); } // I
+        case 10u: { return vec4<f32>(0.76, 0.4, 0.32, 1.0); } // single cell
+        case 11u: { return vec4<f32>(0.84, 0.76, 0.2, 1.0); } // 2x2 square
+        case 12u: { return vec4<f32>(0.36, 0.68, 0.28, 1.0); } // S
+        case 13u: { return vec4<f32>(0.84, 0.36, 0.6, 1.0); } // Z
+        case 14u: { return vec4<f32>(0.28, 0.4, 0.8, 1.0); } // J
+        default: { return vec4<f32>(0.48, 0.8, 0.76, 1.0); } // line of 3
+    }
+}
+
 @fragment
 fn fs_main() -> @location(0) vec4<f32> {
     if c.is_cursor == 1u {
         return vec4<f32>(1.0, 0.0, 0.0, 1.0); // 🔴 Red for cursor
+    } else if c.is_cursor == 2u {
+        return vec4<f32>(1.0, 1.0, 1.0, 1.0); // ⚪ White for highlighted cells
+    } else if c.is_cursor == 3u {
+        return vec4<f32>(1.0, 0.9, 0.5, 1.0); // light gold for a double clear
+    } else if c.is_cursor == 4u {
+        return vec4<f32>(1.0, 0.8, 0.0, 1.0); // 🟡 Gold for 3+ lines
+    } else if c.is_cursor == 5u {
+        // a shape colour barely above the board background, for the best move hint
+        return vec4<f32>(0.26, 0.16, 0.02, 1.0);
+    } else if c.is_cursor == 6u {
+        // greyish, set apart from the shape colours, for the cells a nearly full line needs
+        return vec4<f32>(0.3, 0.28, 0.24, 1.0);
+    } else if c.is_cursor >= 7u && c.is_cursor <= 15u {
+        return shape_color(c.is_cursor);
+    } else if c.is_cursor == 16u {
+        return vec4<f32>(0.0, 0.0, 0.0, 0.6); // the pause overlay, the only see-through one
+    } else if c.is_cursor == 17u {
+        return vec4<f32>(0.2, 0.85, 0.3, 1.0); // 🟢 Green for a shadow that can be placed
     } else {
         return vec4<f32>(0.5, 0.3, 0.0, 1.0); // 🟡 Yellowish for everything else
     }
@@ -0,0 +1,23 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) alpha: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) alpha: f32,
+};
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(input.position, 0.0, 1.0);
+    out.alpha = input.alpha;
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // same red as the cursor, fading out
+    return vec4<f32>(1.0, 0.0, 0.0, input.alpha);
+}
@@ -0,0 +1,153 @@
+use crate::game_entities::{Board, Cell};
+
+// URL-safe base64 without padding, so a code survives being pasted into a chat or a link
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// why a pasted code isn't a board
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    InvalidCharacter(char),
+    // cut off somewhere, or with something added at the end
+    WrongLength,
+    // a board needs at least one cell
+    EmptyBoard,
+    // bits set past the last cell
+    TrailingBits,
+}
+
+impl Board {
+    // columns and rows as two little-endian u16s, then a bit per cell row by row (filled is 1,
+    // lowest bit first), all in base64. Only the cells are kept, not the shapes on them.
+    pub fn to_code(&self) -> String {
+        let mut bytes = Vec::with_capacity(4 + self.grid.len().div_ceil(8));
+        bytes.extend_from_slice(&(self.cols as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.rows as u16).to_le_bytes());
+        for cells in self.grid.chunks(8) {
+            let byte = cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| **cell == Cell::Filled)
+                .fold(0u8, |byte, (bit, _)| byte | 1 << bit);
+            bytes.push(byte);
+        }
+        encode(&bytes)
+    }
+
+    // reverse of `to_code`, surrounding whitespace is ignored
+    pub fn from_code(code: &str) -> Result<Self, DecodeError> {
+        let bytes = decode(code.trim())?;
+        if bytes.len() < 4 {
+            return Err(DecodeError::WrongLength);
+        }
+        let cols = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+        let rows = u16::from_le_bytes([bytes[2], bytes[3]]) as usize;
+        if cols == 0 || rows == 0 {
+            return Err(DecodeError::EmptyBoard);
+        }
+        let cells = &bytes[4..];
+        if cells.len() != (cols * rows).div_ceil(8) {
+            return Err(DecodeError::WrongLength);
+        }
+        let mut board = Self::with_dimensions(cols, rows);
+        for ix in 0..cols * rows {
+            if cells[ix / 8] & 1 << (ix % 8) != 0 {
+                board.set_cell(ix % cols, ix / cols, Cell::Filled);
+            }
+        }
+        let used_bits = (cols * rows) % 8;
+        if used_bits != 0 && cells[cells.len() - 1] >> used_bits != 0 {
+            return Err(DecodeError::TrailingBits);
+        }
+        Ok(board)
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut code = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        // 2, 3 or 4 characters for 1, 2 or 3 bytes
+        for i in 0..=chunk.len() {
+            code.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    code
+}
+
+fn decode(code: &str) -> Result<Vec<u8>, DecodeError> {
+    let values = code
+        .chars()
+        .map(|c| {
+            ALPHABET
+                .iter()
+                .position(|&a| a as char == c)
+                .map(|value| value as u32)
+                .ok_or(DecodeError::InvalidCharacter(c))
+        })
+        .collect::<Result<Vec<u32>, DecodeError>>()?;
+    let mut bytes = Vec::with_capacity(values.len() * 3 / 4);
+    for chunk in values.chunks(4) {
+        // a single character can't hold a whole byte
+        if chunk.len() == 1 {
+            return Err(DecodeError::WrongLength);
+        }
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, &value)| group | value << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_code_round_trip() {
+        for ascii in ["#.\n.#\n", "###.#\n.....\n#...#\n", "#\n", "........\n"] {
+            let board = Board::from_ascii(ascii).unwrap();
+
+            let code = board.to_code();
+
+            assert!(code
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+            assert_eq!(Board::from_code(&code).unwrap().to_ascii(), ascii);
+        }
+        assert_eq!(encode(b"Man"), "TWFu");
+        assert_eq!(encode(b"Ma"), "TWE");
+        assert_eq!(decode("TWE").unwrap(), b"Ma");
+    }
+
+    #[test]
+    fn test_malformed_board_codes_are_rejected() {
+        let code = Board::from_ascii("#.#\n.#.\n#.#\n").unwrap().to_code();
+
+        assert_eq!(
+            Board::from_code(&format!("{}!", &code[1..])).err(),
+            Some(DecodeError::InvalidCharacter('!'))
+        );
+        assert_eq!(
+            Board::from_code(&code[..code.len() - 2]).err(),
+            Some(DecodeError::WrongLength)
+        );
+        assert_eq!(Board::from_code("").err(), Some(DecodeError::WrongLength));
+        // 0 x 3
+        assert_eq!(
+            Board::from_code(&encode(&[0, 0, 3, 0, 0])).err(),
+            Some(DecodeError::EmptyBoard)
+        );
+        // a 3 x 1 board with the fourth bit set
+        assert_eq!(
+            Board::from_code(&encode(&[3, 0, 1, 0, 0b1001])).err(),
+            Some(DecodeError::TrailingBits)
+        );
+        assert!(Board::from_code(&format!("  {}\n", code)).is_ok());
+    }
+}
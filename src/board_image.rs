@@ -0,0 +1,52 @@
+use image::{Rgba, RgbaImage};
+
+use crate::game_entities::{Board, Cell};
+
+// same colours as the board is drawn with, see `textured.frag.wgsl` and `rounded_rect.wgsl`
+const EMPTY_COLOR: Rgba<u8> = Rgba([38, 26, 8, 255]);
+const FILLED_COLOR: Rgba<u8> = Rgba([128, 77, 0, 255]);
+// size of a cell in the images `--board-image` saves
+pub const EXPORT_CELL_PX: u32 = 32;
+
+fn cell_color(cell: &Cell) -> Rgba<u8> {
+    match cell {
+        Cell::Empty => EMPTY_COLOR,
+        Cell::Filled => FILLED_COLOR,
+    }
+}
+
+impl Board {
+    // the board as a picture, `cell_px` pixels per cell and the top row first. No GPU involved,
+    // for thumbnails and docs.
+    pub fn to_image(&self, cell_px: u32) -> RgbaImage {
+        RgbaImage::from_fn(
+            self.cols as u32 * cell_px,
+            self.rows as u32 * cell_px,
+            |x, y| {
+                let cell = self.get((x / cell_px) as usize, (y / cell_px) as usize);
+                cell.map_or(EMPTY_COLOR, cell_color)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_image_has_a_square_per_cell() {
+        let board = Board::from_ascii("#.\n.#\n").unwrap();
+
+        let image = board.to_image(3);
+
+        assert_eq!(image.dimensions(), (6, 6));
+        // top-left and bottom-right quadrants are filled
+        for (x, y) in [(0, 0), (2, 2), (3, 3), (5, 5)] {
+            assert_eq!(*image.get_pixel(x, y), FILLED_COLOR, "({}, {})", x, y);
+        }
+        for (x, y) in [(3, 0), (5, 2), (0, 3), (2, 5)] {
+            assert_eq!(*image.get_pixel(x, y), EMPTY_COLOR, "({}, {})", x, y);
+        }
+    }
+}
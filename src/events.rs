@@ -4,5 +4,11 @@ use crate::space_converters::{CellCoord, OffsetXY};
 #[derive(Debug, Clone)]
 pub enum Event {
     ShapeSelected(usize, OffsetXY),
+    // the selected shape was dropped back to where it came from
+    ShapeDeselected,
     SelectedShapePlaced(ShapeType, CellCoord),
+    // board cell that was clicked and the anchor offset to the top-left of the shape under it
+    PlacedShapePickedUp(CellCoord, OffsetXY),
+    // number of rows and cols the last placement cleared
+    LinesCleared(usize),
 }
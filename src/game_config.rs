@@ -0,0 +1,261 @@
+use std::time::Duration;
+
+use crate::game_entities::BaseShapeType;
+use crate::goal::LevelGoal;
+
+// where row 0 of the board is. Only changes how board rows map onto the screen and mouse, the
+// board itself is always stored top row first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoardOrigin {
+    TopLeft,
+    // for levels ported from tools that count rows from the bottom
+    BottomLeft,
+}
+
+impl BoardOrigin {
+    // board row <-> row on the screen counted from the top, works both ways
+    pub fn visual_row(self, row: usize, rows: usize) -> usize {
+        match self {
+            BoardOrigin::TopLeft => row,
+            BoardOrigin::BottomLeft => rows - 1 - row,
+        }
+    }
+}
+
+// kinds of symmetry for the symmetric puzzle variant
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Symmetry {
+    // left half mirrored onto the right
+    Mirror,
+    // the same after turning the board by 180 degrees
+    Rotational,
+}
+
+impl Symmetry {
+    // `mirror` or `rotational`, as `--symmetry` takes it
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "mirror" => Some(Symmetry::Mirror),
+            "rotational" => Some(Symmetry::Rotational),
+            _ => None,
+        }
+    }
+
+    // the cell that has to look like (col, row) on a symmetric board, might be the same one
+    pub fn partner(self, col: usize, row: usize, cols: usize, rows: usize) -> (usize, usize) {
+        match self {
+            Symmetry::Mirror => (cols - 1 - col, row),
+            Symmetry::Rotational => (cols - 1 - col, rows - 1 - row),
+        }
+    }
+}
+
+// shape of a `SpeedRamp` between its start and its end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RampCurve {
+    Linear,
+    // slow at first, most of the speed up comes at the end
+    EaseIn,
+}
+
+impl RampCurve {
+    // `linear` or `ease-in`, as `--decay-ramp` takes it
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "linear" => Some(RampCurve::Linear),
+            "ease-in" => Some(RampCurve::EaseIn),
+            _ => None,
+        }
+    }
+}
+
+// a rate growing over the level, from 1 at level start to `max_factor` after `ramp_time`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedRamp {
+    pub curve: RampCurve,
+    pub ramp_time: Duration,
+    pub max_factor: f32,
+}
+
+impl SpeedRamp {
+    // what the rate is multiplied with `level_time` into the level
+    pub fn factor(&self, level_time: Duration) -> f32 {
+        let t = if self.ramp_time.is_zero() {
+            1.0
+        } else {
+            (level_time.as_secs_f32() / self.ramp_time.as_secs_f32()).min(1.0)
+        };
+        let eased = match self.curve {
+            RampCurve::Linear => t,
+            RampCurve::EaseIn => t * t,
+        };
+        1.0 + (self.max_factor - 1.0) * eased
+    }
+}
+
+// game rules, as opposed to `UserRenderConfig` which only describes how things are drawn.
+#[derive(Clone, Debug)]
+pub struct GameConfig {
+    // clicking a placed shape on the board lifts it back into the hand
+    pub pick_up_placed_shapes: bool,
+    pub pick_up_cost: i32,
+    // the first panel of every level has at least one shape that fits, to avoid unfair starts
+    pub solvable_first_panel: bool,
+    // rerolls a visible shape into a placeable one when the panel has fewer valid placements
+    // than the threshold. Meant to be off for hardcore modes.
+    pub anti_frustration: bool,
+    pub anti_frustration_threshold: usize,
+    // placing shapes closer to the bottom of the board awards up to `soft_drop_max_bonus` points
+    pub soft_drop_scoring: bool,
+    pub soft_drop_max_bonus: i32,
+    // "endless" mode: instead of a fresh board every level, the current one keeps its cells
+    // and gets one more row and column, up to `board_growth_max_size`
+    pub board_growth: bool,
+    pub board_growth_max_size: usize,
+    // nothing moves while the window is in the background
+    pub pause_on_focus_loss: bool,
+    pub board_origin: BoardOrigin,
+    // writes the recovery file after every N placements
+    pub autosave_every: Option<u32>,
+    pub level_goal: LevelGoal,
+    // variant mode: a shape going past an edge of the board continues at the opposite edge
+    pub wraparound: bool,
+    // prompts walking through the first placement of level 1
+    pub tutorial: bool,
+    // points for leaving the board empty with a clear, 0 turns it off
+    pub perfect_clear_bonus: i32,
+    // points for a placement completing a pattern like the main diagonal, 0 turns it off
+    pub pattern_bonus: i32,
+    // the final score of every game goes into the top ten, which is kept in the settings file
+    pub keep_leaderboard: bool,
+    // after a placement, another visible panel shape of the same kind gets selected right away
+    pub sticky_selection: bool,
+    // every level starts with a thumbnail of its board for this long, any key or click skips it
+    pub level_intro: Option<Duration>,
+    // a lost level can be played again from its start, keeping the score of the levels before
+    pub allow_retry: bool,
+    // Ctrl and the restart key start a new run from level 1 while playing
+    pub quick_restart: bool,
+    // the game over screen offers its choices as a menu, picked with the arrow keys and Enter
+    pub game_over_menu: bool,
+    // puzzle variant: every level starts symmetric, and placements keeping it that way earn
+    // `symmetry_bonus` points
+    pub symmetry: Option<Symmetry>,
+    pub symmetry_bonus: i32,
+    // this many of the cells filled at level start are bonus cells, clearing a line with one in
+    // it earns `bonus_cell_points` for each
+    pub bonus_cells: usize,
+    pub bonus_cell_points: i32,
+    // tension mechanic: after this long without a placement the score starts to go down, a
+    // point every `score_decay_every`. None turns it off.
+    pub score_decay_grace: Option<Duration>,
+    pub score_decay_every: Duration,
+    // the decay clock runs faster the longer the level goes on, both the grace period and the
+    // interval shrink. None keeps it steady.
+    pub score_decay_ramp: Option<SpeedRamp>,
+    // before ending the game, also try the panel shapes in every rotation and mirror
+    pub game_over_checks_orientations: bool,
+    // the rotate key turns the held shape, so the game is only over when no turn of a shape fits
+    pub rotation: bool,
+    // held or spammed rotate turns the shape at most once per this much game time
+    pub rotation_debounce: Duration,
+    // every clear right after another is worth more: the n-th clear in a row earns n times its
+    // points
+    pub combo_scoring: bool,
+    // shapes dealt per round, the panel gets as wide as they need, see `Panel::cols_for`
+    pub panel_shapes: usize,
+    // practice mode: every panel slot offers this shape, in random rotations and mirrors
+    pub forced_shape: Option<BaseShapeType>,
+    // panels are dealt from a `ShapeBag`, so every shape type comes once before any comes again.
+    // Each shape is picked on its own otherwise.
+    pub shape_bag: bool,
+    // variant where every placement has to touch a filled cell, see `MustTouchExisting`
+    pub must_touch_existing: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            pick_up_placed_shapes: false,
+            pick_up_cost: 2,
+            solvable_first_panel: true,
+            anti_frustration: false,
+            anti_frustration_threshold: 3,
+            soft_drop_scoring: false,
+            soft_drop_max_bonus: 3,
+            board_growth: false,
+            board_growth_max_size: 12,
+            pause_on_focus_loss: true,
+            board_origin: BoardOrigin::TopLeft,
+            autosave_every: None,
+            level_goal: LevelGoal::Score,
+            wraparound: false,
+            tutorial: true,
+            perfect_clear_bonus: 0,
+            pattern_bonus: 0,
+            keep_leaderboard: true,
+            sticky_selection: false,
+            level_intro: None,
+            allow_retry: true,
+            quick_restart: true,
+            game_over_menu: false,
+            symmetry: None,
+            symmetry_bonus: 5,
+            bonus_cells: 0,
+            bonus_cell_points: 10,
+            score_decay_grace: None,
+            score_decay_every: Duration::from_secs(1),
+            score_decay_ramp: None,
+            game_over_checks_orientations: false,
+            rotation: false,
+            rotation_debounce: Duration::from_millis(120),
+            combo_scoring: true,
+            panel_shapes: 3,
+            forced_shape: None,
+            shape_bag: true,
+            must_touch_existing: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ramp_curve_names() {
+        assert_eq!(RampCurve::from_name("linear"), Some(RampCurve::Linear));
+        assert_eq!(RampCurve::from_name("Ease-In"), Some(RampCurve::EaseIn));
+        assert_eq!(RampCurve::from_name("steep"), None);
+    }
+
+    #[test]
+    fn test_symmetry_names() {
+        assert_eq!(Symmetry::from_name("mirror"), Some(Symmetry::Mirror));
+        assert_eq!(
+            Symmetry::from_name(" Rotational "),
+            Some(Symmetry::Rotational)
+        );
+        assert_eq!(Symmetry::from_name("diagonal"), None);
+    }
+
+    #[test]
+    fn test_speed_ramp_follows_its_curve() {
+        let mut ramp = SpeedRamp {
+            curve: RampCurve::Linear,
+            ramp_time: Duration::from_secs(60),
+            max_factor: 3.0,
+        };
+        let at = |ramp: &SpeedRamp, secs: u64| ramp.factor(Duration::from_secs(secs));
+
+        assert_eq!(at(&ramp, 0), 1.0);
+        assert_eq!(at(&ramp, 30), 2.0);
+        assert_eq!(at(&ramp, 60), 3.0);
+        // stays at the top afterwards
+        assert_eq!(at(&ramp, 600), 3.0);
+
+        ramp.curve = RampCurve::EaseIn;
+        assert_eq!(at(&ramp, 30), 1.5);
+        assert_eq!(at(&ramp, 60), 3.0);
+    }
+}
@@ -1,10 +1,20 @@
+use crate::date::Date;
+use crate::game_config::{BoardOrigin, GameConfig, Symmetry};
 use crate::game_entities::ShapeState::VISIBLE;
+use crate::leaderboard::Leaderboard;
+use crate::menu::MenuState;
+use crate::patterns::Pattern;
+use crate::placement_rule::{MustTouchExisting, PlacementRule};
+use crate::solver;
 use crate::space_converters::{CellCoord, OffsetXY};
+use crate::tutorial::TutorialStep;
 use cgmath::num_traits::ToPrimitive;
 use rand::prelude::{IteratorRandom, SliceRandom};
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::cmp::{max, min};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
 use strum::IntoEnumIterator;
 use strum_macros::{EnumCount, EnumIter};
 
@@ -14,28 +24,375 @@ pub enum Cell {
     Filled,
 }
 
+#[derive(Clone)]
 pub struct Board {
     pub grid: Vec<Cell>,
-    pub size: usize,
+    pub cols: usize,
+    pub rows: usize,
+    // id of the placed shape each cell belongs to (key into `placed_shapes`).
+    // Cells seeded at level start don't belong to any shape.
+    pub shape_ids: Vec<Option<usize>>,
+    // only the shapes that still have a cell on the board
+    placed_shapes: HashMap<usize, PlacedShape>,
+    next_shape_id: usize,
+    // filled cells worth extra points when cleared, see `GameConfig::bonus_cells`
+    bonus: Vec<bool>,
+    // cells that changed since the renderer last took them, everything is changed on a new board
+    changed: Vec<bool>,
+    // patterns that already scored, see `score_pattern`
+    scored_patterns: Vec<Pattern>,
+}
+
+// row and column indices that were full at the end of a turn
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ClearedLines {
+    pub rows: Vec<usize>,
+    pub cols: Vec<usize>,
+}
+
+impl ClearedLines {
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.cols.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlacedShape {
+    pub shape_type: ShapeType,
+    pub origin: CellCoord,
 }
 
 impl Board {
     pub(crate) fn new(size: usize) -> Self {
+        Self::with_dimensions(size, size)
+    }
+
+    pub fn with_dimensions(cols: usize, rows: usize) -> Self {
         Self {
-            grid: vec![Cell::Empty; size * size],
-            size,
+            grid: vec![Cell::Empty; cols * rows],
+            cols,
+            rows,
+            shape_ids: vec![None; cols * rows],
+            placed_shapes: HashMap::new(),
+            next_shape_id: 0,
+            bonus: vec![false; cols * rows],
+            changed: vec![true; cols * rows],
+            scored_patterns: Vec::new(),
         }
     }
 
+    fn index(&self, col: usize, row: usize) -> Option<usize> {
+        (col < self.cols && row < self.rows).then_some(row * self.cols + col)
+    }
+
     pub fn get(&self, col: usize, row: usize) -> Option<&Cell> {
-        self.grid.get(row * self.size + col)
+        self.index(col, row).map(|ix| &self.grid[ix])
+    }
+
+    // row by row, like `index` lays them out
+    #[cfg(feature = "testing")]
+    pub fn cells(&self) -> &[Cell] {
+        &self.grid
+    }
+
+    pub fn clear_lines(&mut self, lines: &ClearedLines) {
+        for &row in &lines.rows {
+            for col in 0..self.cols {
+                self.set_cell(col, row, Cell::Empty);
+            }
+        }
+        for &col in &lines.cols {
+            for row in 0..self.rows {
+                self.set_cell(col, row, Cell::Empty);
+            }
+        }
+        let scored = std::mem::take(&mut self.scored_patterns);
+        self.scored_patterns = scored.into_iter().filter(|p| p.is_complete(self)).collect();
+        self.forget_cleared_shapes();
+    }
+
+    // drops the placed shapes none of whose cells are left
+    fn forget_cleared_shapes(&mut self) {
+        let on_board: HashSet<usize> = self.shape_ids.iter().flatten().copied().collect();
+        self.placed_shapes.retain(|id, _| on_board.contains(id));
+    }
+
+    // false when the pattern has scored before. It only scores again once a clear breaks it, lifting
+    // a shape off it and putting it back doesn't.
+    pub fn score_pattern(&mut self, pattern: Pattern) -> bool {
+        if self.scored_patterns.contains(&pattern) {
+            return false;
+        }
+        self.scored_patterns.push(pattern);
+        true
     }
 
     // Helper to fill a specific cell (for demo purposes)
     pub fn set_cell(&mut self, col: usize, row: usize, cell: Cell) {
-        if let Some(slot) = self.grid.get_mut(row * self.size + col) {
-            *slot = cell;
+        if let Some(ix) = self.index(col, row) {
+            if self.grid[ix] != cell {
+                self.changed[ix] = true;
+            }
+            self.grid[ix] = cell;
+            self.shape_ids[ix] = None;
+            // the bonus goes with the cell
+            if cell == Cell::Empty {
+                self.bonus[ix] = false;
+            }
+        }
+    }
+
+    // only filled cells can be bonus cells
+    pub fn set_bonus(&mut self, col: usize, row: usize) {
+        if let Some(ix) = self.index(col, row) {
+            self.bonus[ix] = self.grid[ix] == Cell::Filled;
+        }
+    }
+
+    // (col, row) of the bonus cells, row by row
+    pub fn bonus_cells(&self) -> Vec<(usize, usize)> {
+        (0..self.bonus.len())
+            .filter(|&ix| self.bonus[ix])
+            .map(|ix| (ix % self.cols, ix / self.cols))
+            .collect()
+    }
+
+    // indices (row * cols + col) of the cells changed since the last call
+    pub fn take_changed_cells(&mut self) -> Vec<usize> {
+        let changed = (0..self.changed.len())
+            .filter(|&ix| self.changed[ix])
+            .collect();
+        self.changed.fill(false);
+        changed
+    }
+
+    // adds rows at the bottom and cols on the right, keeping cells where they are
+    pub fn grow(&mut self, drow: usize, dcol: usize) {
+        let mut grown = Self::with_dimensions(self.cols + dcol, self.rows + drow);
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let from = row * self.cols + col;
+                let to = row * grown.cols + col;
+                grown.grid[to] = self.grid[from];
+                grown.shape_ids[to] = self.shape_ids[from];
+                grown.bonus[to] = self.bonus[from];
+            }
+        }
+        grown.placed_shapes = std::mem::take(&mut self.placed_shapes);
+        grown.next_shape_id = self.next_shape_id;
+        *self = grown;
+    }
+
+    // `.` for empty and `#` for filled cells, one line per row
+    pub fn to_ascii(&self) -> String {
+        let mut result = String::with_capacity((self.cols + 1) * self.rows);
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                match self.get(col, row) {
+                    Some(Cell::Filled) => result.push('#'),
+                    _ => result.push('.'),
+                }
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    // reverse of `to_ascii`, none when the rows differ in length or there's an unknown character
+    pub fn from_ascii(text: &str) -> Option<Self> {
+        let lines: Vec<&str> = text.lines().collect();
+        let cols = lines.first()?.len();
+        let mut board = Self::with_dimensions(cols, lines.len());
+        for (row, line) in lines.iter().enumerate() {
+            if line.len() != cols {
+                return None;
+            }
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    '#' => board.set_cell(col, row, Cell::Filled),
+                    '.' => {}
+                    _ => return None,
+                }
+            }
+        }
+        Some(board)
+    }
+
+    pub fn is_symmetric(&self, kind: Symmetry) -> bool {
+        (0..self.rows).all(|row| {
+            (0..self.cols).all(|col| {
+                let (partner_col, partner_row) = kind.partner(col, row, self.cols, self.rows);
+                self.get(col, row) == self.get(partner_col, partner_row)
+            })
+        })
+    }
+
+    pub fn filled_count(&self) -> usize {
+        self.grid.iter().filter(|c| **c == Cell::Filled).count()
+    }
+
+    // share of filled cells, 0.0 for an empty board and 1.0 for a full one
+    pub fn fill_fraction(&self) -> f32 {
+        if self.grid.is_empty() {
+            return 0.0;
+        }
+        self.filled_count() as f32 / self.grid.len() as f32
+    }
+
+    // filled cells in each row, top row first
+    pub fn row_fill_counts(&self) -> Vec<usize> {
+        (0..self.rows)
+            .map(|row| {
+                (0..self.cols)
+                    .filter(|&col| self.get(col, row) == Some(&Cell::Filled))
+                    .count()
+            })
+            .collect()
+    }
+
+    pub fn col_fill_counts(&self) -> Vec<usize> {
+        (0..self.cols)
+            .map(|col| {
+                (0..self.rows)
+                    .filter(|&row| self.get(col, row) == Some(&Cell::Filled))
+                    .count()
+            })
+            .collect()
+    }
+
+    // rows and cols that `max_missing` or fewer cells would complete. Full lines aren't near
+    // full, they are cleared.
+    pub fn near_full_lines(&self, max_missing: usize) -> ClearedLines {
+        let near_full = |counts: Vec<usize>, len: usize| -> Vec<usize> {
+            counts
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, filled)| filled < len && len - filled <= max_missing)
+                .map(|(line, _)| line)
+                .collect()
+        };
+        ClearedLines {
+            rows: near_full(self.row_fill_counts(), self.cols),
+            cols: near_full(self.col_fill_counts(), self.rows),
+        }
+    }
+
+    // share of filled cells in each row and col, like `fill_fraction` for single lines
+    pub fn line_fill_fractions(&self) -> (Vec<f32>, Vec<f32>) {
+        let fractions = |counts: Vec<usize>, len: usize| -> Vec<f32> {
+            counts
+                .into_iter()
+                .map(|filled| filled as f32 / len as f32)
+                .collect()
+        };
+        (
+            fractions(self.row_fill_counts(), self.cols),
+            fractions(self.col_fill_counts(), self.rows),
+        )
+    }
+
+    // rows and cols with at least `threshold` of their cells filled, full lines aside
+    pub fn lines_filled_over(&self, threshold: f32) -> ClearedLines {
+        let over = |fractions: Vec<f32>| -> Vec<usize> {
+            fractions
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, fraction)| fraction >= threshold && fraction < 1.0)
+                .map(|(line, _)| line)
+                .collect()
+        };
+        let (rows, cols) = self.line_fill_fractions();
+        ClearedLines {
+            rows: over(rows),
+            cols: over(cols),
+        }
+    }
+
+    pub fn is_row_full(&self, row: usize) -> bool {
+        (0..self.cols).all(|col| self.get(col, row) == Some(&Cell::Filled))
+    }
+
+    pub fn is_col_full(&self, col: usize) -> bool {
+        (0..self.rows).all(|row| self.get(col, row) == Some(&Cell::Filled))
+    }
+
+    pub fn shape_id(&self, col: usize, row: usize) -> Option<usize> {
+        self.index(col, row).and_then(|ix| self.shape_ids[ix])
+    }
+
+    // the placed shape the cell belongs to, None for empty and seeded cells
+    pub fn placed_shape_at(&self, col: usize, row: usize) -> Option<PlacedShape> {
+        self.shape_id(col, row).map(|id| self.placed_shapes[&id])
+    }
+
+    // what the cell is coloured by, None for empty and seeded cells
+    pub fn shape_type_at(&self, col: usize, row: usize) -> Option<BaseShapeType> {
+        self.placed_shape_at(col, row)
+            .map(|placed| placed.shape_type.base_shape_type())
+    }
+
+    // fills the cells of the shape with top-left at `origin` and remembers they belong together.
+    // Cells past the right/bottom edge wrap around, only wraparound placements get there.
+    pub fn fill_shape(&mut self, shape_type: &ShapeType, origin: &CellCoord) -> usize {
+        let id = self.next_shape_id;
+        self.next_shape_id += 1;
+        self.placed_shapes.insert(
+            id,
+            PlacedShape {
+                shape_type: *shape_type,
+                origin: *origin,
+            },
+        );
+        for cell in shape_type.cells_at(origin) {
+            let col = cell.col as usize % self.cols;
+            let row = cell.row as usize % self.rows;
+            self.set_cell(col, row, Cell::Filled);
+            if let Some(ix) = self.index(col, row) {
+                self.shape_ids[ix] = Some(id);
+            }
         }
+        id
+    }
+
+    // contiguous group of cells belonging to the same placed shape as the cell at (col, row).
+    // Neighbours wrap around the edges for shapes placed in wraparound mode.
+    pub fn shape_group(&self, col: usize, row: usize) -> Vec<CellCoord> {
+        let Some(id) = self.shape_id(col, row) else {
+            return vec![];
+        };
+
+        let mut group = vec![];
+        let mut to_visit = vec![CellCoord::new(col as i16, row as i16)];
+        while let Some(cell) = to_visit.pop() {
+            let cell = cell.wrapped(self.cols, self.rows);
+            if group.contains(&cell) {
+                continue;
+            }
+            if self.shape_id(cell.col as usize, cell.row as usize) != Some(id) {
+                continue;
+            }
+            group.push(cell);
+            to_visit.push(CellCoord::new(cell.col - 1, cell.row));
+            to_visit.push(CellCoord::new(cell.col + 1, cell.row));
+            to_visit.push(CellCoord::new(cell.col, cell.row - 1));
+            to_visit.push(CellCoord::new(cell.col, cell.row + 1));
+        }
+        group
+    }
+
+    // removes the placed shape under (col, row) from the board. Only shapes that are still
+    // whole can be lifted, i.e. none of their cells were cleared by a full row/col.
+    pub fn lift_shape(&mut self, col: usize, row: usize) -> Option<PlacedShape> {
+        let placed = self.placed_shape_at(col, row)?;
+        let group = self.shape_group(col, row);
+        if group.len() != placed.shape_type.cells().len() {
+            return None;
+        }
+        for cell in group {
+            self.set_cell(cell.col as usize, cell.row as usize, Cell::Empty);
+        }
+        self.forget_cleared_shapes();
+        Some(placed)
     }
 }
 
@@ -46,14 +403,76 @@ pub struct ShapeType {
     rotation: ShapeRot,
 }
 impl ShapeType {
+    pub fn new(base_shape_type: BaseShapeType, mirror: bool, rotation: ShapeRot) -> Self {
+        Self {
+            base_shape_type,
+            mirror,
+            rotation,
+        }
+    }
+
+    pub fn base_shape_type(&self) -> BaseShapeType {
+        self.base_shape_type
+    }
+
+    pub fn with_rotation(&self, rotation: ShapeRot) -> Self {
+        Self { rotation, ..*self }
+    }
+
+    pub fn rotated_cw(&self) -> Self {
+        self.with_rotation(self.rotation.next())
+    }
+
+    pub fn rotated_ccw(&self) -> Self {
+        self.with_rotation(self.rotation.prev())
+    }
+
+    // the shape in every `ShapeRot`, starting from `ShapeRot::No`
+    pub fn all_rotations(&self) -> Vec<ShapeType> {
+        ShapeRot::iter()
+            .map(|rot| self.with_rotation(rot))
+            .collect()
+    }
+
+    // every rotation, mirrored and not
+    pub fn all_orientations(&self) -> Vec<ShapeType> {
+        [false, true]
+            .into_iter()
+            .flat_map(|mirror| Self { mirror, ..*self }.all_rotations())
+            .collect()
+    }
+
     pub fn horizontal_cell_size(&self) -> i16 {
-        let n = self.base_shape_type.dimensions();
-        return match self.rotation {
-            ShapeRot::No => n.horizontal,
-            ShapeRot::Cw90 => n.vertical,
-            ShapeRot::Cw180 => n.horizontal,
-            ShapeRot::Cw270 => n.vertical,
+        self.dimensions().horizontal
+    }
+
+    pub fn vertical_cell_size(&self) -> i16 {
+        self.dimensions().vertical
+    }
+
+    // upside down copy of the shape: flipping vertically is mirroring and turning by 180,
+    // and mirroring turns the rotation the other way
+    pub fn flipped_vertically(&self) -> Self {
+        let rotation = match self.rotation {
+            ShapeRot::No => ShapeRot::Cw180,
+            ShapeRot::Cw90 => ShapeRot::Cw90,
+            ShapeRot::Cw180 => ShapeRot::No,
+            ShapeRot::Cw270 => ShapeRot::Cw270,
         };
+        Self {
+            mirror: !self.mirror,
+            rotation,
+            ..*self
+        }
+    }
+
+    // bounding box after rotation, i.e. the box `cells()` fits into
+    fn dimensions(&self) -> Dimension {
+        let n = self.base_shape_type.dimensions();
+        match self.rotation {
+            ShapeRot::No | ShapeRot::Cw180 => n,
+            ShapeRot::Cw90 | ShapeRot::Cw270 => Dimension::new(n.vertical, n.horizontal),
+        }
     }
 
     pub fn cells(&self) -> Vec<(usize, usize)> {
@@ -81,6 +500,15 @@ impl ShapeType {
 
         transformed_cells
     }
+
+    // board cells covered with the shape's top-left at `origin`, the held shape is drawn
+    // with the same layout
+    pub fn cells_at(&self, origin: &CellCoord) -> Vec<CellCoord> {
+        self.cells()
+            .into_iter()
+            .map(|(dx, dy)| CellCoord::new(origin.col + dx as i16, origin.row + dy as i16))
+            .collect()
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug, EnumCount, EnumIter)]
@@ -91,13 +519,40 @@ pub enum ShapeRot {
     Cw270,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug, EnumCount, EnumIter)]
+impl ShapeRot {
+    // a quarter turn clockwise
+    pub fn next(self) -> Self {
+        match self {
+            ShapeRot::No => ShapeRot::Cw90,
+            ShapeRot::Cw90 => ShapeRot::Cw180,
+            ShapeRot::Cw180 => ShapeRot::Cw270,
+            ShapeRot::Cw270 => ShapeRot::No,
+        }
+    }
+
+    // a quarter turn counter-clockwise, `next` backwards
+    pub fn prev(self) -> Self {
+        match self {
+            ShapeRot::No => ShapeRot::Cw270,
+            ShapeRot::Cw90 => ShapeRot::No,
+            ShapeRot::Cw180 => ShapeRot::Cw90,
+            ShapeRot::Cw270 => ShapeRot::Cw180,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, EnumCount, EnumIter)]
 pub enum BaseShapeType {
     T1,
     L1,
     I1,
     O,
     OO,
+    S,
+    Z,
+    J,
+    // a line of 3
+    I3,
 }
 
 struct Dimension {
@@ -114,6 +569,11 @@ impl Dimension {
 }
 
 impl BaseShapeType {
+    // the variant name, `T1`, `OO` etc., as `--practice` takes it
+    pub fn from_name(name: &str) -> Option<Self> {
+        BaseShapeType::iter().find(|shape| format!("{:?}", shape) == name.trim())
+    }
+
     fn dimensions(&self) -> Dimension {
         match self {
             BaseShapeType::T1 => Dimension::new(3, 2),
@@ -121,6 +581,10 @@ impl BaseShapeType {
             BaseShapeType::I1 => Dimension::new(1, 4),
             BaseShapeType::O => Dimension::new(1, 1),
             BaseShapeType::OO => Dimension::new(2, 2),
+            BaseShapeType::S => Dimension::new(3, 2),
+            BaseShapeType::Z => Dimension::new(3, 2),
+            BaseShapeType::J => Dimension::new(2, 3),
+            BaseShapeType::I3 => Dimension::new(1, 3),
         }
     }
 
@@ -135,6 +599,12 @@ impl BaseShapeType {
 
             BaseShapeType::O => vec![(0, 0)],
             BaseShapeType::OO => vec![(0, 0), (0, 1), (1, 0), (1, 1)],
+
+            BaseShapeType::S => vec![(1, 0), (2, 0), (0, 1), (1, 1)],
+            BaseShapeType::Z => vec![(0, 0), (1, 0), (1, 1), (2, 1)],
+            BaseShapeType::J => vec![(1, 0), (1, 1), (1, 2), (0, 2)],
+
+            BaseShapeType::I3 => vec![(0, 0), (0, 1), (0, 2)],
         };
     }
 }
@@ -166,27 +636,49 @@ impl Shape {
         }
     }
 
-    pub fn get_random_choice(n: usize) -> Vec<Shape> {
-        let mut rng = thread_rng(); // Random number generator
+    pub fn get_random_choice(rng: &mut impl Rng, n: usize) -> Vec<Shape> {
         let shapes: Vec<BaseShapeType> = BaseShapeType::iter().collect();
+        Self::get_random_choice_of(rng, n, &shapes)
+    }
 
+    // like `get_random_choice`, with the base types limited to `shapes`
+    pub fn get_random_choice_of(
+        rng: &mut impl Rng,
+        n: usize,
+        shapes: &[BaseShapeType],
+    ) -> Vec<Shape> {
         let random_shapes: Vec<ShapeType> = (0..n)
             .map(|_| {
-                let base_shape = shapes.choose(&mut rng).unwrap();
+                let base_shape = shapes.choose(rng).unwrap();
                 let mirror = rng.gen_bool(0.5);
-                let rotation = ShapeRot::iter().choose(&mut rng).unwrap();
+                let rotation = ShapeRot::iter().choose(rng).unwrap();
 
-                ShapeType {
-                    base_shape_type: *base_shape,
-                    mirror,
-                    rotation,
-                }
+                ShapeType::new(*base_shape, mirror, rotation)
+            })
+            .collect();
+
+        Self::lay_out_in_panel(random_shapes)
+    }
+
+    // like `get_random_choice`, with the base types taken out of `bag`
+    pub fn get_bag_choice(rng: &mut impl Rng, bag: &mut ShapeBag, n: usize) -> Vec<Shape> {
+        let bag_shapes: Vec<ShapeType> = (0..n)
+            .map(|_| {
+                let base_shape = bag.draw(rng);
+                let mirror = rng.gen_bool(0.5);
+                let rotation = ShapeRot::iter().choose(rng).unwrap();
+
+                ShapeType::new(base_shape, mirror, rotation)
             })
             .collect();
 
+        Self::lay_out_in_panel(bag_shapes)
+    }
+
+    pub fn lay_out_in_panel(shape_types: Vec<ShapeType>) -> Vec<Shape> {
         // Compute positions using a fold
         let mut current_col_offset = 0;
-        random_shapes
+        shape_types
             .into_iter()
             .map(|shape| {
                 let position = current_col_offset;
@@ -201,6 +693,25 @@ impl Shape {
     }
 }
 
+// every base shape type once in random order, then the next bag. Droughts and floods of one
+// shape are at most a bag long.
+#[derive(Clone, Debug, Default)]
+pub struct ShapeBag {
+    // what's left of the current bag, empty before the first draw
+    remaining: Vec<BaseShapeType>,
+}
+
+impl ShapeBag {
+    // a round can take more shapes than are left, the bag is refilled in the middle of it then
+    pub fn draw(&mut self, rng: &mut impl Rng) -> BaseShapeType {
+        if self.remaining.is_empty() {
+            self.remaining = BaseShapeType::iter().collect();
+        }
+        let ix = rng.gen_range(0..self.remaining.len());
+        self.remaining.swap_remove(ix)
+    }
+}
+
 // todo Mb split into game and UI and system state (or even input). UI is a function of a game, but game - is what the logic is derived from
 // and ui - what is actually rendered?
 // system state - is whatever we need from the user. Like mouse position/last click position etc.Mb RNG comes here.
@@ -211,20 +722,196 @@ pub struct Game {
 
     pub panel: Panel,
     pub game_state: GameState,
+    // time until the level intro ends by itself
+    pub level_intro_left: Option<Duration>,
+    // variant mode constraint applied on top of the regular placement validation
+    pub placement_rule: Option<Box<dyn PlacementRule>>,
+    pub config: GameConfig,
+    // what the last placement cleared, filled in by `ScoreCleanupSystem`
+    pub last_cleared: ClearedLines,
+    // nothing is updated while paused
+    pub pause: Option<PauseReason>,
+    // shapes placed since the start of the game, over all levels
+    pub placements: u32,
+    // game time since the last placement, for `DecaySystem`
+    pub idle: Duration,
+    // game time since the level started, for `GameConfig::score_decay_ramp`
+    pub level_time: Duration,
+    // game time since the held shape was last rotated, none before the first rotation
+    pub since_rotation: Option<Duration>,
+    // current step of the first level tutorial, none when it's off or done
+    pub tutorial: Option<TutorialStep>,
+    // over all levels, like `placements`
+    pub ledger: ScoreLedger,
+    // best games so far, loaded from and saved to the settings file by main
+    pub leaderboard: Leaderboard,
+    // the run lost once and got its entry, losing again after a retry doesn't add another.
+    // A restart is a new run.
+    pub on_leaderboard: bool,
+    // day of the daily challenge, none for a regular game
+    pub daily: Option<Date>,
+    // everything random about the game comes from here, seeded for the daily challenge
+    pub rng: StdRng,
+    // what the panels are dealt from, see `GameConfig::shape_bag`. A new level starts a new bag.
+    pub shape_bag: ShapeBag,
+    // how the level started, for `retry_level`
+    level_start: LevelStart,
+    // how the last placements found the game, the newest at the back. Only for the current
+    // level, at most `UNDO_STEPS`.
+    undo_stack: VecDeque<BoardSnapshot>,
+    // lines cleared by the last placement that are still being animated away. No placement
+    // goes through until they are gone.
+    pub clearing: Option<ClearingAnimation>,
+    // choices on the game over screen, see `GameConfig::game_over_menu`
+    pub menu: Option<MenuState>,
+    // the player chose to quit from a menu, main closes the window
+    pub quit_requested: bool,
 
     pub ui: UI,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PauseReason {
+    FocusLost,
+    // the pause key
+    Player,
+}
+
 pub struct UI {
     pub need_to_update_board: bool,
     pub need_to_update_panel: bool,
     pub lingering_frames: u8,
+    // whether the last drawn frame had the pause overlay on it
+    pub shown_paused: bool,
+    // index of the visible panel shape under the cursor
+    pub hovered_panel_shape: Option<usize>,
+    // real time left of the slow motion replay of a big clear
+    pub slow_motion: Option<Duration>,
+    // time since the board got into the danger zone, none while it's below the threshold
+    pub danger_zone: Option<Duration>,
+    // time since some line got over the edge glow threshold, none while none is
+    pub edge_glow: Option<Duration>,
+    // the held shape turning into its new orientation, the game already has the new one
+    pub rotation_animation: Option<RotationAnimation>,
+    // a new panel coming in, the static panel isn't drawn until it's done
+    pub panel_refresh: Option<PanelRefresh>,
+}
+
+impl UI {
+    // how fast animations run compared to real time
+    pub fn time_scale(&self) -> f32 {
+        match self.slow_motion {
+            Some(_) => SLOW_MOTION_TIME_SCALE,
+            None => 1.0,
+        }
+    }
+}
+
+// the game right before a placement, enough for `Game::undo` to take back the placement and
+// the clears and points that came with it
+struct BoardSnapshot {
+    board: Board,
+    // the panel before it was dealt again for an emptied one, and the bag it was dealt from
+    panel: Vec<Shape>,
+    shape_bag: ShapeBag,
+    // the shape in the hand when it was placed
+    selected_shape: Option<SelectedShape>,
+    stats: GameStats,
+    ledger: ScoreLedger,
+    placements: u32,
+    // the panels dealt after an undo are the ones the placement would have got
+    rng: StdRng,
+}
+
+const UNDO_STEPS: usize = 10;
+
+// what `Game::retry_level` needs to deal the level again
+struct LevelStart {
+    rng: StdRng,
+    board_size: usize,
+    total_score: i32,
+    ledger: ScoreLedger,
+    // board size of level 1, for `Game::restart`
+    first_board_size: usize,
+}
+
+// full lines that are still on the board, they go once `elapsed` reaches
+// `UserRenderConfig::clear_animation`
+pub struct ClearingAnimation {
+    pub lines: ClearedLines,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PanelRefreshPhase {
+    SlideOut,
+    SlideIn,
+}
+
+// the shapes of the old panel slide out, then the ones of the new panel slide in
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanelRefresh {
+    // panel cells of the shapes that were still visible on the old panel
+    pub outgoing: Vec<CellCoord>,
+    pub phase: PanelRefreshPhase,
+    pub elapsed: Duration,
 }
 
+impl PanelRefresh {
+    // nothing slides out of a panel that was used up
+    pub fn new(outgoing: Vec<CellCoord>) -> Self {
+        let phase = if outgoing.is_empty() {
+            PanelRefreshPhase::SlideIn
+        } else {
+            PanelRefreshPhase::SlideOut
+        };
+        Self {
+            outgoing,
+            phase,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    // each phase takes `phase_duration`. False once the new shapes are in.
+    pub fn advance(&mut self, dt: Duration, phase_duration: Duration) -> bool {
+        self.elapsed += dt;
+        if self.elapsed < phase_duration {
+            return true;
+        }
+        match self.phase {
+            PanelRefreshPhase::SlideOut => {
+                self.phase = PanelRefreshPhase::SlideIn;
+                self.elapsed = Duration::ZERO;
+                true
+            }
+            PanelRefreshPhase::SlideIn => false,
+        }
+    }
+
+    // how far the current phase got, from 0 to 1
+    pub fn progress(&self, phase_duration: Duration) -> f32 {
+        if phase_duration.is_zero() {
+            return 1.0;
+        }
+        (self.elapsed.as_secs_f32() / phase_duration.as_secs_f32()).min(1.0)
+    }
+}
+
+pub struct RotationAnimation {
+    // orientation before the rotation
+    pub from: ShapeType,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone)]
 pub struct SelectedShape {
     pub shape_type: ShapeType,
     //distance from selection point to top-left of the shape. So it must be always negative
     pub anchor_offset: OffsetXY,
+    // board cell the shape was picked up from, so it can be put back on deselect
+    pub picked_up_from: Option<CellCoord>,
+    // panel slot the shape was selected from, its orientation is written back there on deselect
+    pub panel_index: Option<usize>,
 }
 
 pub struct Panel {
@@ -233,17 +920,17 @@ pub struct Panel {
 }
 
 impl Panel {
-    fn from_shapes(shape_choice: Vec<Shape>) -> Self {
+    // shapes are expected to be laid out by `Shape::lay_out_in_panel`, so the cells are put
+    // at the same `col_offset_in_panel_basis` the selection anchor is computed from
+    pub fn from_shapes(shape_choice: Vec<Shape>) -> Self {
         let mut result: HashMap<CellCoord, usize> = HashMap::new();
-        let mut offset_col = 0;
-        let mut max_dx = 0;
         for (i, s) in shape_choice.iter().enumerate() {
             for (dx, dy) in s.kind.cells() {
-                result.insert(CellCoord::new((dx + offset_col) as i16, dy as i16), i);
-                max_dx = max(max_dx, dx)
+                result.insert(
+                    CellCoord::new(dx as i16 + s.col_offset_in_panel_basis, dy as i16),
+                    i,
+                );
             }
-            offset_col = offset_col + 2 + max_dx;
-            max_dx = 0;
         }
 
         return Panel {
@@ -252,98 +939,503 @@ impl Panel {
         };
     }
 
-    pub fn generate_for_3() -> Self {
-        let shapes = Shape::get_random_choice(3);
+    // `n` random shapes side by side, they fit into `Panel::cols_for(n)` columns
+    pub fn generate_for_n(rng: &mut impl Rng, n: usize) -> Self {
+        let shapes = Shape::get_random_choice(rng, n);
         Self::from_shapes(shapes)
     }
+
+    // `generate_for_n` as the rules ask for it: `GameConfig::panel_shapes` of them, out of `bag`
+    // with `GameConfig::shape_bag`, and with `GameConfig::forced_shape` every slot gets that shape
+    pub fn generate(rng: &mut impl Rng, bag: &mut ShapeBag, config: &GameConfig) -> Self {
+        let n = config.panel_shapes;
+        match config.forced_shape {
+            Some(shape) => Self::from_shapes(Shape::get_random_choice_of(rng, n, &[shape])),
+            None if config.shape_bag => Self::from_shapes(Shape::get_bag_choice(rng, bag, n)),
+            None => Self::generate_for_n(rng, n),
+        }
+    }
+
+    // columns `n` shapes take at most, when each is as wide as the longest side of any shape. The
+    // panel is drawn and clicked in this many, see `UserRenderConfig::with_panel_shapes`.
+    pub fn cols_for(n: usize) -> usize {
+        let widest = BaseShapeType::iter()
+            .map(|shape| {
+                let dimensions = shape.dimensions();
+                max(dimensions.horizontal, dimensions.vertical) as usize
+            })
+            .max()
+            .unwrap();
+        (n * (widest + 1)).saturating_sub(1)
+    }
+
+    // like `generate`, but at least one of the shapes can be placed on the game board. Only the
+    // shapes of the panel that is dealt are taken out of the bag.
+    pub fn generate_solvable(game: &mut Game) -> Self {
+        for _ in 0..SOLVABLE_PANEL_ATTEMPTS {
+            let mut bag = game.shape_bag.clone();
+            let panel = Self::generate(&mut game.rng, &mut bag, &game.config);
+            if panel
+                .shape_choice
+                .iter()
+                .any(|s| solver::has_valid_placement(game, &s.kind))
+            {
+                game.shape_bag = bag;
+                return panel;
+            }
+        }
+
+        let panel = Self::generate(&mut game.rng, &mut game.shape_bag, &game.config);
+        panel.with_placeable_shape(game)
+    }
+
+    // swaps the first shape for one the solver can place, under the placement rule and the
+    // forced shape. When there's none, e.g. on a full board, the panel stays as it is.
+    fn with_placeable_shape(self, game: &mut Game) -> Self {
+        let mut rng = game.rng.clone();
+        let placeable = solver::any_placeable_shape(game, &mut rng);
+        game.rng = rng;
+        let Some(placeable) = placeable else {
+            return self;
+        };
+        let mut shape_types: Vec<ShapeType> =
+            self.shape_choice.into_iter().map(|s| s.kind).collect();
+        shape_types[0] = placeable;
+        Self::from_shapes(Shape::lay_out_in_panel(shape_types))
+    }
+}
+
+const SOLVABLE_PANEL_ATTEMPTS: usize = 20;
+const SLOW_MOTION_TIME_SCALE: f32 = 0.3;
+
+impl Panel {
+    // swaps the kind of one shape, re-laying out the panel while keeping the shape states
+    pub fn replace_shape(&mut self, ix: usize, kind: ShapeType) {
+        let mut kinds: Vec<ShapeType> = self.shape_choice.iter().map(|s| s.kind).collect();
+        kinds[ix] = kind;
+        let mut shapes = Shape::lay_out_in_panel(kinds);
+        for (new, old) in shapes.iter_mut().zip(self.shape_choice.iter()) {
+            new.set_state(old.state.clone());
+        }
+        *self = Self::from_shapes(shapes);
+    }
+
+    // panel cells of the shapes that are still to be picked
+    pub fn visible_cells(&self) -> Vec<CellCoord> {
+        self.shapes_in_cell_space
+            .iter()
+            .filter(|(_, &ix)| {
+                self.shape_choice
+                    .get(ix)
+                    .is_some_and(|shape| shape.state == VISIBLE)
+            })
+            .map(|(coord, _)| *coord)
+            .collect()
+    }
 }
 
 impl Game {
+    #[cfg(test)]
     pub fn new_level(board_size: usize, level: u16, total_score: i32) -> Self {
+        Self::new_level_with_config(GameConfig::default(), board_size, level, total_score)
+    }
+
+    pub fn new_level_with_config(
+        config: GameConfig,
+        board_size: usize,
+        level: u16,
+        total_score: i32,
+    ) -> Self {
+        Self::new_level_with_rng(
+            config,
+            board_size,
+            level,
+            total_score,
+            StdRng::from_entropy(),
+        )
+    }
+
+    // daily challenge: everyone playing on `date` gets the same boards and panels
+    pub fn daily(board_size: usize, date: Date) -> Self {
+        let mut game = Self::new_level_with_rng(
+            GameConfig::default(),
+            board_size,
+            1,
+            0,
+            StdRng::seed_from_u64(date.seed()),
+        );
+        game.daily = Some(date);
+        game
+    }
+
+    // the level is generated from `rng`, which is kept for the panels dealt later on
+    fn new_level_with_rng(
+        config: GameConfig,
+        board_size: usize,
+        level: u16,
+        total_score: i32,
+        mut rng: StdRng,
+    ) -> Self {
+        let level_rng = rng.clone();
         // could go to level description
         let cells_filled = min(level as usize * 3 + 3, board_size * 3);
         let target_score = level as i32 * 10;
 
-        let mut rng = thread_rng();
-
-        let panel = Panel::generate_for_3();
+        let mut shape_bag = ShapeBag::default();
+        let panel = Panel::generate(&mut rng, &mut shape_bag, &config);
         let mut board = Board::new(board_size);
         // Generate unique random cell coordinates
-        let generated: Vec<(usize, usize)> = (0..board_size)
-            .flat_map(|row| (0..board_size).map(move |col| (col, row)))
-            .choose_multiple(&mut rng, cells_filled);
+        let all_cells = (0..board_size).flat_map(|row| (0..board_size).map(move |col| (col, row)));
+        let generated: Vec<(usize, usize)> = match config.symmetry {
+            None => all_cells.choose_multiple(&mut rng, cells_filled),
+            // half of the cells, each with its partner. About as many cells as without.
+            Some(symmetry) => all_cells
+                .filter(|&(col, row)| {
+                    let (partner_col, partner_row) =
+                        symmetry.partner(col, row, board_size, board_size);
+                    (row, col) <= (partner_row, partner_col)
+                })
+                .choose_multiple(&mut rng, cells_filled.div_ceil(2))
+                .into_iter()
+                .flat_map(|(col, row)| {
+                    [
+                        (col, row),
+                        symmetry.partner(col, row, board_size, board_size),
+                    ]
+                })
+                .collect(),
+        };
 
         // Fill the selected cells
-        for (col, row) in generated {
+        for &(col, row) in &generated {
             board.set_cell(col, row, Cell::Filled);
         }
+        if config.bonus_cells > 0 {
+            for &(col, row) in generated.choose_multiple(&mut rng, config.bonus_cells) {
+                board.set_bonus(col, row);
+            }
+        }
 
         let stats = GameStats {
             level,
             target_score,
             current_score: 0,
             total_score,
+            lines_cleared: 0,
+            cells_cleared: 0,
+            combo: 0,
+            best_score: 0,
         };
 
         let ui = UI {
             need_to_update_board: true,
             need_to_update_panel: true,
-            lingering_frames: 10
+            lingering_frames: 10,
+            shown_paused: false,
+            slow_motion: None,
+            danger_zone: None,
+            edge_glow: None,
+            rotation_animation: None,
+            panel_refresh: None,
+            hovered_panel_shape: None,
         };
 
-        Self {
+        let config_tutorial = config.tutorial;
+        let level_intro = config.level_intro;
+        let mut game = Self {
             board,
             selected_shape: None,
             stats,
             panel,
-            game_state: GameState::Playing,
+            game_state: match level_intro {
+                Some(_) => GameState::LevelIntro,
+                None => GameState::Playing,
+            },
+            level_intro_left: level_intro,
+            placement_rule: if config.must_touch_existing {
+                Some(Box::new(MustTouchExisting))
+            } else {
+                None
+            },
+            config,
+            last_cleared: ClearedLines::default(),
+            pause: None,
+            placements: 0,
+            idle: Duration::ZERO,
+            level_time: Duration::ZERO,
+            since_rotation: None,
+            ledger: ScoreLedger::default(),
+            leaderboard: Leaderboard::default(),
+            on_leaderboard: false,
+            tutorial: (config_tutorial && level == 1).then_some(TutorialStep::SelectShape),
+            daily: None,
+            rng,
+            shape_bag,
+            undo_stack: VecDeque::new(),
+            clearing: None,
+            menu: None,
+            quit_requested: false,
+            level_start: LevelStart {
+                rng: level_rng,
+                board_size,
+                total_score,
+                ledger: ScoreLedger::default(),
+                first_board_size: board_size,
+            },
             ui,
+        };
+        // no unplaceable panel right at the start of the level
+        if game.config.solvable_first_panel {
+            game.panel = Panel::generate_solvable(&mut game);
         }
+        game
     }
 
     pub fn go_next_level(&mut self) {
-        *self = Self::new_level(
-            self.board.size,
+        // the kept board mustn't carry full lines over
+        self.finish_clearing();
+        let first_board_size = self.level_start.first_board_size;
+        let placement_rule = self.placement_rule.take();
+        let placements = self.placements;
+        let ledger = self.ledger;
+        let leaderboard = std::mem::take(&mut self.leaderboard);
+        let on_leaderboard = self.on_leaderboard;
+        let daily = self.daily;
+        let rng = self.rng.clone();
+        let best_score = self.stats.best_score;
+        let grow = self.config.board_growth && self.board.cols < self.config.board_growth_max_size;
+        let mut board = std::mem::replace(&mut self.board, Board::new(0));
+        let next_size = if grow { board.cols + 1 } else { board.cols };
+        *self = Self::new_level_with_rng(
+            self.config.clone(),
+            next_size,
             self.stats.level + 1,
             self.stats.total_score,
+            rng,
         );
+        if grow {
+            board.grow(1, 1);
+            self.board = board;
+            if self.config.solvable_first_panel {
+                self.panel = Panel::generate_solvable(self);
+            }
+        }
+        self.placement_rule = placement_rule;
+        self.placements = placements;
+        self.ledger = ledger;
+        self.leaderboard = leaderboard;
+        self.on_leaderboard = on_leaderboard;
+        self.daily = daily;
+        self.stats.best_score = best_score;
+        self.level_start.ledger = ledger;
+        self.level_start.first_board_size = first_board_size;
+        if grow {
+            // the grown board was kept, a retry deals a fresh one of its size
+            self.level_start.board_size = self.board.cols;
+        }
     }
 
-    pub fn is_valid_placement(&self, shape: &ShapeType, cell_coord: &CellCoord) -> bool {
-        if cell_coord.col < 0 || cell_coord.row < 0 {
-            return false;
-        }
+    // starts the level on `board` instead of the dealt one, e.g. a shared one. Retrying or
+    // restarting deals boards of its size from then on.
+    pub fn replace_board(&mut self, board: Board) {
+        self.level_start.board_size = board.cols;
+        self.level_start.first_board_size = board.cols;
+        self.board = board;
+        self.ui.need_to_update_board = true;
+    }
+
+    // deals the current level again, as it was when it started: same board, panels and target.
+    // Everything scored during the level is taken back.
+    pub fn retry_level(&mut self) {
+        println!("Retrying level {}", self.stats.level);
+        let placement_rule = self.placement_rule.take();
+        let placements = self.placements;
+        let leaderboard = std::mem::take(&mut self.leaderboard);
+        let on_leaderboard = self.on_leaderboard;
+        let daily = self.daily;
+        let start_rng = self.level_start.rng.clone();
+        let ledger = self.level_start.ledger;
+        let first_board_size = self.level_start.first_board_size;
+        let best_score = self.stats.best_score;
+        *self = Self::new_level_with_rng(
+            self.config.clone(),
+            self.level_start.board_size,
+            self.stats.level,
+            self.level_start.total_score,
+            start_rng,
+        );
+        self.placement_rule = placement_rule;
+        self.placements = placements;
+        self.ledger = ledger;
+        self.level_start.ledger = ledger;
+        self.level_start.first_board_size = first_board_size;
+        self.leaderboard = leaderboard;
+        self.on_leaderboard = on_leaderboard;
+        self.daily = daily;
+        self.stats.best_score = best_score;
+    }
+
+    // a new run from level 1 with the same rules. The daily challenge starts over with the same
+    // levels, a regular game with new ones.
+    pub fn restart(&mut self) {
+        println!("Restarting from level 1");
+        let placement_rule = self.placement_rule.take();
+        let leaderboard = std::mem::take(&mut self.leaderboard);
+        let daily = self.daily;
+        let best_score = self.stats.best_score;
+        let rng = match daily {
+            Some(date) => StdRng::seed_from_u64(date.seed()),
+            None => StdRng::from_entropy(),
+        };
+        *self = Self::new_level_with_rng(
+            self.config.clone(),
+            self.level_start.first_board_size,
+            1,
+            0,
+            rng,
+        );
+        self.placement_rule = placement_rule;
+        self.leaderboard = leaderboard;
+        self.daily = daily;
+        self.stats.best_score = best_score;
+    }
+
+    // shape as it's stored in the board for a shape as the player sees it, and the other way round.
+    // With the origin at the bottom the board is drawn upside down, so are the shapes in it.
+    pub fn board_shape(&self, shape: &ShapeType) -> ShapeType {
+        match self.config.board_origin {
+            BoardOrigin::TopLeft => *shape,
+            BoardOrigin::BottomLeft => shape.flipped_vertically(),
+        }
+    }
+
+    // shape and top-left board cell to place, for the shape seen with its top-left at `visual_cell`
+    pub fn board_placement(
+        &self,
+        shape: &ShapeType,
+        visual_cell: &CellCoord,
+    ) -> (ShapeType, CellCoord) {
+        match self.config.board_origin {
+            BoardOrigin::TopLeft => (*shape, *visual_cell),
+            BoardOrigin::BottomLeft => {
+                let row = self.board.rows as i16 - visual_cell.row - shape.vertical_cell_size();
+                (
+                    shape.flipped_vertically(),
+                    CellCoord::new(visual_cell.col, row),
+                )
+            }
+        }
+    }
+
+    // pauses when the window loses focus and resumes when it's back, unless paused for another reason
+    pub fn focus_changed(&mut self, focused: bool) {
+        match (focused, self.pause) {
+            (false, None)
+                if self.config.pause_on_focus_loss && self.game_state == GameState::Playing =>
+            {
+                self.pause = Some(PauseReason::FocusLost);
+            }
+            (true, Some(PauseReason::FocusLost)) => self.pause = None,
+            _ => {}
+        }
+    }
+
+    // only a game in play can be paused. Any pause ends with it, the one for losing focus too.
+    pub fn toggle_pause(&mut self) {
+        self.pause = match self.pause {
+            Some(_) => None,
+            None if self.game_state == GameState::Playing => Some(PauseReason::Player),
+            None => None,
+        };
+    }
+
+    // rows and cols that would be full with the shape placed, without placing it
+    pub fn lines_completed_by(&self, shape: &ShapeType, cell_coord: &CellCoord) -> ClearedLines {
+        let board = &self.board;
+        let covered: Vec<(usize, usize)> = shape
+            .cells_at(cell_coord)
+            .iter()
+            .map(|c| c.wrapped(board.cols, board.rows))
+            .map(|c| (c.col as usize, c.row as usize))
+            .collect();
+        let filled = |col: usize, row: usize| {
+            board.get(col, row) == Some(&Cell::Filled) || covered.contains(&(col, row))
+        };
+        ClearedLines {
+            rows: (0..board.rows)
+                .filter(|&row| (0..board.cols).all(|col| filled(col, row)))
+                .collect(),
+            cols: (0..board.cols)
+                .filter(|&col| (0..board.rows).all(|row| filled(col, row)))
+                .collect(),
+        }
+    }
+
+    pub fn is_valid_placement(&self, shape: &ShapeType, cell_coord: &CellCoord) -> bool {
+        let cell_coord = if self.config.wraparound {
+            cell_coord.wrapped(self.board.cols, self.board.rows)
+        } else {
+            *cell_coord
+        };
+        if cell_coord.col < 0 || cell_coord.row < 0 {
+            return false;
+        }
         let col = cell_coord.col.to_usize().unwrap();
         let row = cell_coord.row.to_usize().unwrap();
+        let mut target_cells = Vec::new();
         for (dx, dy) in shape.cells() {
-            let nx = col.wrapping_add(dx);
-            let ny = row.wrapping_add(dy);
-            if nx >= self.board.size || ny >= self.board.size {
+            let mut nx = col.wrapping_add(dx);
+            let mut ny = row.wrapping_add(dy);
+            if self.config.wraparound {
+                nx %= self.board.cols;
+                ny %= self.board.rows;
+            }
+            if nx >= self.board.cols || ny >= self.board.rows {
                 return false;
             }
 
             if self.board.get(nx, ny).is_none_or(|x| x == &Cell::Filled) {
                 return false;
             }
+            target_cells.push(CellCoord::new(nx as i16, ny as i16));
         }
-        true
+
+        self.placement_rule
+            .as_ref()
+            .is_none_or(|rule| rule.allows(&self.board, &target_cells))
+    }
+
+    // whether a click would place the shape now. Lines still being cleared would be cleared
+    // again, so nothing is placed until they are gone.
+    pub fn accepts_placement(&self, shape: &ShapeType, cell_coord: &CellCoord) -> bool {
+        self.clearing.is_none() && self.is_valid_placement(shape, cell_coord)
     }
 
     pub fn place_shape(&mut self, shape_type: &ShapeType, cell_coord: &CellCoord) {
         assert!(
             cell_coord.row >= 0
-                && cell_coord.row < self.board.size.to_i16().unwrap()
+                && cell_coord.row < self.board.rows.to_i16().unwrap()
                 && cell_coord.col >= 0
-                && cell_coord.col < self.board.size.to_i16().unwrap(),
+                && cell_coord.col < self.board.cols.to_i16().unwrap(),
             "error placing cell out of the board {:?}",
             cell_coord
         );
-        for (dx, dy) in shape_type.cells() {
-            let col = cell_coord.col as usize + dx;
-            let row = cell_coord.row as usize + dy;
-
-            self.board.set_cell(col, row, Cell::Filled);
+        // taken before the placement, so `ScoreCleanupSystem` clearing lines afterwards and the
+        // panel getting dealt again are undone with it
+        if self.undo_stack.len() == UNDO_STEPS {
+            self.undo_stack.pop_front();
         }
+        self.undo_stack.push_back(BoardSnapshot {
+            board: self.board.clone(),
+            panel: self.panel.shape_choice.clone(),
+            shape_bag: self.shape_bag.clone(),
+            selected_shape: self.selected_shape.clone(),
+            stats: self.stats.clone(),
+            ledger: self.ledger,
+            placements: self.placements,
+            rng: self.rng.clone(),
+        });
+        self.board.fill_shape(shape_type, cell_coord);
 
         self.selected_shape = None;
         for s in self.panel.shape_choice.iter_mut() {
@@ -353,8 +1445,59 @@ impl Game {
         }
     }
 
+    // takes back the last placement, returns false when there's nothing left to take back. A
+    // shape from the panel goes back to its slot, one lifted off the board back into the hand.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop_back() else {
+            return false;
+        };
+        self.board = snapshot.board;
+        self.board.changed.fill(true);
+        let mut shapes = snapshot.panel;
+        for shape in shapes.iter_mut() {
+            if shape.state == ShapeState::SELECTED {
+                shape.set_state(ShapeState::VISIBLE);
+            }
+        }
+        self.panel = Panel::from_shapes(shapes);
+        self.shape_bag = snapshot.shape_bag;
+        self.selected_shape = snapshot
+            .selected_shape
+            .filter(|selected| selected.picked_up_from.is_some());
+        self.stats = snapshot.stats;
+        self.ledger = snapshot.ledger;
+        self.placements = snapshot.placements;
+        self.rng = snapshot.rng;
+        self.last_cleared = ClearedLines::default();
+        self.clearing = None;
+        self.ui.need_to_update_board = true;
+        self.ui.need_to_update_panel = true;
+        true
+    }
+
     pub fn deselect(&mut self) {
-        self.selected_shape = None;
+        match self.selected_shape.take() {
+            Some(SelectedShape {
+                shape_type,
+                picked_up_from: Some(origin),
+                ..
+            }) => {
+                // the cells it was lifted from are still free, nothing is placed while holding a shape
+                let board_shape = self.board_shape(&shape_type);
+                self.board.fill_shape(&board_shape, &origin);
+                self.ui.need_to_update_board = true;
+            }
+            // turned while it was held
+            Some(SelectedShape {
+                shape_type,
+                panel_index: Some(ix),
+                ..
+            }) if self.panel.shape_choice[ix].kind != shape_type => {
+                self.panel.replace_shape(ix, shape_type);
+                self.ui.need_to_update_panel = true;
+            }
+            _ => {}
+        }
 
         for s in self.panel.shape_choice.iter_mut() {
             if s.state == ShapeState::SELECTED {
@@ -363,44 +1506,182 @@ impl Game {
         }
     }
 
-    pub fn clean_row(&mut self, row: usize) {
-        for col in 0..self.board.size {
-            self.board.set_cell(col, row, Cell::Empty)
+    // when the visible shapes have almost no valid placements left, gives the player a shape that
+    // fits instead of the least placeable one
+    pub fn reroll_unplaceable_shape(&mut self) -> bool {
+        let counts: Vec<(usize, usize)> = self
+            .panel
+            .shape_choice
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.state == VISIBLE)
+            .map(|(ix, s)| (ix, solver::valid_placements(self, &s.kind).len()))
+            .collect();
+        let total: usize = counts.iter().map(|(_, n)| n).sum();
+        if counts.is_empty() || total >= self.config.anti_frustration_threshold {
+            return false;
         }
+
+        let (ix, _) = *counts.iter().min_by_key(|(_, n)| *n).unwrap();
+        // from the game's own rng, so a seeded game gets the same shape every time
+        let mut rng = self.rng.clone();
+        let kind = solver::any_placeable_shape(self, &mut rng);
+        self.rng = rng;
+        let Some(kind) = kind else {
+            return false;
+        };
+        println!("Rerolling shape {:?} into {:?}", ix, kind);
+        self.panel.replace_shape(ix, kind);
+        self.ui.need_to_update_panel = true;
+        true
     }
 
-    pub fn clean_col(&mut self, col: usize) {
-        for row in 0..self.board.size {
-            self.board.set_cell(col, row, Cell::Empty)
+    // picks up a whole placed shape from the board and makes it the selected one, at a score cost
+    pub fn pick_up_shape(&mut self, cell_coord: &CellCoord, anchor_offset: OffsetXY) -> bool {
+        if !self.config.pick_up_placed_shapes
+            || self.selected_shape.is_some()
+            || cell_coord.col < 0
+            || cell_coord.row < 0
+        {
+            return false;
+        }
+        let Some(placed) = self
+            .board
+            .lift_shape(cell_coord.col as usize, cell_coord.row as usize)
+        else {
+            return false;
+        };
+
+        self.selected_shape = Some(SelectedShape {
+            shape_type: self.board_shape(&placed.shape_type),
+            anchor_offset,
+            picked_up_from: Some(placed.origin),
+            panel_index: None,
+        });
+        self.stats.current_score = max(0, self.stats.current_score - self.config.pick_up_cost);
+        let total_before = self.stats.total_score;
+        self.stats.total_score = max(0, self.stats.total_score - self.config.pick_up_cost);
+        self.ledger.pick_ups -= total_before - self.stats.total_score;
+        self.ui.need_to_update_board = true;
+        true
+    }
+
+    // empties the lines of a running clear animation right away
+    pub fn finish_clearing(&mut self) {
+        if let Some(clearing) = self.clearing.take() {
+            self.board.clear_lines(&clearing.lines);
+            self.ui.need_to_update_board = true;
         }
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum GameState {
+    // the starting board is shown as a thumbnail, see `GameConfig::level_intro`
+    LevelIntro,
     Playing,
     GameOver,
     MoveToNextLevel,
 }
 
+#[derive(Clone)]
 pub struct GameStats {
     pub level: u16,
     pub target_score: i32,
     pub current_score: i32,
     pub total_score: i32,
+    // cleared during the current level
+    pub lines_cleared: u32,
+    pub cells_cleared: u32,
+    // placements in a row that cleared something, see `GameConfig::combo_scoring`
+    pub combo: u32,
+    // highest `total_score` of the games before, kept in `high_score::high_score_path`
+    pub best_score: i32,
+}
+
+// where the points of the session came from, for the game over summary. Sums up to the total
+// score of a game started from zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ScoreLedger {
+    // one point per cell of every cleared line
+    pub cells: i32,
+    // bonus for clearing rows and cols with the same placement
+    pub multi_clear: i32,
+    // extra points for clears in a row
+    pub combo: i32,
+    pub perfect_clear: i32,
+    // bonus objectives, see `patterns::Pattern`
+    pub patterns: i32,
+    // placements that kept the board symmetric, see `GameConfig::symmetry`
+    pub symmetry: i32,
+    pub soft_drop: i32,
+    // cleared bonus cells, see `GameConfig::bonus_cells`
+    pub bonus_cells: i32,
+    // negative, what picking placed shapes back up cost
+    pub pick_ups: i32,
+    // negative, what idling cost, see `GameConfig::score_decay_grace`
+    pub decay: i32,
+}
+
+impl ScoreLedger {
+    pub fn total(&self) -> i32 {
+        self.cells
+            + self.multi_clear
+            + self.combo
+            + self.perfect_clear
+            + self.patterns
+            + self.symmetry
+            + self.bonus_cells
+            + self.soft_drop
+            + self.pick_ups
+            + self.decay
+    }
+
+    // points added since `before`, per category
+    #[cfg(test)]
+    pub fn since(&self, before: &ScoreLedger) -> ScoreLedger {
+        ScoreLedger {
+            cells: self.cells - before.cells,
+            multi_clear: self.multi_clear - before.multi_clear,
+            combo: self.combo - before.combo,
+            perfect_clear: self.perfect_clear - before.perfect_clear,
+            patterns: self.patterns - before.patterns,
+            symmetry: self.symmetry - before.symmetry,
+            bonus_cells: self.bonus_cells - before.bonus_cells,
+            soft_drop: self.soft_drop - before.soft_drop,
+            pick_ups: self.pick_ups - before.pick_ups,
+            decay: self.decay - before.decay,
+        }
+    }
+
+    pub fn breakdown(&self) -> Vec<(&'static str, i32)> {
+        vec![
+            ("Cells", self.cells),
+            ("Multi-clears", self.multi_clear),
+            ("Combos", self.combo),
+            ("Perfect clears", self.perfect_clear),
+            ("Patterns", self.patterns),
+            ("Symmetry", self.symmetry),
+            ("Bonus cells", self.bonus_cells),
+            ("Soft drops", self.soft_drop),
+            ("Pick-ups", self.pick_ups),
+            ("Idle decay", self.decay),
+        ]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::game_entities::BaseShapeType;
+    use strum::EnumCount;
 
     use super::*;
 
     #[test]
     fn test_shapes_as_grid() {
         let shapes = vec![
-            Shape::new(BaseShapeType::I2, 0),
-            Shape::new(BaseShapeType::OO, 0),
+            Shape::new(ShapeType::new(BaseShapeType::I1, false, ShapeRot::Cw90), 0),
+            Shape::new(ShapeType::new(BaseShapeType::OO, false, ShapeRot::No), 5),
         ];
 
         let result = Panel::from_shapes(shapes);
@@ -412,12 +1693,766 @@ mod tests {
             (CellCoord::new(2, 0), 0),
             (CellCoord::new(3, 0), 0),
             // Second shape (O) should be placed with an offset
-            (CellCoord::new(5, 0), 0),
-            (CellCoord::new(5, 1), 0),
-            (CellCoord::new(6, 0), 0),
-            (CellCoord::new(6, 1), 0),
+            (CellCoord::new(5, 0), 1),
+            (CellCoord::new(5, 1), 1),
+            (CellCoord::new(6, 0), 1),
+            (CellCoord::new(6, 1), 1),
         ]);
 
         assert_eq!(result.shapes_in_cell_space, expected);
     }
+
+    fn game_with_empty_board(size: usize) -> Game {
+        let mut game = Game::new_level(size, 1, 0);
+        game.board = Board::new(size);
+        game.config.pick_up_placed_shapes = true;
+        game
+    }
+
+    #[test]
+    fn test_shape_group_of_placed_shape() {
+        let mut game = game_with_empty_board(6);
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.place_shape(&l_shape, &CellCoord::new(0, 0));
+        // touches the L, but is a different shape
+        game.place_shape(&square, &CellCoord::new(1, 0));
+
+        let mut group = game.board.shape_group(0, 1);
+        group.sort_by_key(|c| (c.col, c.row));
+        assert_eq!(
+            group,
+            vec![
+                CellCoord::new(0, 0),
+                CellCoord::new(0, 1),
+                CellCoord::new(0, 2),
+                CellCoord::new(1, 2),
+            ]
+        );
+        assert_eq!(game.board.shape_group(1, 1).len(), 4);
+        assert!(game.board.shape_group(5, 5).is_empty());
+    }
+
+    #[test]
+    fn test_pick_up_removes_shape_cleanly() {
+        let mut game = game_with_empty_board(6);
+        game.stats.current_score = 5;
+        game.stats.total_score = 5;
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.place_shape(&l_shape, &CellCoord::new(0, 0));
+        game.place_shape(&square, &CellCoord::new(1, 0));
+
+        assert!(game.pick_up_shape(&CellCoord::new(1, 2), OffsetXY(0, 0)));
+
+        for (col, row) in [(0, 0), (0, 1), (0, 2), (1, 2)] {
+            assert_eq!(game.board.get(col, row), Some(&Cell::Empty));
+            assert_eq!(game.board.shape_id(col, row), None);
+        }
+        for (col, row) in [(1, 0), (2, 0), (1, 1), (2, 1)] {
+            assert_eq!(game.board.get(col, row), Some(&Cell::Filled));
+        }
+        let selected = game.selected_shape.as_ref().unwrap();
+        assert_eq!(selected.shape_type, l_shape);
+        assert_eq!(selected.picked_up_from, Some(CellCoord::new(0, 0)));
+        assert_eq!(game.stats.current_score, 5 - game.config.pick_up_cost);
+        assert_eq!(game.ledger.pick_ups, -game.config.pick_up_cost);
+
+        // deselecting puts it back where it was
+        game.deselect();
+        assert_eq!(game.board.get(0, 2), Some(&Cell::Filled));
+        assert_eq!(game.board.shape_group(0, 2).len(), 4);
+    }
+
+    #[test]
+    fn test_focus_loss_pauses_until_focus_is_back() {
+        let mut game = game_with_empty_board(6);
+
+        game.focus_changed(false);
+        assert_eq!(game.pause, Some(PauseReason::FocusLost));
+        game.focus_changed(false);
+        assert_eq!(game.pause, Some(PauseReason::FocusLost));
+        game.focus_changed(true);
+        assert_eq!(game.pause, None);
+
+        game.config.pause_on_focus_loss = false;
+        game.focus_changed(false);
+        assert_eq!(game.pause, None);
+    }
+
+    #[test]
+    fn test_deselect_writes_rotation_back_to_panel_slot() {
+        let mut game = game_with_empty_board(6);
+        let t_shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        game.panel = panel_of(vec![l_shape, t_shape, l_shape]);
+        game.panel.shape_choice[1].set_state(ShapeState::SELECTED);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: t_shape.with_rotation(ShapeRot::Cw90),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: Some(1),
+        });
+
+        game.deselect();
+
+        assert_eq!(
+            game.panel.shape_choice[1].kind,
+            t_shape.with_rotation(ShapeRot::Cw90)
+        );
+        assert_eq!(game.panel.shape_choice[1].state, ShapeState::VISIBLE);
+        assert_eq!(game.panel.shape_choice[0].kind, l_shape);
+        assert_eq!(game.panel.shape_choice[2].kind, l_shape);
+    }
+
+    #[test]
+    fn test_all_rotations_of_t_shape() {
+        let shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::Cw180);
+
+        let cells: Vec<Vec<(usize, usize)>> = shape
+            .all_rotations()
+            .iter()
+            .map(|rotated| {
+                let mut cells = rotated.cells();
+                cells.sort();
+                cells
+            })
+            .collect();
+
+        // .#.    .#    ###    #.
+        // ###    ##    .#.    ##
+        //        .#           #.
+        assert_eq!(
+            cells,
+            vec![
+                vec![(0, 1), (1, 0), (1, 1), (2, 1)],
+                vec![(0, 1), (1, 0), (1, 1), (1, 2)],
+                vec![(0, 0), (1, 0), (1, 1), (2, 0)],
+                vec![(0, 0), (0, 1), (0, 2), (1, 1)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_shapes_stay_within_their_bounding_box() {
+        for base in [
+            BaseShapeType::S,
+            BaseShapeType::Z,
+            BaseShapeType::J,
+            BaseShapeType::I3,
+        ] {
+            for shape in ShapeType::new(base, false, ShapeRot::No).all_orientations() {
+                let cells = shape.cells();
+                let (w, h) = (
+                    shape.horizontal_cell_size() as usize,
+                    shape.vertical_cell_size() as usize,
+                );
+
+                let mut distinct = cells.clone();
+                distinct.sort();
+                distinct.dedup();
+                assert_eq!(distinct.len(), base.cells().len(), "{:?}", shape);
+                assert!(cells.iter().all(|&(x, y)| x < w && y < h), "{:?}", shape);
+                // the box is tight, every column and row of it has a cell
+                assert!((0..w).all(|col| cells.iter().any(|&(x, _)| x == col)));
+                assert!((0..h).all(|row| cells.iter().any(|&(_, y)| y == row)));
+            }
+        }
+
+        // .##    #.
+        // ##.    ##
+        //        .#
+        let mut s = ShapeType::new(BaseShapeType::S, false, ShapeRot::Cw90).cells();
+        s.sort();
+        assert_eq!(s, vec![(0, 0), (0, 1), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn test_board_to_ascii() {
+        let mut board = Board::new(3);
+        board.set_cell(0, 0, Cell::Filled);
+        board.set_cell(2, 1, Cell::Filled);
+        board.set_cell(1, 2, Cell::Filled);
+
+        assert_eq!(board.to_ascii(), "#..\n..#\n.#.\n");
+    }
+
+    #[test]
+    fn test_flipped_vertically_is_upside_down() {
+        for shape in all_shape_types() {
+            let h = shape.vertical_cell_size() as usize;
+            let mut expected: Vec<(usize, usize)> =
+                shape.cells().iter().map(|&(x, y)| (x, h - 1 - y)).collect();
+            let mut flipped = shape.flipped_vertically().cells();
+            expected.sort();
+            flipped.sort();
+
+            assert_eq!(flipped, expected, "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn test_fill_fraction_of_half_filled_board() {
+        let mut board = Board::new(4);
+        assert_eq!(board.fill_fraction(), 0.0);
+        for row in 0..2 {
+            for col in 0..4 {
+                board.set_cell(col, row, Cell::Filled);
+            }
+        }
+
+        assert_eq!(board.fill_fraction(), 0.5);
+    }
+
+    #[test]
+    fn test_board_from_ascii() {
+        let board = Board::from_ascii("#..\n..#\n").unwrap();
+
+        assert_eq!((board.cols, board.rows), (3, 2));
+        assert_eq!(board.to_ascii(), "#..\n..#\n");
+        assert!(Board::from_ascii("#..\n.#\n").is_none());
+        assert!(Board::from_ascii("#x.\n").is_none());
+    }
+
+    #[test]
+    fn test_grow_keeps_filled_cells() {
+        let mut board = Board::new(3);
+        board.set_cell(0, 0, Cell::Filled);
+        board.set_cell(2, 1, Cell::Filled);
+        board.set_cell(1, 2, Cell::Filled);
+
+        board.grow(1, 2);
+
+        assert_eq!(board.cols, 5);
+        assert_eq!(board.rows, 4);
+        assert_eq!(board.to_ascii(), "#....\n..#..\n.#...\n.....\n");
+    }
+
+    #[test]
+    fn test_panel_refresh_slides_out_then_in() {
+        let phase = Duration::from_millis(100);
+        let mut refresh = PanelRefresh::new(vec![CellCoord::new(0, 0)]);
+        assert_eq!(refresh.phase, PanelRefreshPhase::SlideOut);
+
+        assert!(refresh.advance(Duration::from_millis(40), phase));
+        assert!((refresh.progress(phase) - 0.4).abs() < 1e-6);
+        assert!(refresh.advance(Duration::from_millis(60), phase));
+        assert_eq!(refresh.phase, PanelRefreshPhase::SlideIn);
+        assert_eq!(refresh.progress(phase), 0.0);
+
+        assert!(refresh.advance(Duration::from_millis(99), phase));
+        assert!(!refresh.advance(Duration::from_millis(1), phase));
+
+        // a used up panel has nothing to slide out
+        let refresh = PanelRefresh::new(vec![]);
+        assert_eq!(refresh.phase, PanelRefreshPhase::SlideIn);
+    }
+
+    #[test]
+    fn test_near_full_lines_are_one_cell_from_full() {
+        let board = Board::from_ascii("###.\n####\n#...\n#.##\n").unwrap();
+
+        assert_eq!(board.row_fill_counts(), vec![3, 4, 1, 3]);
+        assert_eq!(board.col_fill_counts(), vec![4, 2, 3, 2]);
+        let one_away = board.near_full_lines(1);
+        assert_eq!(one_away.rows, vec![0, 3]);
+        assert_eq!(one_away.cols, vec![2]);
+
+        let two_away = board.near_full_lines(2);
+        assert_eq!(two_away.rows, vec![0, 3]);
+        assert_eq!(two_away.cols, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_lines_over_the_glow_threshold() {
+        let board = Board::from_ascii("####.\n###..\n#####\n.....\n####.\n").unwrap();
+
+        let (rows, cols) = board.line_fill_fractions();
+        assert_eq!(rows, vec![0.8, 0.6, 1.0, 0.0, 0.8]);
+        assert_eq!(cols[0], 0.8);
+
+        let glowing = board.lines_filled_over(0.8);
+        // the full row is about to be cleared, it doesn't glow
+        assert_eq!(glowing.rows, vec![0, 4]);
+        assert_eq!(glowing.cols, vec![0, 1, 2]);
+        assert_eq!(board.lines_filled_over(0.6).rows, vec![0, 1, 4]);
+        assert!(Board::new(5).lines_filled_over(0.8).is_empty());
+    }
+
+    #[test]
+    fn test_board_symmetry() {
+        let mirrored = Board::from_ascii("#..#\n.##.\n#...\n").unwrap();
+        assert!(!mirrored.is_symmetric(Symmetry::Mirror));
+        let mirrored = Board::from_ascii("#..#\n.##.\n#..#\n").unwrap();
+        assert!(mirrored.is_symmetric(Symmetry::Mirror));
+        assert!(mirrored.is_symmetric(Symmetry::Rotational));
+
+        let turned = Board::from_ascii("##.\n...\n.##\n").unwrap();
+        assert!(turned.is_symmetric(Symmetry::Rotational));
+        assert!(!turned.is_symmetric(Symmetry::Mirror));
+        let off_center = Board::from_ascii("##.\n.#.\n##.\n").unwrap();
+        assert!(!off_center.is_symmetric(Symmetry::Rotational));
+        assert!(Board::new(5).is_symmetric(Symmetry::Mirror));
+    }
+
+    #[test]
+    fn test_symmetric_mode_seeds_symmetric_boards() {
+        for symmetry in [Symmetry::Mirror, Symmetry::Rotational] {
+            let config = GameConfig {
+                symmetry: Some(symmetry),
+                ..GameConfig::default()
+            };
+            for level in 1..6 {
+                let game = Game::new_level_with_config(config.clone(), 7, level, 0);
+                assert!(
+                    game.board.is_symmetric(symmetry),
+                    "{}",
+                    game.board.to_ascii()
+                );
+                assert!(game.board.filled_count() > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bonus_cells_are_seeded_on_filled_cells() {
+        let config = GameConfig {
+            bonus_cells: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::new_level_with_config(config, 7, 3, 0);
+
+        let bonus = game.board.bonus_cells();
+        assert_eq!(bonus.len(), 2);
+        assert!(bonus
+            .iter()
+            .all(|&(col, row)| game.board.get(col, row) == Some(&Cell::Filled)));
+    }
+
+    #[test]
+    fn test_next_level_grows_board_when_enabled() {
+        let mut game = game_with_empty_board(5);
+        game.config.board_growth = true;
+        game.board.set_cell(4, 4, Cell::Filled);
+
+        game.go_next_level();
+
+        assert_eq!((game.board.cols, game.board.rows), (6, 6));
+        assert_eq!(game.board.get(4, 4), Some(&Cell::Filled));
+        assert_eq!(game.stats.level, 2);
+    }
+
+    fn full_board_with_hole(size: usize, col: usize, row: usize) -> Board {
+        let mut board = Board::new(size);
+        for r in 0..size {
+            for c in 0..size {
+                board.set_cell(c, r, Cell::Filled);
+            }
+        }
+        board.set_cell(col, row, Cell::Empty);
+        board
+    }
+
+    fn panel_of(kinds: Vec<ShapeType>) -> Panel {
+        Panel::from_shapes(Shape::lay_out_in_panel(kinds))
+    }
+
+    fn all_shape_types() -> Vec<ShapeType> {
+        BaseShapeType::iter()
+            .flat_map(|base| {
+                ShapeRot::iter().flat_map(move |rot| {
+                    [false, true]
+                        .into_iter()
+                        .map(move |mirror| ShapeType::new(base, mirror, rot))
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rotated_shapes_do_not_overlap_in_panel() {
+        for first in all_shape_types() {
+            for second in all_shape_types() {
+                let panel = panel_of(vec![first, second, first]);
+                let total_cells = 2 * first.cells().len() + second.cells().len();
+                assert_eq!(panel.shapes_in_cell_space.len(), total_cells);
+
+                for (ix, shape) in panel.shape_choice.iter().enumerate() {
+                    let cols: Vec<i16> = panel
+                        .shapes_in_cell_space
+                        .iter()
+                        .filter(|(_, &i)| i == ix)
+                        .map(|(c, _)| c.col)
+                        .collect();
+                    let min_col = *cols.iter().min().unwrap();
+                    let max_col = *cols.iter().max().unwrap();
+                    assert_eq!(min_col, shape.col_offset_in_panel_basis);
+                    assert_eq!(max_col - min_col + 1, shape.kind.horizontal_cell_size());
+                    if let Some(next) = panel.shape_choice.get(ix + 1) {
+                        assert!(max_col < next.col_offset_in_panel_basis - 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_near_dead_panel_gets_placeable_shape() {
+        let mut game = Game::new_level(5, 1, 0);
+        game.config.anti_frustration = true;
+        game.board = full_board_with_hole(5, 3, 1);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.panel = panel_of(vec![square, square, square]);
+
+        assert!(game.reroll_unplaceable_shape());
+
+        assert_eq!(
+            game.panel
+                .shape_choice
+                .iter()
+                .filter(|s| solver::has_valid_placement(&game, &s.kind))
+                .count(),
+            1
+        );
+        assert!(game.panel.shape_choice.iter().all(|s| s.state == VISIBLE));
+    }
+
+    #[test]
+    fn test_reroll_follows_the_game_seed() {
+        let rerolled = || {
+            let mut game = Game::daily(
+                5,
+                Date {
+                    year: 2024,
+                    month: 3,
+                    day: 1,
+                },
+            );
+            game.config.anti_frustration = true;
+            game.board = full_board_with_hole(5, 3, 1);
+            let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+            game.panel = panel_of(vec![square, square, square]);
+            assert!(game.reroll_unplaceable_shape());
+            game.panel.shape_choice[0].kind
+        };
+
+        assert!((0..10).all(|_| rerolled() == rerolled()));
+    }
+
+    // only allows shapes of at least that many cells
+    struct AtLeastCells(usize);
+    impl PlacementRule for AtLeastCells {
+        fn allows(&self, _board: &Board, target_cells: &[CellCoord]) -> bool {
+            target_cells.len() >= self.0
+        }
+    }
+
+    #[test]
+    fn test_fallback_panel_shape_follows_the_placement_rule() {
+        let mut game = Game::new_level(5, 1, 0);
+        game.board = full_board_with_hole(5, 1, 2);
+        game.board.set_cell(2, 2, Cell::Empty);
+        game.board.set_cell(3, 2, Cell::Empty);
+        game.placement_rule = Some(Box::new(AtLeastCells(2)));
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        let panel = panel_of(vec![single, single, single]).with_placeable_shape(&mut game);
+
+        // a single cell fits the hole, but only the line of 3 is allowed
+        assert_eq!(
+            panel.shape_choice[0].kind.base_shape_type(),
+            BaseShapeType::I3
+        );
+        assert!(solver::has_valid_placement(
+            &game,
+            &panel.shape_choice[0].kind
+        ));
+    }
+
+    #[test]
+    fn test_fallback_panel_on_a_full_board_is_left_alone() {
+        let mut game = Game::new_level(5, 1, 0);
+        game.board = full_board_with_hole(5, 0, 0);
+        game.board.set_cell(0, 0, Cell::Filled);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+
+        let panel = panel_of(vec![square, square, square]).with_placeable_shape(&mut game);
+
+        assert!(panel.shape_choice.iter().all(|s| s.kind == square));
+    }
+
+    #[test]
+    fn test_placeable_panel_is_not_rerolled() {
+        let mut game = Game::new_level(5, 1, 0);
+        game.config.anti_frustration = true;
+        game.board = Board::new(5);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.panel = panel_of(vec![square, square, square]);
+
+        assert!(!game.reroll_unplaceable_shape());
+        assert!(game.panel.shape_choice.iter().all(|s| s.kind == square));
+    }
+
+    #[test]
+    fn test_first_panel_of_level_has_valid_placement() {
+        for _ in 0..200 {
+            // 15 of 25 cells are filled at level 4 on a 5x5 board
+            let game = Game::new_level(5, 4, 0);
+            assert!(game
+                .panel
+                .shape_choice
+                .iter()
+                .any(|s| solver::has_valid_placement(&game, &s.kind)));
+        }
+    }
+
+    #[test]
+    fn test_five_shape_panel_fits_without_overlaps() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..100 {
+            let panel = Panel::generate_for_n(&mut rng, 5);
+
+            assert_eq!(panel.shape_choice.len(), 5);
+            let cells: usize = panel
+                .shape_choice
+                .iter()
+                .map(|s| s.kind.cells().len())
+                .sum();
+            // every cell of every shape got its own key
+            assert_eq!(panel.shapes_in_cell_space.len(), cells);
+            assert!(panel
+                .shapes_in_cell_space
+                .keys()
+                .all(|cell| (cell.col as usize) < Panel::cols_for(5)));
+        }
+        assert_eq!(Panel::cols_for(3), 14);
+    }
+
+    #[test]
+    fn test_bag_deals_every_shape_once_before_repeating() {
+        let mut rng = StdRng::seed_from_u64(1763);
+        let mut bag = ShapeBag::default();
+
+        for _ in 0..3 {
+            let mut drawn: Vec<BaseShapeType> = (0..BaseShapeType::COUNT)
+                .map(|_| bag.draw(&mut rng))
+                .collect();
+            drawn.sort_by_key(|shape| *shape as usize);
+            assert_eq!(drawn, BaseShapeType::iter().collect::<Vec<_>>());
+        }
+
+        // the second round of 5 runs over the end of the first bag
+        let config = GameConfig {
+            panel_shapes: 5,
+            ..GameConfig::default()
+        };
+        let mut bag = ShapeBag::default();
+        let first = Panel::generate(&mut rng, &mut bag, &config);
+        let second = Panel::generate(&mut rng, &mut bag, &config);
+        let mut dealt: Vec<BaseShapeType> = first
+            .shape_choice
+            .iter()
+            .chain(&second.shape_choice)
+            .map(|s| s.kind.base_shape_type())
+            .collect();
+        assert_eq!(dealt.len(), 10);
+        dealt.truncate(BaseShapeType::COUNT);
+        dealt.sort_by_key(|shape| *shape as usize);
+        assert_eq!(dealt, BaseShapeType::iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_base_shape_names() {
+        assert_eq!(BaseShapeType::from_name("OO"), Some(BaseShapeType::OO));
+        assert_eq!(BaseShapeType::from_name(" I3"), Some(BaseShapeType::I3));
+        assert_eq!(BaseShapeType::from_name("Q"), None);
+    }
+
+    #[test]
+    fn test_forced_shape_fills_every_panel_slot() {
+        let config = GameConfig {
+            forced_shape: Some(BaseShapeType::L1),
+            ..GameConfig::default()
+        };
+        let mut game = Game::new_level_with_config(config, 5, 1, 0);
+        for _ in 0..50 {
+            assert_eq!(game.panel.shape_choice.len(), 3);
+            assert!(game
+                .panel
+                .shape_choice
+                .iter()
+                .all(|s| s.kind.base_shape_type() == BaseShapeType::L1));
+            game.panel = Panel::generate_solvable(&mut game);
+        }
+        // the anti-frustration reroll sticks to it too
+        assert!(solver::any_placeable_shape(&game, &mut game.rng.clone())
+            .is_some_and(|kind| kind.base_shape_type() == BaseShapeType::L1));
+    }
+
+    #[test]
+    fn test_placed_cells_are_tagged_with_their_shape_type() {
+        let mut game = game_with_empty_board(5);
+        game.board.set_cell(4, 4, Cell::Filled);
+        let t_shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+
+        game.place_shape(&t_shape, &CellCoord::new(1, 1));
+
+        let mut tagged = vec![];
+        for row in 0..5 {
+            for col in 0..5 {
+                if let Some(shape) = game.board.shape_type_at(col, row) {
+                    assert_eq!(shape, BaseShapeType::T1);
+                    tagged.push((col, row));
+                }
+            }
+        }
+        assert_eq!(tagged, vec![(2, 1), (1, 2), (2, 2), (3, 2)]);
+        // the seeded cell has no shape to take a colour from
+        assert_eq!(game.board.shape_type_at(4, 4), None);
+    }
+
+    #[test]
+    fn test_partially_cleared_shape_can_not_be_picked_up() {
+        let mut game = game_with_empty_board(6);
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        game.place_shape(&l_shape, &CellCoord::new(0, 0));
+        game.board.clear_lines(&ClearedLines {
+            rows: vec![2],
+            cols: vec![],
+        });
+
+        assert!(!game.pick_up_shape(&CellCoord::new(0, 0), OffsetXY(0, 0)));
+        assert_eq!(game.board.get(0, 0), Some(&Cell::Filled));
+        assert!(game.selected_shape.is_none());
+    }
+
+    #[test]
+    fn test_cleared_shapes_are_forgotten() {
+        let mut game = game_with_empty_board(6);
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        let t_shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+        game.place_shape(&l_shape, &CellCoord::new(0, 0));
+        game.place_shape(&t_shape, &CellCoord::new(2, 3));
+        assert_eq!(game.board.placed_shapes.len(), 2);
+
+        game.board.clear_lines(&ClearedLines {
+            rows: vec![2],
+            cols: vec![0],
+        });
+
+        assert_eq!(game.board.placed_shapes.len(), 1);
+        assert_eq!(game.board.shape_type_at(3, 4), Some(BaseShapeType::T1));
+        assert!(game.pick_up_shape(&CellCoord::new(3, 4), OffsetXY(0, 0)));
+        assert!(game.board.placed_shapes.is_empty());
+    }
+
+    #[test]
+    fn test_lines_completed_by_a_previewed_placement() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::from_ascii("###.\n##..\n....\n###.\n").unwrap();
+        let before = game.board.to_ascii();
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+
+        let lines = game.lines_completed_by(&line, &CellCoord::new(3, 0));
+
+        assert_eq!(lines.rows, vec![0, 3]);
+        assert_eq!(lines.cols, vec![3]);
+        // only a dry run
+        assert_eq!(game.board.to_ascii(), before);
+
+        let dot = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        assert!(game
+            .lines_completed_by(&dot, &CellCoord::new(2, 1))
+            .is_empty());
+        assert_eq!(
+            game.lines_completed_by(&dot, &CellCoord::new(3, 0)).rows,
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_wraparound_placement_straddles_right_edge() {
+        let mut game = Game::new_level(5, 1, 0);
+        game.board = Board::new(5);
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::Cw90);
+        assert!(!game.is_valid_placement(&line, &CellCoord::new(3, 1)));
+
+        game.config.wraparound = true;
+        assert!(game.is_valid_placement(&line, &CellCoord::new(3, 1)));
+        game.place_shape(&line, &CellCoord::new(3, 1));
+
+        assert_eq!(game.board.to_ascii().lines().nth(1), Some("##.##"));
+        // the wrapped part is still one shape
+        assert_eq!(game.board.shape_group(0, 1).len(), 4);
+        // and blocks the cells it wrapped onto
+        assert!(!game.is_valid_placement(&line, &CellCoord::new(4, 1)));
+    }
+
+    #[test]
+    fn test_same_daily_date_deals_the_same_panels() {
+        let date = Date {
+            year: 2025,
+            month: 1,
+            day: 2,
+        };
+        // panels as they come, the level's first one and the ones dealt after it
+        let panels = |date: Date| {
+            let mut game = Game::daily(10, date);
+            let mut kinds = vec![game.board.to_ascii()];
+            for _ in 0..5 {
+                kinds.extend(
+                    game.panel
+                        .shape_choice
+                        .iter()
+                        .map(|s| format!("{:?}", s.kind)),
+                );
+                game.panel = Panel::generate_for_n(&mut game.rng, 3);
+            }
+            kinds
+        };
+
+        assert_eq!(panels(date), panels(date));
+        assert_ne!(panels(date), panels(Date { day: 3, ..date }));
+        assert_eq!(Game::daily(10, date).daily, Some(date));
+    }
+
+    #[test]
+    fn test_retry_level_deals_the_level_again_with_the_earlier_total() {
+        let mut game = Game::new_level(8, 1, 0);
+        game.stats.total_score = 15;
+        game.go_next_level();
+        let board = game.board.to_ascii();
+        let kinds: Vec<ShapeType> = game.panel.shape_choice.iter().map(|s| s.kind).collect();
+        let target = game.stats.target_score;
+
+        game.stats.current_score = 40;
+        game.stats.total_score += 40;
+        game.ledger.cells += 40;
+        game.board.set_cell(0, 0, Cell::Filled);
+        game.panel = Panel::generate_for_n(&mut game.rng, 3);
+        game.game_state = GameState::GameOver;
+        game.retry_level();
+
+        assert_eq!(game.stats.level, 2);
+        assert_eq!(game.stats.total_score, 15);
+        assert_eq!(game.stats.current_score, 0);
+        assert_eq!(game.stats.target_score, target);
+        assert_eq!(game.ledger.total(), 0);
+        assert_eq!(game.board.to_ascii(), board);
+        let retried: Vec<ShapeType> = game.panel.shape_choice.iter().map(|s| s.kind).collect();
+        assert_eq!(retried, kinds);
+        assert_ne!(game.game_state, GameState::GameOver);
+    }
+
+    #[test]
+    fn test_replaced_board_size_carries_over_to_retry_and_restart() {
+        let mut game = Game::new_level(8, 1, 0);
+
+        game.replace_board(Board::new(5));
+        game.retry_level();
+        assert_eq!(game.board.cols, 5);
+
+        game.restart();
+        assert_eq!(game.board.cols, 5);
+    }
 }
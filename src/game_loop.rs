@@ -0,0 +1,972 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::events::Event;
+use crate::game_entities::{Game, GameState, SelectedShape, ShapeState};
+#[cfg(test)]
+use crate::game_entities::{ScoreLedger, ShapeType};
+use crate::input::Input;
+use crate::menu::{MenuAction, MenuState};
+use crate::render::render::UserRenderConfig;
+use crate::save::SaveHook;
+#[cfg(test)]
+use crate::space_converters::CellCoord;
+use crate::system::{
+    AntiFrustrationSystem, AutosaveSystem, ClearingSystem, DangerZoneSystem, DecaySystem,
+    EdgeGlowSystem, LevelIntroSystem, NewGameSystem, PanelHoverSystem, PanelRefreshSystem,
+    PlacementSystem, RotationAnimationSystem, RotationSystem, ScoreCleanupSystem,
+    SelectionValidationSystem, SlowMotionSystem, System, WinOrLoseSystem,
+};
+
+// game logic of a single frame: no window, rendering or audio, so it can also run headless
+pub struct GameLoop {
+    selection_system: SelectionValidationSystem,
+    placement_system: PlacementSystem,
+    score_cleanup_system: ScoreCleanupSystem,
+    game_progress_system: WinOrLoseSystem,
+    new_game_system: NewGameSystem,
+    anti_frustration_system: AntiFrustrationSystem,
+    clearing_system: ClearingSystem,
+    panel_hover_system: PanelHoverSystem,
+    autosave_system: AutosaveSystem,
+    slow_motion_system: SlowMotionSystem,
+    danger_zone_system: DangerZoneSystem,
+    edge_glow_system: EdgeGlowSystem,
+    level_intro_system: LevelIntroSystem,
+    rotation_system: RotationSystem,
+    rotation_animation_system: RotationAnimationSystem,
+    decay_system: DecaySystem,
+    panel_refresh_system: PanelRefreshSystem,
+}
+
+impl GameLoop {
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self::with_save_hook(None)
+    }
+
+    // `save_hook` gets the game whenever an autosave is due
+    pub fn with_save_hook(save_hook: Option<SaveHook>) -> Self {
+        Self {
+            selection_system: SelectionValidationSystem,
+            placement_system: PlacementSystem,
+            score_cleanup_system: ScoreCleanupSystem,
+            game_progress_system: WinOrLoseSystem,
+            new_game_system: NewGameSystem,
+            anti_frustration_system: AntiFrustrationSystem,
+            clearing_system: ClearingSystem,
+            panel_hover_system: PanelHoverSystem,
+            autosave_system: AutosaveSystem { save_hook },
+            slow_motion_system: SlowMotionSystem,
+            danger_zone_system: DangerZoneSystem,
+            edge_glow_system: EdgeGlowSystem,
+            level_intro_system: LevelIntroSystem,
+            rotation_system: RotationSystem,
+            rotation_animation_system: RotationAnimationSystem,
+            decay_system: DecaySystem,
+            panel_refresh_system: PanelRefreshSystem,
+        }
+    }
+
+    // returns the game events handled during the frame, e.g. to play sounds for them
+    pub fn update(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        game_event_queue: &mut VecDeque<Event>,
+        config: &UserRenderConfig,
+    ) -> Vec<Event> {
+        let mut handled = vec![];
+        if input.pause_pressed {
+            game.toggle_pause();
+        }
+        if game.pause.is_some() {
+            return handled;
+        }
+
+        self.game_progress_system
+            .update_state(input, dt, game, game_event_queue, config, None);
+
+        if game.game_state == GameState::MoveToNextLevel {
+            self.new_game_system
+                .update_state(input, dt, game, game_event_queue, config, None)
+        }
+
+        if game.game_state == GameState::GameOver {
+            if game.config.game_over_menu {
+                self.navigate_menu(input, game);
+            } else if game.config.allow_retry && input.retry_pressed {
+                game.retry_level();
+            }
+        }
+
+        if game.game_state == GameState::Playing
+            && game.config.quick_restart
+            && input.restart_pressed
+        {
+            game.restart();
+        }
+
+        if game.game_state == GameState::LevelIntro {
+            // the click that skips the intro isn't played
+            self.level_intro_system
+                .update_state(input, dt, game, game_event_queue, config, None);
+        } else if game.game_state == GameState::Playing {
+            // what `SpeedRamp`s go by, the intro and pauses don't count
+            game.level_time += dt;
+            if input.undo_pressed && game.undo() {
+                println!("Took back the last placement");
+            }
+            self.selection_system
+                .update_state(input, dt, game, game_event_queue, config, None);
+
+            while let Some(event) = game_event_queue.pop_front() {
+                match event {
+                    Event::ShapeSelected(n, ref coord) => {
+                        game.deselect();
+                        let selected_shape = game.panel.shape_choice.get_mut(n).unwrap();
+                        game.selected_shape = Some(SelectedShape {
+                            shape_type: selected_shape.kind,
+                            anchor_offset: coord.clone(),
+                            picked_up_from: None,
+                            panel_index: Some(n),
+                        });
+                        selected_shape.set_state(ShapeState::SELECTED);
+                        game.ui.need_to_update_panel = true;
+                        println!("Shape {:?} is selected", &selected_shape);
+                    }
+                    Event::ShapeDeselected => {
+                        game.deselect();
+                        game.ui.need_to_update_panel = true;
+                    }
+                    Event::SelectedShapePlaced(_, _) => {
+                        self.on_shape_placed(input, dt, game, game_event_queue, config, &event);
+                    }
+                    Event::PlacedShapePickedUp(cell, ref anchor_offset) => {
+                        if game.pick_up_shape(&cell, anchor_offset.clone()) {
+                            println!("Picked up shape at {:?}", cell);
+                        }
+                    }
+                    Event::LinesCleared(_) => {
+                        self.slow_motion_system.update_state(
+                            input,
+                            dt,
+                            game,
+                            game_event_queue,
+                            config,
+                            Some(&event),
+                        );
+                    }
+                }
+                game.tutorial = game.tutorial.and_then(|step| step.after(&event));
+                handled.push(event);
+            }
+
+            self.rotation_system
+                .update_state(input, dt, game, game_event_queue, config, None);
+            self.decay_system
+                .update_state(input, dt, game, game_event_queue, config, None);
+        }
+
+        self.panel_hover_system
+            .update_state(input, dt, game, game_event_queue, config, None);
+        // animations follow the time scale, the slow motion itself runs in real time
+        let animation_dt = dt.mul_f32(game.ui.time_scale());
+        self.slow_motion_system
+            .update_state(input, dt, game, game_event_queue, config, None);
+        let was_clearing = game.clearing.is_some();
+        self.clearing_system.update_state(
+            input,
+            animation_dt,
+            game,
+            game_event_queue,
+            config,
+            None,
+        );
+        // the rerolls after a placement wait for the board it leaves behind
+        if was_clearing && game.clearing.is_none() {
+            self.anti_frustration_system.update_state(
+                input,
+                dt,
+                game,
+                game_event_queue,
+                config,
+                None,
+            );
+        }
+        self.danger_zone_system.update_state(
+            input,
+            animation_dt,
+            game,
+            game_event_queue,
+            config,
+            None,
+        );
+        self.edge_glow_system.update_state(
+            input,
+            animation_dt,
+            game,
+            game_event_queue,
+            config,
+            None,
+        );
+        self.rotation_animation_system.update_state(
+            input,
+            animation_dt,
+            game,
+            game_event_queue,
+            config,
+            None,
+        );
+        self.panel_refresh_system.update_state(
+            input,
+            animation_dt,
+            game,
+            game_event_queue,
+            config,
+            None,
+        );
+
+        handled
+    }
+
+    // places the shape with its top-left at `cell` as if it was clicked there, without input or
+    // a selection. Returns false and leaves the game as is when the placement isn't valid.
+    // There's no frame to animate a clear in, the lines are emptied right away.
+    #[cfg(test)]
+    pub fn apply_move(
+        &self,
+        game: &mut Game,
+        shape: ShapeType,
+        cell: CellCoord,
+        config: &UserRenderConfig,
+    ) -> bool {
+        if !game.is_valid_placement(&shape, &cell) {
+            return false;
+        }
+        let event = Event::SelectedShapePlaced(shape, cell);
+        let mut events = VecDeque::new();
+        self.on_shape_placed(
+            &Input::new(),
+            Duration::ZERO,
+            game,
+            &mut events,
+            config,
+            &event,
+        );
+        if game.clearing.is_some() {
+            game.finish_clearing();
+            self.anti_frustration_system.update_state(
+                &Input::new(),
+                Duration::ZERO,
+                game,
+                &mut events,
+                config,
+                None,
+            );
+        }
+        true
+    }
+
+    fn on_shape_placed(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        game_event_queue: &mut VecDeque<Event>,
+        config: &UserRenderConfig,
+        event: &Event,
+    ) {
+        self.placement_system
+            .update_state(input, dt, game, game_event_queue, config, Some(event));
+        self.score_cleanup_system.update_state(
+            input,
+            dt,
+            game,
+            game_event_queue,
+            config,
+            Some(event),
+        );
+        self.decay_system
+            .update_state(input, dt, game, game_event_queue, config, Some(event));
+        if game.clearing.is_none() {
+            self.anti_frustration_system.update_state(
+                input,
+                dt,
+                game,
+                game_event_queue,
+                config,
+                None,
+            );
+        }
+        self.autosave_system
+            .update_state(input, dt, game, game_event_queue, config, None);
+    }
+
+    // the game over menu is opened on the first update after losing
+    fn navigate_menu(&self, input: &Input, game: &mut Game) {
+        let allow_retry = game.config.allow_retry;
+        let menu = game
+            .menu
+            .get_or_insert_with(|| MenuState::game_over(allow_retry));
+        if input.menu_up_pressed {
+            menu.up();
+        }
+        if input.menu_down_pressed {
+            menu.down();
+        }
+        let action = input.menu_select_pressed.then(|| menu.select());
+        if input.menu_up_pressed || input.menu_down_pressed {
+            // the highlight moved, nothing else would redraw the idle screen
+            game.ui.need_to_update_board = true;
+        }
+        match action {
+            Some(MenuAction::RetryLevel) => game.retry_level(),
+            Some(MenuAction::Quit) => game.quit_requested = true,
+            None => {}
+        }
+    }
+}
+
+// why a batch of moves stopped, `move_index` is the position of the failing move in the batch
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveError {
+    NoSuchShape {
+        move_index: usize,
+        panel_index: usize,
+    },
+    // placed already, or selected by the player
+    ShapeNotAvailable {
+        move_index: usize,
+        panel_index: usize,
+    },
+    InvalidPlacement {
+        move_index: usize,
+        cell: CellCoord,
+    },
+}
+
+impl Game {
+    // places panel shapes as (panel index, cell the player would put its top-left at) one after
+    // the other, without input or events. Returns what each move scored. Stops at the first move
+    // that can't be made; the moves before it stay applied. The systems run with `config`, the
+    // caller decides which animations the placements go through.
+    #[cfg(test)]
+    pub fn apply_moves(
+        &mut self,
+        moves: &[(usize, CellCoord)],
+        config: &UserRenderConfig,
+    ) -> Result<Vec<ScoreLedger>, MoveError> {
+        let game_loop = GameLoop::new();
+        let mut scored = Vec::with_capacity(moves.len());
+        for (move_index, &(panel_index, cell)) in moves.iter().enumerate() {
+            let shape = self
+                .panel
+                .shape_choice
+                .get(panel_index)
+                .ok_or(MoveError::NoSuchShape {
+                    move_index,
+                    panel_index,
+                })?;
+            if shape.state != ShapeState::VISIBLE {
+                return Err(MoveError::ShapeNotAvailable {
+                    move_index,
+                    panel_index,
+                });
+            }
+            let (board_shape, board_cell) = self.board_placement(&shape.kind, &cell);
+            let before = self.ledger;
+            // `place_shape` puts the selected panel shape away
+            self.panel.shape_choice[panel_index].set_state(ShapeState::SELECTED);
+            if !game_loop.apply_move(self, board_shape, board_cell, config) {
+                self.panel.shape_choice[panel_index].set_state(ShapeState::VISIBLE);
+                return Err(MoveError::InvalidPlacement { move_index, cell });
+            }
+            self.ui.need_to_update_panel = true;
+            scored.push(self.ledger.since(&before));
+        }
+        Ok(scored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_config::{BoardOrigin, GameConfig};
+    use crate::game_entities::{
+        BaseShapeType, Board, Cell, Panel, PauseReason, Shape, ShapeRot, ShapeType,
+    };
+    use crate::solver;
+    use crate::space_converters::{OffsetXY, XY};
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+    use std::cell::Cell as Counter;
+    use std::rc::Rc;
+    use winit::event::ElementState;
+    use winit::keyboard::{KeyCode, ModifiersState};
+
+    #[test]
+    fn test_single_clear_awards_points_once() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.stats.target_score = 1000;
+        game.board = Board::new(config.board_size_cols);
+        for col in 1..config.board_size_cols {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        game.selected_shape = Some(SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+
+        // click into the only empty cell of row 0
+        let mut input = Input::new();
+        input.mouse_left_clicked = Some(XY(
+            config.board_offset_x_px + 1.0,
+            config.board_offset_y_px + 1.0,
+        ));
+        let handled = game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+        input.reset();
+        let placed = handled
+            .iter()
+            .filter(|e| matches!(e, Event::SelectedShapePlaced(_, _)))
+            .count();
+        assert_eq!(placed, 1);
+        assert!(matches!(handled.last(), Some(Event::LinesCleared(1))));
+
+        let score = config.board_size_cols as i32;
+        assert_eq!(game.stats.current_score, score);
+        assert_eq!(game.stats.total_score, score);
+
+        // nothing is scored again while the row plays its clear out
+        let handled = game_loop.update(
+            &input,
+            config.clear_animation.unwrap(),
+            &mut game,
+            &mut events,
+            &config,
+        );
+        assert!(handled.is_empty());
+        assert_eq!(game.stats.current_score, score);
+        assert!(!game.board.is_row_full(0));
+    }
+
+    #[test]
+    fn test_pause_key_toggles_the_pause() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.board = Board::new(config.board_size_cols);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+        let mut pause = Input::new();
+        pause.pause_pressed = true;
+
+        game_loop.update(&pause, Duration::ZERO, &mut game, &mut events, &config);
+        assert_eq!(game.pause, Some(PauseReason::Player));
+
+        // clicks don't place anything meanwhile
+        let mut click = Input::new();
+        click.mouse_left_clicked = Some(XY(
+            config.board_offset_x_px + 1.0,
+            config.board_offset_y_px + 1.0,
+        ));
+        let handled = game_loop.update(&click, Duration::ZERO, &mut game, &mut events, &config);
+        assert!(handled.is_empty());
+        assert_eq!(game.board.filled_count(), 0);
+
+        game_loop.update(&pause, Duration::ZERO, &mut game, &mut events, &config);
+        assert_eq!(game.pause, None);
+        assert_eq!(game.game_state, GameState::Playing);
+    }
+
+    #[test]
+    fn test_cleared_cells_stay_until_the_animation_ends() {
+        let config = UserRenderConfig::default();
+        let duration = config.clear_animation.unwrap();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.stats.target_score = 1000;
+        game.board = Board::new(config.board_size_cols);
+        for col in 1..config.board_size_cols {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let select = |game: &mut Game| {
+            game.selected_shape = Some(SelectedShape {
+                shape_type: single,
+                anchor_offset: OffsetXY(0, 0),
+                picked_up_from: None,
+                panel_index: None,
+            })
+        };
+        let click_at = |row: usize| {
+            let mut input = Input::new();
+            input.mouse_left_clicked = Some(XY(
+                config.board_offset_x_px + 1.0,
+                config.board_offset_y_px + 1.0 + row as f32 * config.cell_size_px,
+            ));
+            input
+        };
+
+        select(&mut game);
+        game_loop.update(
+            &click_at(0),
+            Duration::ZERO,
+            &mut game,
+            &mut events,
+            &config,
+        );
+        assert!(game.clearing.is_some());
+        assert!(game.board.is_row_full(0));
+
+        // halfway through, the row is still there and the board takes no new shapes
+        game_loop.update(&Input::new(), duration / 2, &mut game, &mut events, &config);
+        assert!(game.board.is_row_full(0));
+        select(&mut game);
+        let handled = game_loop.update(
+            &click_at(1),
+            Duration::ZERO,
+            &mut game,
+            &mut events,
+            &config,
+        );
+        assert!(!handled
+            .iter()
+            .any(|e| matches!(e, Event::SelectedShapePlaced(_, _))));
+
+        game_loop.update(&Input::new(), duration / 2, &mut game, &mut events, &config);
+        assert!(game.clearing.is_none());
+        assert_eq!(game.board.filled_count(), 0);
+    }
+
+    #[test]
+    fn test_clear_reaching_the_goal_empties_its_lines_before_the_board_grows() {
+        let config = UserRenderConfig::default();
+        let duration = config.clear_animation.unwrap();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let size = config.board_size_cols;
+        let mut game = Game::new_level(size, 1, 0);
+        game.config.board_growth = true;
+        game.stats.target_score = 1;
+        game.board = Board::new(size);
+        for col in 1..size {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        game.selected_shape = Some(SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+        let mut input = Input::new();
+        input.mouse_left_clicked = Some(XY(
+            config.board_offset_x_px + 1.0,
+            config.board_offset_y_px + 1.0,
+        ));
+
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+        // the level waits for the clear to play out
+        game_loop.update(&Input::new(), duration / 2, &mut game, &mut events, &config);
+        assert_eq!(game.stats.level, 1);
+        assert!(game.board.is_row_full(0));
+
+        game_loop.update(&Input::new(), duration / 2, &mut game, &mut events, &config);
+        game_loop.update(
+            &Input::new(),
+            Duration::ZERO,
+            &mut game,
+            &mut events,
+            &config,
+        );
+        assert_eq!(game.stats.level, 2);
+        assert_eq!(game.board.cols, size + 1);
+        assert_eq!(game.board.filled_count(), 0);
+    }
+
+    #[test]
+    fn test_reduced_motion_clears_in_a_single_frame() {
+        let config = UserRenderConfig {
+            reduce_motion: true,
+            slow_motion_min_lines: 1,
+            ..UserRenderConfig::default()
+        };
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.stats.target_score = 1000;
+        game.board = Board::new(config.board_size_cols);
+        for i in 1..config.board_size_cols {
+            game.board.set_cell(i, 0, Cell::Filled);
+            game.board.set_cell(0, i, Cell::Filled);
+        }
+        game.selected_shape = Some(SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+        let mut input = Input::new();
+        input.mouse_left_clicked = Some(XY(
+            config.board_offset_x_px + 1.0,
+            config.board_offset_y_px + 1.0,
+        ));
+
+        let handled = game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+
+        assert!(matches!(handled.last(), Some(Event::LinesCleared(2))));
+        assert_eq!(game.board.filled_count(), 0);
+        assert!(game.clearing.is_none());
+        assert!(game.ui.slow_motion.is_none());
+        assert_eq!(game.ui.time_scale(), 1.0);
+    }
+
+    #[test]
+    fn test_bottom_left_origin_places_row_0_at_the_bottom() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.config.board_origin = BoardOrigin::BottomLeft;
+        game.board = Board::new(config.board_size_cols);
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: l_shape,
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+
+        // top-left of the L three cells above the bottom edge, so it sits on the bottom row
+        let bottom = config.board_offset_y_px + config.board_size_rows as f32 * config.cell_size_px;
+        let mut input = Input::new();
+        input.mouse_left_clicked = Some(XY(
+            config.board_offset_x_px + 1.0,
+            bottom - 3.0 * config.cell_size_px + 1.0,
+        ));
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+
+        // the foot of the L is on screen at the bottom, i.e. in board row 0
+        assert_eq!(game.board.get(0, 0), Some(&Cell::Filled));
+        assert_eq!(game.board.get(1, 0), Some(&Cell::Filled));
+        assert_eq!(game.board.get(0, 2), Some(&Cell::Filled));
+        assert_eq!(game.board.get(1, 2), Some(&Cell::Empty));
+    }
+
+    #[test]
+    fn test_autosave_every_n_placements() {
+        let config = UserRenderConfig::default();
+        let saves = Rc::new(Counter::new(0));
+        let saves_in_hook = saves.clone();
+        let game_loop = GameLoop::with_save_hook(Some(Box::new(move |_| {
+            saves_in_hook.set(saves_in_hook.get() + 1)
+        })));
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.stats.target_score = 1000;
+        // off unless asked for
+        assert_eq!(game.config.autosave_every, None);
+        game.config.autosave_every = Some(2);
+        game.board = Board::new(config.board_size_cols);
+
+        let mut input = Input::new();
+        for col in 0..5 {
+            game.selected_shape = Some(SelectedShape {
+                shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+                anchor_offset: OffsetXY(0, 0),
+                picked_up_from: None,
+                panel_index: None,
+            });
+            input.mouse_left_clicked = Some(XY(
+                config.board_offset_x_px + (col as f32 + 0.5) * config.cell_size_px,
+                config.board_offset_y_px + 1.0,
+            ));
+            game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+        }
+
+        assert_eq!(game.placements, 5);
+        assert_eq!(saves.get(), 2);
+    }
+
+    // random valid placements under a fixed seed, checking the board and score after each one
+    #[test]
+    fn test_fuzz_placement_and_clearing() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut rng = StdRng::seed_from_u64(1677);
+        let shapes = solver::all_shape_types();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.stats.target_score = i32::MAX;
+
+        for _ in 0..3000 {
+            let shape = *shapes.choose(&mut rng).unwrap();
+            let Some(&cell) = solver::valid_placements(&game, &shape).choose(&mut rng) else {
+                if solver::any_placeable_shape(&game, &mut rng).is_none() {
+                    game.board = Board::new(config.board_size_cols);
+                }
+                continue;
+            };
+            let filled_before = game.board.filled_count();
+            let score_before = game.stats.total_score;
+
+            assert!(game_loop.apply_move(&mut game, shape, cell, &config));
+
+            let cleared = &game.last_cleared;
+            for &row in &cleared.rows {
+                for col in 0..game.board.cols {
+                    assert_eq!(game.board.get(col, row), Some(&Cell::Empty));
+                }
+            }
+            for &col in &cleared.cols {
+                for row in 0..game.board.rows {
+                    assert_eq!(game.board.get(col, row), Some(&Cell::Empty));
+                }
+            }
+            for line in 0..game.board.rows {
+                assert!(!game.board.is_row_full(line));
+                assert!(!game.board.is_col_full(line));
+            }
+
+            let (rows, cols) = (cleared.rows.len(), cleared.cols.len());
+            let cleared_cells = rows * game.board.cols + cols * game.board.rows - rows * cols;
+            assert_eq!(
+                game.board.filled_count(),
+                filled_before + shape.cells().len() - cleared_cells
+            );
+            assert!(game.stats.total_score >= score_before);
+            assert_eq!(game.stats.total_score > score_before, !cleared.is_empty());
+        }
+    }
+
+    fn batch_game() -> Game {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::new(4);
+        for col in 0..3 {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![single, square, square]));
+        game
+    }
+
+    #[test]
+    fn test_batch_of_moves_scores_each_move() {
+        let mut game = batch_game();
+
+        let scored = game
+            .apply_moves(
+                &[(0, CellCoord::new(3, 0)), (1, CellCoord::new(0, 2))],
+                &UserRenderConfig::default(),
+            )
+            .unwrap();
+
+        assert_eq!(scored.len(), 2);
+        assert_eq!(scored[0].cells, 4);
+        assert_eq!(scored[1].total(), 0);
+        assert_eq!(game.ledger.total(), 4);
+        assert_eq!(game.board.get(0, 0), Some(&Cell::Empty));
+        assert_eq!(game.board.get(1, 3), Some(&Cell::Filled));
+        assert_eq!(game.panel.shape_choice[2].state, ShapeState::VISIBLE);
+    }
+
+    #[test]
+    fn test_batch_stops_at_the_first_invalid_move() {
+        let mut game = batch_game();
+
+        let result = game.apply_moves(
+            &[
+                (1, CellCoord::new(0, 2)),
+                (2, CellCoord::new(1, 2)),
+                (0, CellCoord::new(3, 0)),
+            ],
+            &UserRenderConfig::default(),
+        );
+
+        assert_eq!(
+            result,
+            Err(MoveError::InvalidPlacement {
+                move_index: 1,
+                cell: CellCoord::new(1, 2),
+            })
+        );
+        // the first move stays, the failing shape is still there to play
+        assert_eq!(game.board.get(0, 2), Some(&Cell::Filled));
+        assert_eq!(game.panel.shape_choice[1].state, ShapeState::PLACED);
+        assert_eq!(game.panel.shape_choice[2].state, ShapeState::VISIBLE);
+        assert_eq!(game.board.get(3, 0), Some(&Cell::Empty));
+
+        assert_eq!(
+            game.apply_moves(&[(1, CellCoord::new(2, 2))], &UserRenderConfig::default()),
+            Err(MoveError::ShapeNotAvailable {
+                move_index: 0,
+                panel_index: 1,
+            })
+        );
+        assert_eq!(
+            game.apply_moves(&[(7, CellCoord::new(2, 2))], &UserRenderConfig::default()),
+            Err(MoveError::NoSuchShape {
+                move_index: 0,
+                panel_index: 7,
+            })
+        );
+    }
+
+    #[test]
+    fn test_sticky_selection_picks_the_identical_shape() {
+        let config = UserRenderConfig::default();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        game.board = Board::new(config.board_size_cols);
+        game.config.sticky_selection = true;
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![square, single, single]));
+        game.panel.shape_choice[1].set_state(ShapeState::SELECTED);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: single,
+            anchor_offset: OffsetXY(-3, -4),
+            picked_up_from: None,
+            panel_index: Some(1),
+        });
+        let mut queue = VecDeque::from([Event::SelectedShapePlaced(single, CellCoord::new(0, 0))]);
+
+        GameLoop::new().update(
+            &Input::new(),
+            Duration::ZERO,
+            &mut game,
+            &mut queue,
+            &config,
+        );
+
+        let selected = game.selected_shape.as_ref().unwrap();
+        assert_eq!(selected.panel_index, Some(2));
+        assert_eq!(
+            (selected.anchor_offset.0, selected.anchor_offset.1),
+            (-3, -4)
+        );
+        assert_eq!(game.panel.shape_choice[1].state, ShapeState::PLACED);
+        assert_eq!(game.panel.shape_choice[2].state, ShapeState::SELECTED);
+        assert_eq!(game.panel.shape_choice[0].state, ShapeState::VISIBLE);
+    }
+
+    #[test]
+    fn test_game_over_menu_retries_the_level() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level_with_config(
+            GameConfig {
+                game_over_menu: true,
+                ..GameConfig::default()
+            },
+            4,
+            1,
+            0,
+        );
+        game.game_state = GameState::GameOver;
+        let mut input = Input::new();
+
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+        assert_eq!(game.menu.as_ref().map(|menu| menu.selected), Some(0));
+
+        // down to Quit and around again to Retry
+        input.update_kb(&KeyCode::ArrowDown, &ElementState::Pressed);
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+        input.reset();
+        input.update_kb(&KeyCode::ArrowDown, &ElementState::Pressed);
+        input.update_kb(&KeyCode::Enter, &ElementState::Pressed);
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+
+        assert!(!game.quit_requested);
+        assert_ne!(game.game_state, GameState::GameOver);
+        assert!(game.menu.is_none());
+    }
+
+    #[test]
+    fn test_ctrl_and_the_restart_key_start_over_from_level_1() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut events = VecDeque::new();
+        let mut game = Game::new_level(4, 3, 0);
+        game.board = Board::new(4);
+        let mut input = Input::new();
+
+        // the key alone is the retry of the game over screen
+        input.update_kb(&KeyCode::KeyR, &ElementState::Pressed);
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+        input.reset();
+        assert_eq!(game.stats.level, 3);
+
+        input.update_modifiers(ModifiersState::CONTROL);
+        input.update_kb(&KeyCode::KeyR, &ElementState::Pressed);
+        assert!(!input.rotate_pressed);
+        game_loop.update(&input, Duration::ZERO, &mut game, &mut events, &config);
+
+        assert_eq!(game.stats.level, 1);
+        assert_eq!(game.board.cols, 4);
+    }
+
+    #[test]
+    fn test_level_time_runs_without_decay() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let mut game = Game::new_level(config.board_size_cols, 1, 0);
+        assert_eq!(game.config.score_decay_grace, None);
+
+        for _ in 0..3 {
+            game_loop.update(
+                &Input::new(),
+                Duration::from_millis(100),
+                &mut game,
+                &mut VecDeque::new(),
+                &config,
+            );
+        }
+
+        assert_eq!(game.level_time, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_level_intro_goes_on_to_playing() {
+        let config = UserRenderConfig::default();
+        let game_loop = GameLoop::new();
+        let game_config = GameConfig {
+            level_intro: Some(Duration::from_millis(1000)),
+            ..GameConfig::default()
+        };
+        let mut game = Game::new_level_with_config(game_config, config.board_size_cols, 1, 0);
+        let mut queue = VecDeque::new();
+        assert_eq!(game.game_state, GameState::LevelIntro);
+
+        let frame = |game: &mut Game, input: &Input, millis: u64, queue: &mut VecDeque<Event>| {
+            game_loop.update(input, Duration::from_millis(millis), game, queue, &config)
+        };
+        frame(&mut game, &Input::new(), 600, &mut queue);
+        assert_eq!(game.game_state, GameState::LevelIntro);
+        frame(&mut game, &Input::new(), 600, &mut queue);
+        assert_eq!(game.game_state, GameState::Playing);
+
+        // the next level has one too, a key skips it
+        game.go_next_level();
+        assert_eq!(game.game_state, GameState::LevelIntro);
+        let mut input = Input::new();
+        input.key_pressed = true;
+        frame(&mut game, &input, 10, &mut queue);
+        assert_eq!(game.game_state, GameState::Playing);
+        assert!(game.selected_shape.is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::game_entities::Game;
+
+const LINES_PER_LEVEL: u32 = 2;
+const CELLS_PER_LEVEL: u32 = 20;
+
+// what finishes a level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelGoal {
+    // total score reaches `GameStats::target_score`
+    Score,
+    // lines cleared in the run reach `LINES_PER_LEVEL` per level
+    LinesCleared,
+    // same, for cells and `CELLS_PER_LEVEL`
+    CellsCleared,
+}
+
+impl LevelGoal {
+    // `score`, `lines` or `cells`, as `--goal` takes it
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "score" => Some(LevelGoal::Score),
+            "lines" => Some(LevelGoal::LinesCleared),
+            "cells" => Some(LevelGoal::CellsCleared),
+            _ => None,
+        }
+    }
+}
+
+// (reached so far, needed) for the current level
+pub fn goal_counts(game: &Game) -> (i64, i64) {
+    let level = game.stats.level as i64;
+    match game.config.level_goal {
+        LevelGoal::Score => (
+            game.stats.total_score as i64,
+            game.stats.target_score as i64,
+        ),
+        LevelGoal::LinesCleared => (
+            game.stats.lines_cleared as i64,
+            level * LINES_PER_LEVEL as i64,
+        ),
+        LevelGoal::CellsCleared => (
+            game.stats.cells_cleared as i64,
+            level * CELLS_PER_LEVEL as i64,
+        ),
+    }
+}
+
+// 0.0 at the start of the level, 1.0 once the goal is reached
+pub fn progress_toward_goal(game: &Game) -> f32 {
+    let (reached, needed) = goal_counts(game);
+    if needed <= 0 {
+        return 1.0;
+    }
+    (reached as f32 / needed as f32).clamp(0.0, 1.0)
+}
+
+pub fn goal_text(game: &Game) -> String {
+    let (reached, needed) = goal_counts(game);
+    let unit = match game.config.level_goal {
+        LevelGoal::Score => "points",
+        LevelGoal::LinesCleared => "lines",
+        LevelGoal::CellsCleared => "cells",
+    };
+    format!("Goal: {}/{} {}", reached.min(needed), needed, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_config::GameConfig;
+
+    fn game_with_goal(level_goal: LevelGoal) -> Game {
+        let config = GameConfig {
+            level_goal,
+            ..GameConfig::default()
+        };
+        Game::new_level_with_config(config, 8, 2, 0)
+    }
+
+    #[test]
+    fn test_goal_names() {
+        assert_eq!(LevelGoal::from_name("lines"), Some(LevelGoal::LinesCleared));
+        assert_eq!(
+            LevelGoal::from_name(" Cells"),
+            Some(LevelGoal::CellsCleared)
+        );
+        assert_eq!(LevelGoal::from_name("time"), None);
+    }
+
+    #[test]
+    fn test_score_goal_progress() {
+        let mut game = game_with_goal(LevelGoal::Score);
+        game.stats.total_score = 5;
+
+        assert_eq!(progress_toward_goal(&game), 0.25);
+        assert_eq!(goal_text(&game), "Goal: 5/20 points");
+    }
+
+    #[test]
+    fn test_lines_goal_progress() {
+        let mut game = game_with_goal(LevelGoal::LinesCleared);
+        game.stats.total_score = 100;
+        game.stats.lines_cleared = 1;
+
+        assert_eq!(progress_toward_goal(&game), 0.25);
+        assert_eq!(goal_text(&game), "Goal: 1/4 lines");
+    }
+
+    #[test]
+    fn test_cells_goal_progress() {
+        let mut game = game_with_goal(LevelGoal::CellsCleared);
+        game.stats.cells_cleared = 30;
+
+        assert_eq!(progress_toward_goal(&game), 0.75);
+
+        game.stats.cells_cleared = 50;
+        assert_eq!(progress_toward_goal(&game), 1.0);
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::settings::settings_path;
+
+// next to the settings, as `{"best_score": 40}`
+pub fn high_score_path() -> PathBuf {
+    settings_path().with_file_name("high_score.json")
+}
+
+pub fn save_high_score(path: &Path, score: i32) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{{\"best_score\": {}}}\n", score))
+}
+
+// 0 on the first run, when there's no file yet, and for a file that can't be read
+pub fn load_high_score(path: &Path) -> i32 {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return 0,
+        Err(e) => {
+            println!("Could not read the high score from {:?}: {}", path, e);
+            return 0;
+        }
+    };
+    match parse_best_score(&text) {
+        Some(score) => score,
+        None => {
+            println!("Broken high score file {:?}, starting from 0", path);
+            0
+        }
+    }
+}
+
+// only the one object `save_high_score` writes, whitespace anywhere between the tokens
+fn parse_best_score(text: &str) -> Option<i32> {
+    let body = text.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let (key, value) = body.split_once(':')?;
+    if key.trim() != "\"best_score\"" {
+        return None;
+    }
+    value.trim().parse().ok().filter(|score| *score >= 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::temp_settings_path;
+
+    #[test]
+    fn test_high_score_round_trip() {
+        let path = temp_settings_path("high_score_round_trip");
+
+        save_high_score(&path, 1234).unwrap();
+
+        assert_eq!(load_high_score(&path), 1234);
+        assert_eq!(
+            load_high_score(&temp_settings_path("high_score_missing")),
+            0
+        );
+        for broken in [
+            "",
+            "{}",
+            "{\"best_score\": \"a lot\"}",
+            "[40]",
+            "{\"best\": 40}",
+        ] {
+            fs::write(&path, broken).unwrap();
+            assert_eq!(load_high_score(&path), 0, "{:?}", broken);
+        }
+        fs::write(&path, " { \"best_score\" :7 }").unwrap();
+        assert_eq!(load_high_score(&path), 7);
+    }
+}
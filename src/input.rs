@@ -1,15 +1,56 @@
-use winit::dpi::PhysicalPosition;
-use winit::event::MouseButton;
+use std::collections::VecDeque;
+use std::time::Duration;
+use winit::dpi::{LogicalPosition, PhysicalPosition};
+use winit::event::{MouseButton, TouchPhase};
+use winit::keyboard::ModifiersState;
 use winit::{event::ElementState, keyboard::KeyCode};
 
+use crate::key_bindings::{Action, KeyBindings};
 use crate::space_converters::XY;
 
+#[derive(Debug, Clone)]
+pub enum MouseClick {
+    Left(XY),
+    Right,
+}
+
 #[derive(Debug, Default)]
 pub struct Input {
-    pub esc_pressed: bool,
+    pub key_bindings: KeyBindings,
+    pub quit_pressed: bool,
+    // until the end of the update
+    pub retry_pressed: bool,
+    pub rotate_pressed: bool,
+    pub frame_stats_toggled: bool,
+    pub restart_pressed: bool,
+    pub undo_pressed: bool,
+    pub pause_pressed: bool,
+    // held Shift, Ctrl, Alt and Super keys, from the window's modifier events
+    modifiers: ModifiersState,
+    // menu navigation, always the arrow keys and Enter whatever is bound to them
+    pub menu_up_pressed: bool,
+    pub menu_down_pressed: bool,
+    pub menu_select_pressed: bool,
+    // some key went down since the last update
+    pub key_pressed: bool,
+    // click handled in the current update, taken from `pending_clicks` by `poll_click`
     pub mouse_left_clicked: Option<XY>,
     pub mouse_right_clicked: bool,
     pub mouse_position: XY,
+    // false until the window reports where the mouse is. A game started with the mouse outside
+    // the window would have it at (0, 0) otherwise.
+    cursor_positioned: bool,
+    // where the cursor is drawn when smoothing is on, trailing `mouse_position`. Clicks always
+    // use the exact position.
+    smoothed_mouse_position: Option<XY>,
+    // clicks from the window event handlers. They are consumed one per update, so clicks
+    // arriving between redraws (or several within a single frame) are never lost.
+    pending_clicks: VecDeque<MouseClick>,
+    // id of the finger standing in for the mouse, other touches while it's down are ignored
+    primary_touch: Option<u64>,
+    // the window reports the cursor in physical pixels, everything else is laid out in logical
+    // ones. None is the same as 1.
+    scale_factor: Option<f64>,
 }
 
 impl Input {
@@ -19,22 +60,71 @@ impl Input {
 
     pub fn update_kb(&mut self, key: &KeyCode, state: &ElementState) -> bool {
         let pressed = state.is_pressed();
+        self.key_pressed |= pressed;
         match key {
-            KeyCode::Escape => {
-                self.esc_pressed = pressed;
+            KeyCode::ArrowUp => self.menu_up_pressed |= pressed,
+            KeyCode::ArrowDown => self.menu_down_pressed |= pressed,
+            KeyCode::Enter | KeyCode::NumpadEnter => self.menu_select_pressed |= pressed,
+            _ => {}
+        }
+        match self.key_bindings.action_for(key, self.ctrl()) {
+            Some(Action::Quit) => {
+                self.quit_pressed = pressed;
                 true
             }
-            _ => false,
+            Some(Action::Retry) => {
+                self.retry_pressed |= pressed;
+                true
+            }
+            Some(Action::Rotate) => {
+                self.rotate_pressed |= pressed;
+                true
+            }
+            Some(Action::ToggleFrameStats) => {
+                self.frame_stats_toggled |= pressed;
+                true
+            }
+            Some(Action::Restart) => {
+                self.restart_pressed |= pressed;
+                true
+            }
+            Some(Action::Undo) => {
+                self.undo_pressed |= pressed;
+                true
+            }
+            Some(Action::Pause) => {
+                self.pause_pressed |= pressed;
+                true
+            }
+            None => false,
         }
     }
 
+    pub fn update_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    // modifiers held right now, unlike the `_pressed` flags they aren't reset between updates
+    pub fn ctrl(&self) -> bool {
+        self.modifiers.control_key()
+    }
+
+    pub fn shift(&self) -> bool {
+        self.modifiers.shift_key()
+    }
+
     pub fn update_mouse(&mut self, button: &MouseButton, state: &ElementState) -> bool {
         let pressed = state.is_pressed();
+        if pressed && !self.cursor_positioned {
+            println!("Ignoring {:?} click, the cursor wasn't seen yet", button);
+            return false;
+        }
         if pressed {
             match button {
                 MouseButton::Left => {
                     println!("Left mouse button clicked at {:?}", self.mouse_position);
-                    self.mouse_left_clicked = Some(self.mouse_position.clone());
+                    self.pending_clicks
+                        .push_back(MouseClick::Left(self.mouse_position.clone()));
                     true
                 }
                 MouseButton::Right => {
@@ -42,7 +132,7 @@ impl Input {
                         "Right mouse button clicked at {:?}",
                         self.mouse_position.clone()
                     );
-                    self.mouse_right_clicked = true;
+                    self.pending_clicks.push_back(MouseClick::Right);
                     true
                 }
                 _ => false,
@@ -53,11 +143,269 @@ impl Input {
     }
 
     pub fn update_mouse_position(&mut self, position: PhysicalPosition<f64>) {
-        self.mouse_position = XY(position.x as f32, position.y as f32);
+        let position: LogicalPosition<f32> = position.to_logical(self.scale_factor.unwrap_or(1.0));
+        self.mouse_position = XY(position.x, position.y);
+        self.cursor_positioned = true;
+    }
+
+    // the primary touch acts as the mouse: going down is a left click where it lands, moving it
+    // moves the cursor
+    pub fn update_touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+    ) -> bool {
+        match phase {
+            TouchPhase::Started if self.primary_touch.is_none() => {
+                self.primary_touch = Some(id);
+                self.update_mouse_position(location);
+                self.update_mouse(&MouseButton::Left, &ElementState::Pressed)
+            }
+            TouchPhase::Moved if self.primary_touch == Some(id) => {
+                self.update_mouse_position(location);
+                false
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled if self.primary_touch == Some(id) => {
+                self.primary_touch = None;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_cursor_positioned(&self) -> bool {
+        self.cursor_positioned
+    }
+
+    // has to be the scale the window is rendered with, see `render::render_scale`
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = Some(scale_factor);
+    }
+
+    // moves the drawn cursor toward the mouse, `time_constant` is how long it takes to cover
+    // about two thirds of the distance
+    pub fn smooth_cursor(&mut self, dt: Duration, time_constant: Duration) {
+        let smoothed = match &self.smoothed_mouse_position {
+            Some(current) => ema(current, &self.mouse_position, dt, time_constant),
+            None => self.mouse_position.clone(),
+        };
+        self.smoothed_mouse_position = Some(smoothed);
+    }
+
+    // where to draw the cursor
+    pub fn cursor_position(&self) -> &XY {
+        self.smoothed_mouse_position
+            .as_ref()
+            .unwrap_or(&self.mouse_position)
+    }
+
+    // moves the oldest pending click into the current update
+    pub fn poll_click(&mut self) {
+        match self.pending_clicks.pop_front() {
+            Some(MouseClick::Left(position)) => self.mouse_left_clicked = Some(position),
+            Some(MouseClick::Right) => self.mouse_right_clicked = true,
+            None => {}
+        }
     }
 
+    // clears the click handled in this update. Pending clicks stay queued for the next ones.
     pub fn reset(&mut self) {
         self.mouse_left_clicked = None;
         self.mouse_right_clicked = false;
+        self.key_pressed = false;
+        self.retry_pressed = false;
+        self.rotate_pressed = false;
+        self.frame_stats_toggled = false;
+        self.restart_pressed = false;
+        self.undo_pressed = false;
+        self.pause_pressed = false;
+        self.menu_up_pressed = false;
+        self.menu_down_pressed = false;
+        self.menu_select_pressed = false;
+    }
+}
+
+// exponential moving average from `current` toward `target`, independent of the frame rate
+pub fn ema(current: &XY, target: &XY, dt: Duration, time_constant: Duration) -> XY {
+    if time_constant.is_zero() {
+        return target.clone();
+    }
+    let alpha = 1.0 - (-dt.as_secs_f32() / time_constant.as_secs_f32()).exp();
+    XY(
+        current.0 + (target.0 - current.0) * alpha,
+        current.1 + (target.1 - current.1) * alpha,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::render::UserRenderConfig;
+    use crate::space_converters::{to_cell_space, CellCoord};
+
+    #[test]
+    fn test_modifiers_stay_held_across_updates() {
+        let mut input = Input::new();
+        assert!(!input.ctrl() && !input.shift());
+
+        input.update_modifiers(ModifiersState::SHIFT | ModifiersState::ALT);
+        input.reset();
+
+        assert!(input.shift());
+        assert!(!input.ctrl());
+
+        // all released
+        input.update_modifiers(ModifiersState::empty());
+        assert!(!input.shift());
+    }
+
+    #[test]
+    fn test_click_between_redraws_survives_to_next_update() {
+        let mut input = Input::new();
+        input.update_mouse_position(PhysicalPosition::new(10.0, 20.0));
+        // end of the previous frame
+        input.reset();
+
+        input.update_mouse(&MouseButton::Left, &ElementState::Pressed);
+        input.reset();
+
+        input.poll_click();
+        assert_eq!(
+            input.mouse_left_clicked.as_ref().map(|xy| (xy.0, xy.1)),
+            Some((10.0, 20.0))
+        );
+    }
+
+    #[test]
+    fn test_clicks_within_one_frame_are_handled_one_per_update() {
+        let mut input = Input::new();
+        input.update_mouse_position(PhysicalPosition::new(10.0, 20.0));
+        input.update_mouse(&MouseButton::Left, &ElementState::Pressed);
+        input.update_mouse(&MouseButton::Right, &ElementState::Pressed);
+
+        input.poll_click();
+        assert!(input.mouse_left_clicked.is_some());
+        assert!(!input.mouse_right_clicked);
+        input.reset();
+
+        input.poll_click();
+        assert!(input.mouse_left_clicked.is_none());
+        assert!(input.mouse_right_clicked);
+        input.reset();
+
+        input.poll_click();
+        assert!(input.mouse_left_clicked.is_none());
+        assert!(!input.mouse_right_clicked);
+    }
+
+    #[test]
+    fn test_clicks_before_the_cursor_moved_are_ignored() {
+        let mut input = Input::new();
+        assert!(!input.is_cursor_positioned());
+
+        assert!(!input.update_mouse(&MouseButton::Left, &ElementState::Pressed));
+        input.poll_click();
+        assert!(input.mouse_left_clicked.is_none());
+
+        input.update_mouse_position(PhysicalPosition::new(10.0, 20.0));
+        assert!(input.is_cursor_positioned());
+        assert!(input.update_mouse(&MouseButton::Left, &ElementState::Pressed));
+        input.poll_click();
+        assert!(input.mouse_left_clicked.is_some());
+    }
+
+    #[test]
+    fn test_touch_acts_as_the_mouse() {
+        let mut touch = Input::new();
+        assert!(touch.update_touch(1, TouchPhase::Started, PhysicalPosition::new(10.0, 20.0)));
+        // a second finger neither clicks nor moves the cursor
+        assert!(!touch.update_touch(2, TouchPhase::Started, PhysicalPosition::new(90.0, 90.0)));
+        touch.update_touch(2, TouchPhase::Moved, PhysicalPosition::new(80.0, 80.0));
+        touch.update_touch(1, TouchPhase::Moved, PhysicalPosition::new(30.0, 40.0));
+        touch.update_touch(1, TouchPhase::Ended, PhysicalPosition::new(30.0, 40.0));
+
+        let mut mouse = Input::new();
+        mouse.update_mouse_position(PhysicalPosition::new(10.0, 20.0));
+        mouse.update_mouse(&MouseButton::Left, &ElementState::Pressed);
+        mouse.update_mouse_position(PhysicalPosition::new(30.0, 40.0));
+        mouse.update_mouse(&MouseButton::Left, &ElementState::Released);
+
+        for input in [&mut touch, &mut mouse] {
+            input.poll_click();
+            let clicked = input.mouse_left_clicked.as_ref().map(|xy| (xy.0, xy.1));
+            assert_eq!(clicked, Some((10.0, 20.0)));
+            assert_eq!(
+                (input.mouse_position.0, input.mouse_position.1),
+                (30.0, 40.0)
+            );
+            input.reset();
+            input.poll_click();
+            assert!(input.mouse_left_clicked.is_none());
+        }
+
+        // lifting the primary finger lets the next one take over
+        assert!(touch.update_touch(2, TouchPhase::Started, PhysicalPosition::new(50.0, 60.0)));
+    }
+
+    #[test]
+    fn test_click_at_2x_dpi_maps_to_logical_cell() {
+        let config = UserRenderConfig::default();
+        let mut input = Input::new();
+        input.set_scale_factor(2.0);
+        // middle of board cell (2, 1), in physical pixels
+        let x = config.board_offset_x_px + 2.5 * config.cell_size_px;
+        let y = config.board_offset_y_px + 1.5 * config.cell_size_px;
+        input.update_mouse_position(PhysicalPosition::new(2.0 * x as f64, 2.0 * y as f64));
+
+        let cell = to_cell_space(
+            XY(config.board_offset_x_px, config.board_offset_y_px),
+            config.cell_size_px,
+            &input.mouse_position,
+        );
+        assert_eq!(cell, CellCoord::new(2, 1));
+    }
+
+    #[test]
+    fn test_cursor_smoothing_converges_to_the_mouse() {
+        let target = XY(100.0, 50.0);
+        let frame = Duration::from_millis(8);
+        let time_constant = Duration::from_millis(40);
+
+        let mut position = XY(0.0, 0.0);
+        let mut last_distance = f32::MAX;
+        for _ in 0..60 {
+            position = ema(&position, &target, frame, time_constant);
+            let distance = (target.0 - position.0).hypot(target.1 - position.1);
+            assert!(distance < last_distance);
+            last_distance = distance;
+        }
+        assert!(last_distance < 0.01);
+
+        // one time constant covers 1 - 1/e of the way, however it's split into frames
+        let once = ema(&XY(0.0, 0.0), &target, time_constant, time_constant);
+        let mut split = XY(0.0, 0.0);
+        for _ in 0..5 {
+            split = ema(&split, &target, frame, time_constant);
+        }
+        assert!((once.0 - split.0).abs() < 1e-3);
+        assert!((once.0 - 100.0 * (1.0 - (-1.0f32).exp())).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_clicks_ignore_the_smoothed_cursor() {
+        let mut input = Input::new();
+        input.update_mouse_position(PhysicalPosition::new(0.0, 0.0));
+        input.smooth_cursor(Duration::from_millis(8), Duration::from_millis(40));
+        input.update_mouse_position(PhysicalPosition::new(100.0, 0.0));
+        input.smooth_cursor(Duration::from_millis(8), Duration::from_millis(40));
+
+        assert!(input.cursor_position().0 < 100.0);
+        input.update_mouse(&MouseButton::Left, &ElementState::Pressed);
+        input.poll_click();
+        assert_eq!(
+            input.mouse_left_clicked.as_ref().map(|xy| xy.0),
+            Some(100.0)
+        );
     }
 }
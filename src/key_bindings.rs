@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use winit::keyboard::KeyCode;
+
+use crate::settings::SettingsFile;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, EnumIter)]
+pub enum Action {
+    Quit,
+    // play the failed level again, on the game over screen
+    Retry,
+    // turns the held shape clockwise, counter-clockwise with Shift
+    Rotate,
+    // update, render and present timings overlay
+    ToggleFrameStats,
+    // with Ctrl held, a new run from level 1
+    Restart,
+    // takes back the last placement
+    Undo,
+    // stops the game until pressed again
+    Pause,
+}
+
+impl Action {
+    // name in the settings file
+    fn setting_key(&self) -> &'static str {
+        match self {
+            Action::Quit => "key.quit",
+            Action::Retry => "key.retry",
+            Action::Rotate => "key.rotate",
+            Action::ToggleFrameStats => "key.frame_stats",
+            Action::Restart => "key.restart",
+            Action::Undo => "key.undo",
+            Action::Pause => "key.pause",
+        }
+    }
+
+    fn default_key(&self) -> KeyCode {
+        match self {
+            Action::Quit => KeyCode::Escape,
+            Action::Retry => KeyCode::KeyR,
+            Action::Rotate => KeyCode::Space,
+            Action::ToggleFrameStats => KeyCode::F3,
+            Action::Restart => KeyCode::KeyR,
+            Action::Undo => KeyCode::KeyZ,
+            Action::Pause => KeyCode::KeyP,
+        }
+    }
+
+    // only triggered with Ctrl held, so it can share its key with a plain action
+    fn needs_ctrl(&self) -> bool {
+        matches!(self, Action::Restart)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    keys: HashMap<Action, KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::iter().map(|a| (a, a.default_key())).collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    // with `ctrl` a key falls back to its plain action when no Ctrl action is bound to it
+    pub fn action_for(&self, key: &KeyCode, ctrl: bool) -> Option<Action> {
+        let bound = |needs_ctrl: bool| {
+            Action::iter()
+                .find(|action| action.needs_ctrl() == needs_ctrl && self.keys[action] == *key)
+        };
+        if ctrl {
+            bound(true).or_else(|| bound(false))
+        } else {
+            bound(false)
+        }
+    }
+
+    pub fn key_for(&self, action: Action) -> KeyCode {
+        self.keys[&action]
+    }
+
+    #[allow(dead_code)]
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        self.keys.insert(action, key);
+    }
+
+    // actions that are missing from the settings, bound to an unknown key or to one another action
+    // already has keep their default
+    pub fn load(settings: &SettingsFile) -> Self {
+        let mut bindings = Self::default();
+        let mut configured = Vec::new();
+        for action in Action::iter() {
+            let Some(name) = settings.get(action.setting_key()) else {
+                continue;
+            };
+            match key_code_from_name(name) {
+                Some(key) => {
+                    bindings.keys.insert(action, key);
+                    configured.push(action);
+                }
+                None => println!(
+                    "Unknown key {:?} for {:?}, using {:?}",
+                    name,
+                    action,
+                    action.default_key()
+                ),
+            }
+        }
+        for action in configured {
+            let key = bindings.keys[&action];
+            let taken_by = Action::iter().find(|other| {
+                *other != action
+                    && other.needs_ctrl() == action.needs_ctrl()
+                    && bindings.keys[other] == key
+            });
+            if let Some(other) = taken_by {
+                println!(
+                    "Key {:?} for {:?} is already bound to {:?}, using {:?}",
+                    key,
+                    action,
+                    other,
+                    action.default_key()
+                );
+                bindings.keys.insert(action, action.default_key());
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self, settings: &mut SettingsFile) {
+        for action in Action::iter() {
+            settings.set(action.setting_key(), format!("{:?}", self.keys[&action]));
+        }
+    }
+}
+
+// keys that can be bound, stored by their `Debug` name (e.g. `KeyR`, `F3`)
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Escape,
+];
+
+fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .find(|key| format!("{:?}", key) == name)
+        .copied()
+}
+
+// how a key is shown to the player, `R` rather than `KeyR`
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("Key") {
+        Some(letter) => letter.to_string(),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::temp_settings_path;
+
+    #[test]
+    fn test_custom_binding_round_trip() {
+        let path = temp_settings_path("key_bindings_round_trip");
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Action::Quit, KeyCode::KeyQ);
+        let mut settings = SettingsFile::default();
+        bindings.save(&mut settings);
+        settings.write(&path).unwrap();
+
+        let loaded = KeyBindings::load(&SettingsFile::read(&path).unwrap());
+
+        assert_eq!(loaded, bindings);
+        assert_eq!(loaded.action_for(&KeyCode::KeyQ, false), Some(Action::Quit));
+        assert_eq!(loaded.action_for(&KeyCode::Escape, false), None);
+    }
+
+    #[test]
+    fn test_unknown_key_falls_back_to_default() {
+        let settings = SettingsFile::parse("key.quit=NotAKey\n");
+
+        let loaded = KeyBindings::load(&settings);
+
+        assert_eq!(loaded.key_for(Action::Quit), KeyCode::Escape);
+    }
+
+    #[test]
+    fn test_key_bound_twice_keeps_the_default() {
+        // Z is the default undo key
+        let settings = SettingsFile::parse("key.quit=KeyZ\nkey.pause=KeyX\nkey.rotate=KeyX\n");
+
+        let loaded = KeyBindings::load(&settings);
+
+        assert_eq!(loaded.key_for(Action::Quit), KeyCode::Escape);
+        assert_eq!(loaded.key_for(Action::Undo), KeyCode::KeyZ);
+        assert_eq!(loaded.key_for(Action::Rotate), KeyCode::Space);
+        assert_eq!(loaded.key_for(Action::Pause), KeyCode::KeyX);
+        // Ctrl+R and R are different bindings
+        let settings = SettingsFile::parse("key.restart=KeyR\n");
+        assert_eq!(KeyBindings::load(&settings), KeyBindings::default());
+    }
+
+    #[test]
+    fn test_action_for_follows_declaration_order() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Action::Pause, KeyCode::Escape);
+
+        assert_eq!(
+            bindings.action_for(&KeyCode::Escape, false),
+            Some(Action::Quit)
+        );
+    }
+}
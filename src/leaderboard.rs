@@ -0,0 +1,141 @@
+use crate::date::Date;
+use crate::settings::SettingsFile;
+
+pub const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub score: i32,
+    // YYYY-MM-DD, as it's stored
+    pub date: String,
+    pub level: u16,
+}
+
+impl LeaderboardEntry {
+    pub fn today(score: i32, level: u16) -> Self {
+        Self {
+            score,
+            date: Date::today().to_string(),
+            level,
+        }
+    }
+
+    // `score,date,level` in the settings file
+    fn to_setting(&self) -> String {
+        format!("{},{},{}", self.score, self.date, self.level)
+    }
+
+    fn from_setting(value: &str) -> Option<Self> {
+        let mut parts = value.split(',').map(str::trim);
+        let entry = Self {
+            score: parts.next()?.parse().ok()?,
+            date: parts.next()?.to_string(),
+            level: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(entry)
+    }
+}
+
+// best scores first, at most `LEADERBOARD_SIZE` of them
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Leaderboard {
+    entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    pub fn entries(&self) -> &[LeaderboardEntry] {
+        &self.entries
+    }
+
+    // returns the place the entry got, none when it didn't make it onto the board. A tie goes
+    // below the scores that were there first.
+    pub fn insert(&mut self, entry: LeaderboardEntry) -> Option<usize> {
+        let place = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if place >= LEADERBOARD_SIZE {
+            return None;
+        }
+        self.entries.insert(place, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        Some(place)
+    }
+
+    // entries that can't be parsed are dropped
+    pub fn load(settings: &SettingsFile) -> Self {
+        let mut leaderboard = Self::default();
+        for place in 0..LEADERBOARD_SIZE {
+            if let Some(entry) = settings
+                .get(&setting_key(place))
+                .and_then(LeaderboardEntry::from_setting)
+            {
+                leaderboard.insert(entry);
+            }
+        }
+        leaderboard
+    }
+
+    pub fn save(&self, settings: &mut SettingsFile) {
+        for (place, entry) in self.entries.iter().enumerate() {
+            settings.set(&setting_key(place), entry.to_setting());
+        }
+    }
+}
+
+fn setting_key(place: usize) -> String {
+    format!("leaderboard.{}", place + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: i32) -> LeaderboardEntry {
+        LeaderboardEntry {
+            score,
+            date: "2025-01-02".to_string(),
+            level: 3,
+        }
+    }
+
+    #[test]
+    fn test_insert_keeps_scores_sorted() {
+        let mut leaderboard = Leaderboard::default();
+        assert_eq!(leaderboard.insert(entry(10)), Some(0));
+        assert_eq!(leaderboard.insert(entry(30)), Some(0));
+        assert_eq!(leaderboard.insert(entry(20)), Some(1));
+        assert_eq!(leaderboard.insert(entry(20)), Some(2));
+
+        let scores: Vec<i32> = leaderboard.entries().iter().map(|e| e.score).collect();
+        assert_eq!(scores, vec![30, 20, 20, 10]);
+    }
+
+    #[test]
+    fn test_leaderboard_keeps_the_top_ten() {
+        let mut leaderboard = Leaderboard::default();
+        for score in 1..=LEADERBOARD_SIZE as i32 {
+            leaderboard.insert(entry(score * 10));
+        }
+
+        assert_eq!(leaderboard.insert(entry(5)), None);
+        assert_eq!(leaderboard.insert(entry(55)), Some(5));
+        assert_eq!(leaderboard.entries().len(), LEADERBOARD_SIZE);
+        assert_eq!(leaderboard.entries().last().unwrap().score, 20);
+    }
+
+    #[test]
+    fn test_leaderboard_round_trip() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(entry(40));
+        leaderboard.insert(entry(70));
+        let mut settings = SettingsFile::default();
+        leaderboard.save(&mut settings);
+        settings.set("leaderboard.3", "not,an entry".to_string());
+
+        let loaded = Leaderboard::load(&SettingsFile::parse(&settings.to_text()));
+
+        assert_eq!(loaded, leaderboard);
+    }
+}
@@ -1,32 +1,48 @@
 use std::collections::VecDeque;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
-use winit::event_loop::EventLoopWindowTarget;
-use winit::{
-    event::*,
-    event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
-    window::WindowBuilder,
-};
+use winit::{event::*, event_loop::EventLoop, keyboard::PhysicalKey, window::WindowBuilder};
 
 use render::render::Render;
 
 use crate::events::Event::SelectedShapePlaced;
-use crate::game_entities::{Game, GameState, SelectedShape, ShapeState};
+use crate::game_config::{BoardOrigin, GameConfig, RampCurve, Symmetry};
+use crate::game_entities::{BaseShapeType, Board, Game, GameState, Panel};
+use crate::game_loop::GameLoop;
 use crate::input::Input;
 use crate::render::render::UserRenderConfig;
-use crate::system::{
-    NewGameSystem, PlacementSystem, ScoreCleanupSystem, SelectionValidationSystem, System,
-    WinOrLoseSystem,
-};
+use crate::settings::{Settings, SettingsFile};
+use crate::sound::Sound;
+use crate::window_placement::WindowPlacement;
 
+mod board_code;
+mod board_image;
+mod date;
 mod events;
+mod game_config;
 mod game_entities;
+mod game_loop;
+mod goal;
+mod high_score;
 mod input;
+mod key_bindings;
+mod leaderboard;
+mod menu;
+mod patterns;
+mod placement_rule;
+mod presets;
+mod profiler;
 mod render;
+mod save;
+mod settings;
+mod solver;
 mod sound;
 mod space_converters;
 mod system;
+#[cfg(all(test, feature = "testing"))]
+mod testing;
+mod tutorial;
+mod window_placement;
 
 pub async fn run() {
     let mut frame_count = 0;
@@ -34,34 +50,253 @@ pub async fn run() {
     let hardware_settings = HardwareSettings { target_fps: 120 };
     let frame_time: Duration = Duration::from_secs_f64(1.0 / hardware_settings.target_fps as f64);
 
-    let config = UserRenderConfig::default();
+    // prints the names `--audio-device` takes and quits
+    if std::env::args().any(|arg| arg == "--list-audio-devices") {
+        for device in sound::SoundSystem::list_devices() {
+            println!("{}", device);
+        }
+        return;
+    }
+
+    let settings_path = settings::settings_path();
+    let mut settings = Settings::read(&settings_path);
+    let mut config = UserRenderConfig {
+        reduce_motion: settings.reduce_motion,
+        ..UserRenderConfig::default()
+    };
+    // e.g. FLIP_FLOP_BACKEND=gl where the default pick doesn't work
+    if let Ok(name) = std::env::var("FLIP_FLOP_BACKEND") {
+        match render::render::backends_from_name(&name) {
+            Some(backends) => config.backends = backends,
+            None => println!("Unknown render backend {:?}, using auto", name),
+        }
+    }
+    // panel and board on the right, for left-handed players
+    if std::env::args().any(|arg| arg == "--mirror") {
+        config = config.mirrored();
+    }
+    // `--panel-shapes <n>` deals n shapes a round instead of 3
+    let panel_shapes: Option<usize> = std::env::args()
+        .skip_while(|arg| arg != "--panel-shapes")
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n > 0);
+    if let Some(n) = panel_shapes {
+        config = config.with_panel_shapes(n);
+    }
+    // `--symmetry mirror` or `rotational` deals symmetric boards with a bonus for keeping them so
+    let symmetry = match std::env::args()
+        .skip_while(|arg| arg != "--symmetry")
+        .nth(1)
+    {
+        Some(name) => match Symmetry::from_name(&name) {
+            Some(symmetry) => Some(symmetry),
+            None => {
+                println!("Unknown symmetry {:?}, expected mirror or rotational", name);
+                return;
+            }
+        },
+        None => None,
+    };
+    // `--decay-ramp linear` or `ease-in` drains points while idle, faster over the level
+    let decay_ramp = std::env::args()
+        .skip_while(|arg| arg != "--decay-ramp")
+        .nth(1)
+        .and_then(|name| RampCurve::from_name(&name));
+    // the rotate key turns the held shape
+    let rotation = std::env::args().any(|arg| arg == "--rotation");
+    // shapes hanging over an edge come back on the other side
+    let wraparound = std::env::args().any(|arg| arg == "--wraparound");
+    // every placement has to touch a filled cell
+    let must_touch_existing = std::env::args().any(|arg| arg == "--must-touch");
+    // the game over screen offers retry and quit as a menu, picked with the arrow keys
+    let game_over_menu = std::env::args().any(|arg| arg == "--menu");
+    // `--practice <shape>` deals only that shape, e.g. `--practice S`
+    let forced_shape = match std::env::args()
+        .skip_while(|arg| arg != "--practice")
+        .nth(1)
+    {
+        Some(name) => match BaseShapeType::from_name(&name) {
+            Some(shape) => Some(shape),
+            None => {
+                println!(
+                    "Unknown shape {:?}, expected one of T1, L1, I1, O, OO, S, Z, J, I3",
+                    name
+                );
+                return;
+            }
+        },
+        None => None,
+    };
+    // prints the board to stdout after every placement
+    let dump_board = std::env::args().any(|arg| arg == "--dump");
     env_logger::init();
     let event_loop = EventLoop::new().unwrap();
     let size = config.window_size;
-    let window = WindowBuilder::new()
-        .with_visible(false)
-        .with_title("flip flop")
-        .with_inner_size(size)
+    let placement = WindowPlacement::from_config(&config);
+    let window = placement
+        .configure(
+            WindowBuilder::new()
+                .with_visible(false)
+                .with_title("flip flop")
+                .with_inner_size(size),
+        )
         .build(&event_loop)
         .unwrap();
 
     window.set_cursor_visible(true);
 
     let mut render = pollster::block_on(Render::new(&window, config.clone()));
-    let mut game = Game::new_level(config.board_size_cols, 1, 0);
+    if placement.enter_fullscreen(&window) {
+        // the surface was configured for the windowed size
+        render.resize(window.inner_size());
+    }
+    // the same game for everyone playing today
+    let mut game = if std::env::args().any(|arg| arg == "--daily") {
+        Game::daily(config.board_size_cols, date::Date::today())
+    } else if std::env::args().any(|arg| arg == "--classic") {
+        // none of the optional mechanics
+        Game::new_level_with_config(GameConfig::classic(), config.board_size_cols, 1, 0)
+    } else {
+        Game::new_level_with_config(
+            GameConfig {
+                symmetry,
+                rotation,
+                wraparound,
+                must_touch_existing,
+                game_over_menu,
+                forced_shape,
+                ..decay_ramp.map_or_else(GameConfig::default, GameConfig::decaying)
+            },
+            config.board_size_cols,
+            1,
+            0,
+        )
+    };
+    if let Some(n) = panel_shapes {
+        game.config.panel_shapes = n;
+        game.panel = if game.config.solvable_first_panel {
+            Panel::generate_solvable(&mut game)
+        } else {
+            Panel::generate(&mut game.rng, &mut game.shape_bag, &game.config)
+        };
+    }
+    // `--autosave <n>` writes the recovery file every n placements, for `--resume`
+    game.config.autosave_every = std::env::args()
+        .skip_while(|arg| arg != "--autosave")
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n > 0);
+    // `--goal lines` or `cells` finishes levels by clearing instead of by score
+    if let Some(name) = std::env::args().skip_while(|arg| arg != "--goal").nth(1) {
+        match goal::LevelGoal::from_name(&name) {
+            Some(level_goal) => game.config.level_goal = level_goal,
+            None => {
+                println!("Unknown goal {:?}, expected score, lines or cells", name);
+                return;
+            }
+        }
+    }
+    // `--bottom-left` counts board rows from the bottom, for levels ported from tools that do
+    if std::env::args().any(|arg| arg == "--bottom-left") {
+        game.config.board_origin = BoardOrigin::BottomLeft;
+    }
+    // starts on a shared board, `--board <code>` with a code printed by `--dump`
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(code) = args
+        .iter()
+        .position(|arg| arg == "--board")
+        .and_then(|ix| args.get(ix + 1))
+    {
+        match Board::from_code(code) {
+            // the board is drawn square
+            Ok(board) if board.cols == board.rows => game.replace_board(board),
+            Ok(board) => println!(
+                "Could not load the board {:?}: {} x {} isn't square",
+                code, board.cols, board.rows
+            ),
+            Err(e) => println!("Could not load the board {:?}: {:?}", code, e),
+        }
+    }
+    // `--board-image <path>` saves a picture of the starting board and quits, e.g. of a shared one
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--board-image")
+        .and_then(|ix| args.get(ix + 1))
+    {
+        match game.board.to_image(board_image::EXPORT_CELL_PX).save(path) {
+            Ok(()) => println!("Saved the board to {:?}", path),
+            Err(e) => println!("Could not save the board image to {:?}: {}", path, e),
+        }
+        return;
+    }
+    let recovery_path = save::recovery_path();
+    if recovery_path.exists() {
+        if std::env::args().any(|arg| arg == "--resume") {
+            match SettingsFile::read(&recovery_path)
+                .ok()
+                .and_then(|recovery| save::load_game(&recovery, game.config.clone()))
+            {
+                Some(recovered) => {
+                    println!("Resuming from {:?}", recovery_path);
+                    game = recovered;
+                }
+                None => println!("Could not resume from {:?}", recovery_path),
+            }
+        } else {
+            println!(
+                "Found an autosave at {:?}, start with --resume to continue from it",
+                recovery_path
+            );
+        }
+    }
 
-    let sound_system = sound::SoundSystem::new();
-    let sound_pack = sound::SoundPack::new();
+    let mut sound_pack = sound::SoundPack::new();
+    sound_pack.load_placement_clips(&settings.placement_sounds);
     let mut game_event_queue: VecDeque<events::Event> = VecDeque::new();
     let mut input = Input::new();
+    input.set_scale_factor(render::render::render_scale(&config, window.scale_factor()));
 
-    let selection_system = SelectionValidationSystem;
-    let placement_system = PlacementSystem;
-    let score_cleanup_system = ScoreCleanupSystem;
-    let game_progress_system = WinOrLoseSystem;
-    let new_game_system = NewGameSystem;
+    input.key_bindings = settings.key_bindings.clone();
+    // `--audio-device <name>` is remembered for the next start
+    let chosen_device = args
+        .iter()
+        .position(|arg| arg == "--audio-device")
+        .and_then(|ix| args.get(ix + 1));
+    if let Some(device) = chosen_device {
+        settings.audio_device = Some(device.clone());
+    }
+    let mut sound_system = match &settings.audio_device {
+        Some(device) => sound::SoundSystem::with_device(device),
+        None => sound::SoundSystem::new(),
+    };
+    // write the defaults out, so there's a file to edit the bindings in, and upgraded files back
+    if !settings_path.exists() || settings.migrated_from.is_some() || chosen_device.is_some() {
+        if let Err(e) = settings.write(&settings_path) {
+            println!("Could not write settings to {:?}: {}", settings_path, e);
+        }
+    }
 
-    window.set_visible(true);
+    game.leaderboard = settings.leaderboard.clone();
+    game.stats.best_score = high_score::load_high_score(&high_score::high_score_path());
+    // played through once is enough
+    if settings.tutorial_done {
+        game.config.tutorial = false;
+        game.tutorial = None;
+    }
+    let mut tutorial_running = game.tutorial.is_some();
+    let mut last_game_state = game.game_state;
+
+    let music_tracks = settings.music.clone();
+    // level the music was last picked for
+    let mut music_level = None;
+
+    let game_loop = GameLoop::with_save_hook(Some(Box::new(save::write_recovery)));
+
+    placement.show(
+        || render.render_state(&mut game, &input),
+        || window.set_visible(true),
+    );
     let mut last_time = instant::Instant::now();
 
     let window = &window;
@@ -69,19 +304,11 @@ pub async fn run() {
         .run(move |event, control_flow| {
             match event {
                 Event::WindowEvent {
-                    event:
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            event:
-                                KeyEvent {
-                                    state: ElementState::Pressed,
-                                    physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        },
+                    event: WindowEvent::CloseRequested,
                     ..
                 } => control_flow.exit(),
+                // nothing to resume after quitting normally
+                Event::LoopExiting => save::remove_recovery(&save::recovery_path()),
                 Event::WindowEvent {
                     event:
                         WindowEvent::KeyboardInput {
@@ -95,11 +322,17 @@ pub async fn run() {
                         },
                     ..
                 } => {
-                    let input_handled = input.update_kb(&key, &element_state);
-                    if !input_handled {
-                        ignore_input(&element_state, &key, control_flow);
+                    input.update_kb(&key, &element_state);
+                    if input.quit_pressed {
+                        control_flow.exit();
                     }
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::ModifiersChanged(modifiers),
+                    ..
+                } => {
+                    input.update_modifiers(modifiers.state());
+                }
                 Event::WindowEvent {
                     event: WindowEvent::CursorMoved { position, .. },
                     ..
@@ -112,6 +345,12 @@ pub async fn run() {
                 } => {
                     input.update_mouse(&button, &state);
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::Touch(touch),
+                    ..
+                } if config.touch_input => {
+                    input.update_touch(touch.id, touch.phase, touch.location);
+                }
                 Event::WindowEvent {
                     event: WindowEvent::RedrawRequested,
                     ..
@@ -119,83 +358,95 @@ pub async fn run() {
                     let dt = last_time.elapsed();
                     let frame_start = Instant::now();
                     last_time = Instant::now();
+                    input.poll_click();
 
-                    game_progress_system.update_state(
-                        &input,
-                        dt,
-                        &mut game,
-                        &mut game_event_queue,
-                        &config,
-                        None,
-                    );
-
-                    if game.game_state == GameState::MoveToNextLevel {
-                        new_game_system.update_state(
-                            &input,
-                            dt,
-                            &mut game,
-                            &mut game_event_queue,
-                            &config,
-                            None,
-                        )
+                    let update_start = Instant::now();
+                    let handled =
+                        game_loop.update(&input, dt, &mut game, &mut game_event_queue, &config);
+                    render.record_update_time(update_start.elapsed());
+                    if game.quit_requested {
+                        control_flow.exit();
                     }
-
-                    if game.game_state == GameState::Playing {
-                        selection_system.update_state(
-                            &input,
-                            dt,
-                            &mut game,
-                            &mut game_event_queue,
-                            &config,
-                            None,
-                        );
-
-                        while let Some(event) = game_event_queue.pop_front() {
-                            match event {
-                                events::Event::ShapeSelected(n, coord) => {
-                                    game.deselect();
-                                    let selected_shape =
-                                        game.panel.shape_choice.get_mut(n).unwrap();
-                                    game.selected_shape = Some(SelectedShape {
-                                        shape_type: selected_shape.kind,
-                                        anchor_offset: coord,
-                                    });
-                                    selected_shape.set_state(ShapeState::SELECTED);
-                                    game.ui.need_to_update_panel = true;
-                                    println!("Shape {:?} is selected", &selected_shape);
-                                }
-                                SelectedShapePlaced(_, _) => {
-                                    placement_system.update_state(
-                                        &input,
-                                        dt,
-                                        &mut game,
-                                        &mut game_event_queue,
-                                        &config,
-                                        Some(&event),
-                                    );
-                                    score_cleanup_system.update_state(
-                                        &input,
-                                        dt,
-                                        &mut game,
-                                        &mut game_event_queue,
-                                        &config,
-                                        None,
-                                    );
-                                    sound_system.queue(sound_pack.bounce());
-                                }
+                    if game.board.cols != config.board_size_cols
+                        || game.board.rows != config.board_size_rows
+                    {
+                        config = config.resized_board(game.board.cols, game.board.rows);
+                        render.resize_board(config.clone());
+                        // the new index buffer is empty, both parts have to be uploaded again
+                        game.ui.need_to_update_board = true;
+                        game.ui.need_to_update_panel = true;
+                    }
+                    if tutorial_running && game.tutorial.is_none() {
+                        tutorial_running = false;
+                        // not again after a restart either
+                        game.config.tutorial = false;
+                        settings.tutorial_done = true;
+                        if let Err(e) = settings.write(&settings_path) {
+                            println!("Could not write settings to {:?}: {}", settings_path, e);
+                        }
+                    }
+                    if game.game_state != last_game_state {
+                        last_game_state = game.game_state;
+                        if game.game_state == GameState::GameOver {
+                            save::remove_recovery(&save::recovery_path());
+                        }
+                        if game.game_state == GameState::GameOver
+                            && game.stats.total_score > game.stats.best_score
+                        {
+                            game.stats.best_score = game.stats.total_score;
+                            let path = high_score::high_score_path();
+                            if let Err(e) =
+                                high_score::save_high_score(&path, game.stats.best_score)
+                            {
+                                println!("Could not write the high score to {:?}: {}", path, e);
+                            }
+                        }
+                        if game.game_state == GameState::GameOver && game.config.keep_leaderboard {
+                            settings.leaderboard = game.leaderboard.clone();
+                            if let Err(e) = settings.write(&settings_path) {
+                                println!(
+                                    "Could not write the leaderboard to {:?}: {}",
+                                    settings_path, e
+                                );
+                            }
+                        }
+                    }
+                    if music_level != Some(game.stats.level) {
+                        music_level = Some(game.stats.level);
+                        match sound::music_for_level(&music_tracks, game.stats.level) {
+                            Some(track) => sound_system.play_music(track),
+                            None => sound_system.stop_music(),
+                        }
+                    }
+                    for event in &handled {
+                        match sound_pack.sound_for(event) {
+                            Some(Sound::Placed(shape)) => {
+                                sound_system.queue(sound_pack.placement(shape))
+                            }
+                            Some(Sound::Select) => sound_system.queue(sound_pack.select()),
+                            Some(Sound::Deselect) => sound_system.queue(sound_pack.deselect()),
+                            Some(Sound::Clear(lines)) => sound_system
+                                .queue_with_rate(sound_pack.clear(), sound_pack.clear_rate(lines)),
+                            None => {}
+                        }
+                        if let SelectedShapePlaced(_, _) = event {
+                            if dump_board {
+                                println!("{}", game.board.to_ascii());
+                                println!("{}", game.board.to_code());
                             }
                         }
-
-                        score_cleanup_system.update_state(
-                            &input,
-                            dt,
-                            &mut game,
-                            &mut game_event_queue,
-                            &config,
-                            None,
-                        );
                     }
 
+                    // the cursor lagging behind the mouse is motion as well
+                    let smoothing = config.cursor_smoothing.filter(|_| !config.reduce_motion);
+                    if let Some(time_constant) = smoothing {
+                        input.smooth_cursor(dt, time_constant);
+                    }
+                    if input.frame_stats_toggled {
+                        render.toggle_frame_stats();
+                        // an idle screen wouldn't be drawn again otherwise
+                        game.ui.need_to_update_board = true;
+                    }
                     // todo pass UI out of the game?
                     render.render_state(&mut game, &input);
                     input.reset();
@@ -216,6 +467,37 @@ pub async fn run() {
                     }
                 }
 
+                Event::WindowEvent {
+                    event: WindowEvent::Focused(focused),
+                    ..
+                } => {
+                    println!("Window focused: {}", focused);
+                    game.focus_changed(focused);
+                    if sound_system.mute_on_focus_loss {
+                        sound_system.set_muted(!focused);
+                    }
+                }
+
+                Event::WindowEvent {
+                    event: WindowEvent::Occluded(occluded),
+                    ..
+                } => {
+                    println!("Window occluded: {}", occluded);
+                    render.set_occluded(occluded);
+                    if !occluded {
+                        // whatever was on the surface might be gone
+                        game.ui.need_to_update_board = true;
+                        game.ui.need_to_update_panel = true;
+                    }
+                }
+
+                Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { scale_factor, .. },
+                    ..
+                } => {
+                    input.set_scale_factor(render::render::render_scale(&config, scale_factor));
+                }
+
                 Event::WindowEvent {
                     event: WindowEvent::Resized(size),
                     ..
@@ -229,17 +511,6 @@ pub async fn run() {
         .unwrap();
 }
 
-fn ignore_input(
-    element_state: &ElementState,
-    keycode: &KeyCode,
-    control_flow: &EventLoopWindowTarget<()>,
-) {
-    match (keycode, element_state) {
-        (KeyCode::Escape, ElementState::Pressed) => control_flow.exit(),
-        _ => {}
-    }
-}
-
 fn main() {
     pollster::block_on(run());
 }
@@ -0,0 +1,100 @@
+// what choosing a menu item does, carried out by the screen showing the menu
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    RetryLevel,
+    Quit,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuItem {
+    pub label: &'static str,
+    pub action: MenuAction,
+}
+
+// up/down/enter navigation shared by every screen with a list of choices, moving past either
+// end wraps around
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuState {
+    pub items: Vec<MenuItem>,
+    pub selected: usize,
+}
+
+impl MenuState {
+    // the first item starts out selected, there has to be at least one
+    pub fn new(items: Vec<MenuItem>) -> Self {
+        assert!(!items.is_empty(), "a menu needs something to choose");
+        Self { items, selected: 0 }
+    }
+
+    // shown instead of the retry prompt, see `GameConfig::game_over_menu`
+    pub fn game_over(allow_retry: bool) -> Self {
+        let mut items = vec![];
+        if allow_retry {
+            items.push(MenuItem {
+                label: "Retry level",
+                action: MenuAction::RetryLevel,
+            });
+        }
+        items.push(MenuItem {
+            label: "Quit",
+            action: MenuAction::Quit,
+        });
+        Self::new(items)
+    }
+
+    pub fn up(&mut self) {
+        self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % self.items.len();
+    }
+
+    pub fn select(&self) -> MenuAction {
+        self.items[self.selected].action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu_of(actions: &[MenuAction]) -> MenuState {
+        MenuState::new(
+            actions
+                .iter()
+                .map(|&action| MenuItem {
+                    label: "item",
+                    action,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_menu_navigation_wraps_around() {
+        let mut menu = menu_of(&[MenuAction::RetryLevel, MenuAction::Quit, MenuAction::Quit]);
+
+        menu.up();
+        assert_eq!(menu.selected, 2);
+        menu.down();
+        assert_eq!(menu.selected, 0);
+        menu.down();
+        menu.down();
+        menu.down();
+        assert_eq!(menu.selected, 0);
+    }
+
+    #[test]
+    fn test_menu_selects_the_highlighted_action() {
+        let mut menu = MenuState::game_over(true);
+        assert_eq!(menu.select(), MenuAction::RetryLevel);
+        menu.down();
+        assert_eq!(menu.select(), MenuAction::Quit);
+
+        // nothing to retry, quitting is all that's left
+        let mut menu = MenuState::game_over(false);
+        menu.down();
+        assert_eq!(menu.select(), MenuAction::Quit);
+    }
+}
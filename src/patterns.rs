@@ -0,0 +1,79 @@
+use strum_macros::EnumIter;
+
+use crate::game_entities::{Board, Cell};
+
+// bonus objectives, each one awarded when a placement completes it
+#[derive(Clone, Copy, Debug, PartialEq, EnumIter)]
+pub enum Pattern {
+    // top-left to bottom-right, as long as the shorter side of the board
+    MainDiagonal,
+    FourCorners,
+}
+
+impl Pattern {
+    // (col, row) of the cells that have to be filled
+    pub fn cells(self, board: &Board) -> Vec<(usize, usize)> {
+        if board.cols == 0 || board.rows == 0 {
+            return vec![];
+        }
+        match self {
+            Pattern::MainDiagonal => (0..board.cols.min(board.rows)).map(|i| (i, i)).collect(),
+            Pattern::FourCorners => {
+                let (right, bottom) = (board.cols - 1, board.rows - 1);
+                vec![(0, 0), (right, 0), (0, bottom), (right, bottom)]
+            }
+        }
+    }
+
+    pub fn is_complete(self, board: &Board) -> bool {
+        match self {
+            Pattern::MainDiagonal => full_main_diagonal(board),
+            Pattern::FourCorners => four_corners(board),
+        }
+    }
+}
+
+pub fn full_main_diagonal(board: &Board) -> bool {
+    all_filled(board, &Pattern::MainDiagonal.cells(board))
+}
+
+pub fn four_corners(board: &Board) -> bool {
+    all_filled(board, &Pattern::FourCorners.cells(board))
+}
+
+fn all_filled(board: &Board, cells: &[(usize, usize)]) -> bool {
+    !cells.is_empty()
+        && cells
+            .iter()
+            .all(|&(col, row)| board.get(col, row) == Some(&Cell::Filled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_main_diagonal() {
+        let board = Board::from_ascii("#..\n.#.\n..#\n").unwrap();
+        assert!(full_main_diagonal(&board));
+        assert!(!four_corners(&board));
+
+        let gap = Board::from_ascii("###\n...\n###\n").unwrap();
+        assert!(!full_main_diagonal(&gap));
+
+        // only the square part of a wide board counts
+        let wide = Board::from_ascii("#...\n.#..\n").unwrap();
+        assert!(full_main_diagonal(&wide));
+    }
+
+    #[test]
+    fn test_four_corners() {
+        let board = Board::from_ascii("#..#\n....\n#..#\n").unwrap();
+        assert!(four_corners(&board));
+        assert!(!full_main_diagonal(&board));
+
+        let three = Board::from_ascii("#..#\n....\n#...\n").unwrap();
+        assert!(!four_corners(&three));
+        assert!(!four_corners(&Board::new(0)));
+    }
+}
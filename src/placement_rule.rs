@@ -0,0 +1,97 @@
+use crate::game_entities::{Board, Cell};
+use crate::space_converters::CellCoord;
+
+// extra constraint for variant modes, consulted on top of the bounds/overlap checks in
+// `Game::is_valid_placement`. `target_cells` are the board cells the shape would occupy.
+pub trait PlacementRule {
+    fn allows(&self, board: &Board, target_cells: &[CellCoord]) -> bool;
+}
+
+// at least one target cell has to be adjacent (up/down/left/right) to a filled cell.
+// An empty board has nothing to touch, so any placement is allowed there.
+pub struct MustTouchExisting;
+impl PlacementRule for MustTouchExisting {
+    fn allows(&self, board: &Board, target_cells: &[CellCoord]) -> bool {
+        if board.grid.iter().all(|c| c == &Cell::Empty) {
+            return true;
+        }
+
+        target_cells.iter().any(|cell| {
+            [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .any(|(dc, dr)| is_filled(board, cell.col + dc, cell.row + dr))
+        })
+    }
+}
+
+fn is_filled(board: &Board, col: i16, row: i16) -> bool {
+    if col < 0 || row < 0 || col as usize >= board.cols || row as usize >= board.rows {
+        return false;
+    }
+    board
+        .get(col as usize, row as usize)
+        .is_some_and(|x| x == &Cell::Filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_config::GameConfig;
+    use crate::game_entities::{BaseShapeType, Game, ShapeRot, ShapeType};
+
+    fn game_with_single_filled_cell() -> Game {
+        let mut game = Game::new_level(6, 1, 0);
+        game.board = Board::new(6);
+        game.board.set_cell(2, 2, Cell::Filled);
+        game.placement_rule = Some(Box::new(MustTouchExisting));
+        game
+    }
+
+    #[test]
+    fn test_must_touch_accepts_adjacent_placement() {
+        let game = game_with_single_filled_cell();
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+
+        assert!(game.is_valid_placement(&single, &CellCoord::new(3, 2)));
+        assert!(game.is_valid_placement(&single, &CellCoord::new(2, 1)));
+        // square covering (0,2) (1,2) (0,3) (1,3) touches (2,2) from the left
+        assert!(game.is_valid_placement(&square, &CellCoord::new(0, 2)));
+    }
+
+    #[test]
+    fn test_must_touch_rejects_isolated_placement() {
+        let game = game_with_single_filled_cell();
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+
+        // diagonal neighbour does not count
+        assert!(!game.is_valid_placement(&single, &CellCoord::new(3, 3)));
+        assert!(!game.is_valid_placement(&single, &CellCoord::new(5, 5)));
+        assert!(!game.is_valid_placement(&square, &CellCoord::new(4, 4)));
+    }
+
+    #[test]
+    fn test_no_rule_accepts_isolated_placement() {
+        let mut game = game_with_single_filled_cell();
+        game.placement_rule = None;
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        assert!(game.is_valid_placement(&single, &CellCoord::new(5, 5)));
+    }
+
+    #[test]
+    fn test_must_touch_existing_config_sets_the_rule() {
+        let config = GameConfig {
+            must_touch_existing: true,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new_level_with_config(config, 6, 1, 0);
+        game.board = Board::new(6);
+        game.board.set_cell(2, 2, Cell::Filled);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        assert!(game.is_valid_placement(&single, &CellCoord::new(3, 2)));
+        assert!(!game.is_valid_placement(&single, &CellCoord::new(5, 5)));
+    }
+}
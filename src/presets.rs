@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use crate::game_config::{GameConfig, RampCurve, SpeedRamp};
+use crate::goal::LevelGoal;
+
+impl GameConfig {
+    // the game as it was before any of the optional mechanics: random panels played as dealt,
+    // points for cleared cells only, nothing timed and no variant rules. What isn't a rule
+    // (tutorial, leaderboard, autosave, retry) stays as in `default`.
+    pub fn classic() -> Self {
+        Self {
+            pick_up_placed_shapes: false,
+            solvable_first_panel: false,
+            anti_frustration: false,
+            soft_drop_scoring: false,
+            board_growth: false,
+            level_goal: LevelGoal::Score,
+            wraparound: false,
+            perfect_clear_bonus: 0,
+            pattern_bonus: 0,
+            sticky_selection: false,
+            level_intro: None,
+            symmetry: None,
+            bonus_cells: 0,
+            score_decay_grace: None,
+            game_over_checks_orientations: false,
+            rotation: false,
+            combo_scoring: false,
+            forced_shape: None,
+            shape_bag: false,
+            ..Self::default()
+        }
+    }
+
+    // points drain while nothing is placed, a little after each placement at first and faster
+    // along `curve` as the level goes on
+    pub fn decaying(curve: RampCurve) -> Self {
+        Self {
+            score_decay_grace: Some(Duration::from_secs(5)),
+            score_decay_ramp: Some(SpeedRamp {
+                curve,
+                ramp_time: Duration::from_secs(120),
+                max_factor: 3.0,
+            }),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_preset_turns_the_mechanics_off() {
+        let classic = GameConfig::classic();
+
+        assert!(!classic.pick_up_placed_shapes);
+        assert!(!classic.solvable_first_panel);
+        assert!(!classic.anti_frustration);
+        assert!(!classic.soft_drop_scoring);
+        assert!(!classic.board_growth);
+        assert!(!classic.wraparound);
+        assert!(!classic.sticky_selection);
+        assert!(!classic.game_over_checks_orientations);
+        assert!(!classic.rotation);
+        assert!(!classic.combo_scoring);
+        assert!(!classic.shape_bag);
+        assert_eq!(classic.level_goal, LevelGoal::Score);
+        assert_eq!(
+            (
+                classic.perfect_clear_bonus,
+                classic.pattern_bonus,
+                classic.bonus_cells
+            ),
+            (0, 0, 0)
+        );
+        assert_eq!(classic.symmetry, None);
+        assert_eq!(classic.level_intro, None);
+        assert_eq!(classic.score_decay_grace, None);
+    }
+
+    #[test]
+    fn test_decaying_preset_ramps_along_its_curve() {
+        let config = GameConfig::decaying(RampCurve::EaseIn);
+
+        assert!(config.score_decay_grace.is_some());
+        assert_eq!(
+            config.score_decay_ramp.map(|ramp| ramp.curve),
+            Some(RampCurve::EaseIn)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+// the timed parts of a frame, in the order they happen
+#[derive(Clone, Copy, Debug, PartialEq, EnumIter)]
+pub enum FramePhase {
+    // game logic, `GameLoop::update`
+    Update,
+    // building and submitting the draw commands
+    Render,
+    // handing the frame over to the window, this is where vsync waits
+    Present,
+}
+
+impl FramePhase {
+    fn label(self) -> &'static str {
+        match self {
+            FramePhase::Update => "update",
+            FramePhase::Render => "render",
+            FramePhase::Present => "present",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub avg: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+// timings of the last `capacity` frames for every phase, older ones are dropped
+pub struct Profiler {
+    timings: Vec<VecDeque<Duration>>,
+    capacity: usize,
+}
+
+impl Profiler {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            timings: FramePhase::iter()
+                .map(|_| VecDeque::with_capacity(capacity))
+                .collect(),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, phase: FramePhase, duration: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+        let timings = &mut self.timings[phase as usize];
+        if timings.len() == self.capacity {
+            timings.pop_front();
+        }
+        timings.push_back(duration);
+    }
+
+    // all zeros until the phase was timed once
+    pub fn summary(&self, phase: FramePhase) -> FrameStats {
+        let mut sorted: Vec<Duration> = self.timings[phase as usize].iter().copied().collect();
+        if sorted.is_empty() {
+            return FrameStats::default();
+        }
+        sorted.sort();
+        // nearest rank: the smallest timing that 95% of the frames don't go over
+        let p95_rank = (sorted.len() * 95).div_ceil(100);
+        FrameStats {
+            avg: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p95: sorted[p95_rank - 1],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    // a line per phase for the overlay, in milliseconds
+    pub fn overlay_text(&self) -> String {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        FramePhase::iter()
+            .map(|phase| {
+                let stats = self.summary(phase);
+                format!(
+                    "{:<8} avg {:>5.2}  p95 {:>5.2}  max {:>5.2} ms\n",
+                    phase.label(),
+                    ms(stats.avg),
+                    ms(stats.p95),
+                    ms(stats.max)
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_of_synthetic_frames() {
+        let mut profiler = Profiler::new(100);
+        for ms in 1..=100 {
+            profiler.record(FramePhase::Render, Duration::from_millis(ms));
+        }
+
+        let stats = profiler.summary(FramePhase::Render);
+
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.avg, Duration::from_micros(50_500));
+        assert_eq!(profiler.summary(FramePhase::Update), FrameStats::default());
+    }
+
+    #[test]
+    fn test_only_recent_frames_are_summed_up() {
+        let mut profiler = Profiler::new(4);
+        profiler.record(FramePhase::Present, Duration::from_millis(50));
+        for _ in 0..4 {
+            profiler.record(FramePhase::Present, Duration::from_millis(2));
+        }
+
+        let stats = profiler.summary(FramePhase::Present);
+
+        // the slow frame fell out of the window
+        assert_eq!(stats.max, Duration::from_millis(2));
+        assert_eq!(stats.p95, Duration::from_millis(2));
+    }
+}
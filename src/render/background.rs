@@ -0,0 +1,257 @@
+use std::f32::consts::TAU;
+use std::mem::size_of;
+use std::time::Duration;
+
+use bytemuck::{cast_slice, Pod, Zeroable};
+use winit::dpi::PhysicalSize;
+
+use crate::render::render::{render_rect, UserRenderConfig};
+use crate::render::vertex::Vertex;
+use crate::space_converters::XY;
+
+// how far the background reaches past the outer grid points, in cells
+const BACKGROUND_PADDING: f32 = 0.25;
+// one full pulse of the danger zone border
+const DANGER_PULSE_PERIOD: Duration = Duration::from_millis(1000);
+
+// `RoundedRect` in rounded_rect.wgsl, padded to 16 bytes like uniforms are
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
+pub struct RoundedRectParams {
+    center: [f32; 2],
+    half_size: [f32; 2],
+    radius: f32,
+    // 0..1, how red the border is
+    warning: f32,
+    _padding: [f32; 2],
+}
+
+// rect given in logical pixels, the shader works on physical ones. The radius can't be more
+// than half of the shorter side, zero gives sharp corners.
+pub fn rounded_rect_params(
+    top_left: XY,
+    size: XY,
+    radius_px: f32,
+    scale_factor: f32,
+    warning: f32,
+) -> RoundedRectParams {
+    let half_size = [size.0 * scale_factor / 2.0, size.1 * scale_factor / 2.0];
+    RoundedRectParams {
+        center: [
+            top_left.0 * scale_factor + half_size[0],
+            top_left.1 * scale_factor + half_size[1],
+        ],
+        half_size,
+        radius: (radius_px * scale_factor).clamp(0.0, half_size[0].min(half_size[1])),
+        warning,
+        _padding: [0.0; 2],
+    }
+}
+
+// border redness `elapsed` into the danger zone, starts at 0 and peaks halfway through a period
+pub fn danger_pulse(elapsed: Duration) -> f32 {
+    let phase = elapsed.as_secs_f32() / DANGER_PULSE_PERIOD.as_secs_f32();
+    0.5 - 0.5 * (phase * TAU).cos()
+}
+
+// `danger_pulse`, held at its peak with `UserRenderConfig::reduce_motion`
+pub fn steady_pulse(render_config: &UserRenderConfig, elapsed: Duration) -> f32 {
+    if render_config.reduce_motion {
+        1.0
+    } else {
+        danger_pulse(elapsed)
+    }
+}
+
+// (top left, size) of the board and the panel backgrounds, in logical pixels
+fn background_rects(render_config: &UserRenderConfig) -> [(XY, XY); 2] {
+    let cell = render_config.cell_size_px;
+    let pad = cell * BACKGROUND_PADDING;
+    let board_width = cell * render_config.board_size_cols as f32;
+    let board_height = cell * render_config.board_size_rows as f32;
+    [
+        (
+            XY(
+                render_config.board_offset_x_px - pad,
+                render_config.board_offset_y_px - pad,
+            ),
+            XY(board_width + 2.0 * pad, board_height + 2.0 * pad),
+        ),
+        (
+            XY(
+                render_config.panel_offset_x_px - pad,
+                render_config.panel_offset_y_px - pad,
+            ),
+            XY(
+                cell * render_config.panel_cols as f32 + 2.0 * pad,
+                cell * render_config.panel_rows as f32 + 2.0 * pad,
+            ),
+        ),
+    ]
+}
+
+// one quad and one uniform per background
+pub struct BackgroundRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    rects: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+}
+
+impl BackgroundRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Background Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let rects = (0..2)
+            .map(|_| {
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Background Uniform Buffer"),
+                    size: size_of::<RoundedRectParams>() as wgpu::BufferAddress,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Background Bind Group"),
+                    layout: &layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                (buffer, bind_group)
+            })
+            .collect();
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Background Vertex Buffer"),
+            size: (size_of::<Vertex>() * 6 * 2) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../../res/shaders/rounded_rect.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Background Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::DESC],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // the corners fade out
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            rects,
+        }
+    }
+
+    // goes first, everything else is drawn over it. Leaves its own pipeline set.
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        queue: &wgpu::Queue,
+        render_config: &UserRenderConfig,
+        physical_size: PhysicalSize<u32>,
+        board_warning: f32,
+    ) {
+        let scale_factor = physical_size.width as f32 / render_config.window_size.width as f32;
+        let mut vertices = vec![];
+        // the board goes first, only its border warns
+        let warnings = [board_warning, 0.0];
+        for (((top_left, size), (buffer, _)), warning) in background_rects(render_config)
+            .iter()
+            .zip(&self.rects)
+            .zip(warnings)
+        {
+            vertices.extend(render_rect(
+                top_left.clone(),
+                size.0,
+                size.1,
+                &render_config.window_size,
+            ));
+            let params = rounded_rect_params(
+                top_left.clone(),
+                size.clone(),
+                render_config.background_corner_radius_px,
+                scale_factor,
+                warning,
+            );
+            queue.write_buffer(buffer, 0, cast_slice(&[params]));
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, cast_slice(&vertices));
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        for (i, (_, bind_group)) in self.rects.iter().enumerate() {
+            render_pass.set_bind_group(0, bind_group, &[]);
+            let first = i as u32 * 6;
+            render_pass.draw(first..first + 6, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corner_radius_is_scaled_and_clamped() {
+        let params = rounded_rect_params(XY(10.0, 20.0), XY(100.0, 40.0), 8.0, 2.0, 0.0);
+
+        assert_eq!(params.center, [120.0, 80.0]);
+        assert_eq!(params.half_size, [100.0, 40.0]);
+        assert_eq!(params.radius, 16.0);
+
+        // can't be rounder than half the shorter side
+        let pill = rounded_rect_params(XY(0.0, 0.0), XY(100.0, 40.0), 500.0, 1.0, 0.0);
+        assert_eq!(pill.radius, 20.0);
+    }
+
+    #[test]
+    fn test_zero_radius_keeps_sharp_corners() {
+        let params = rounded_rect_params(XY(0.0, 0.0), XY(100.0, 40.0), 0.0, 2.0, 0.0);
+        assert_eq!(params.radius, 0.0);
+
+        let negative = rounded_rect_params(XY(0.0, 0.0), XY(100.0, 40.0), -5.0, 2.0, 0.0);
+        assert_eq!(negative.radius, 0.0);
+    }
+}
@@ -0,0 +1,173 @@
+use std::borrow::Cow;
+use std::num::NonZeroU64;
+
+use bytemuck::cast_slice;
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::render::vertex::CursorState;
+
+// how the fragment shader gets the `CursorState` of a draw
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorStateBinding {
+    PushConstants,
+    // for adapters without push constants (some GL/WebGL ones): one uniform slot per state,
+    // picked with a dynamic offset
+    Uniform,
+}
+
+impl CursorStateBinding {
+    pub fn select(adapter_features: wgpu::Features, force_uniform: bool) -> Self {
+        if !force_uniform && adapter_features.contains(wgpu::Features::PUSH_CONSTANTS) {
+            CursorStateBinding::PushConstants
+        } else {
+            CursorStateBinding::Uniform
+        }
+    }
+
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            CursorStateBinding::PushConstants => wgpu::Features::PUSH_CONSTANTS,
+            CursorStateBinding::Uniform => wgpu::Features::empty(),
+        }
+    }
+
+    // the fragment shader is written with a push constant, the uniform variant only swaps the
+    // declaration
+    pub fn fragment_shader_source(self, source: &str) -> Cow<'static, str> {
+        match self {
+            CursorStateBinding::PushConstants => Cow::Owned(source.to_string()),
+            CursorStateBinding::Uniform => Cow::Owned(source.replace(
+                "var<push_constant> c: PushConstants;",
+                "@group(0) @binding(0) var<uniform> c: PushConstants;",
+            )),
+        }
+    }
+}
+
+// uniforms are padded to 16 bytes
+const UNIFORM_SIZE: u64 = 16;
+
+pub struct CursorStateBinder {
+    binding: CursorStateBinding,
+    // (uniform buffer, bind group, distance between two slots), only for `Uniform`
+    uniform: Option<(wgpu::Buffer, wgpu::BindGroup, u32)>,
+    bind_group_layout: Option<wgpu::BindGroupLayout>,
+}
+
+impl CursorStateBinder {
+    pub fn new(device: &wgpu::Device, binding: CursorStateBinding) -> Self {
+        if binding == CursorStateBinding::PushConstants {
+            return Self {
+                binding,
+                uniform: None,
+                bind_group_layout: None,
+            };
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cursor State Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: NonZeroU64::new(UNIFORM_SIZE),
+                },
+                count: None,
+            }],
+        });
+
+        let stride = device.limits().min_uniform_buffer_offset_alignment;
+        let mut contents = vec![0u32; (stride as usize / 4) * CursorState::COUNT];
+        for state in CursorState::iter() {
+            contents[state as usize * stride as usize / 4] = state as u32;
+        }
+        let buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Cursor State Uniform Buffer"),
+                contents: cast_slice(&contents),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cursor State Bind Group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(UNIFORM_SIZE),
+                }),
+            }],
+        });
+
+        Self {
+            binding,
+            uniform: Some((buffer, bind_group, stride)),
+            bind_group_layout: Some(layout),
+        }
+    }
+
+    pub fn bind_group_layouts(&self) -> Vec<&wgpu::BindGroupLayout> {
+        self.bind_group_layout.iter().collect()
+    }
+
+    pub fn push_constant_ranges(&self) -> Vec<wgpu::PushConstantRange> {
+        match self.binding {
+            CursorStateBinding::PushConstants => vec![wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::FRAGMENT,
+                range: 0..4,
+            }],
+            CursorStateBinding::Uniform => vec![],
+        }
+    }
+
+    pub fn set(&self, render_pass: &mut wgpu::RenderPass<'_>, state: CursorState) {
+        match &self.uniform {
+            None => render_pass.set_push_constants(
+                wgpu::ShaderStages::FRAGMENT,
+                0,
+                cast_slice(&[state as u32]),
+            ),
+            Some((_, bind_group, stride)) => {
+                render_pass.set_bind_group(0, bind_group, &[state as u32 * stride])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binding_follows_adapter_features() {
+        let with_push = wgpu::Features::PUSH_CONSTANTS | wgpu::Features::DEPTH_CLIP_CONTROL;
+
+        assert_eq!(
+            CursorStateBinding::select(with_push, false),
+            CursorStateBinding::PushConstants
+        );
+        assert_eq!(
+            CursorStateBinding::select(wgpu::Features::DEPTH_CLIP_CONTROL, false),
+            CursorStateBinding::Uniform
+        );
+        assert_eq!(
+            CursorStateBinding::select(with_push, true),
+            CursorStateBinding::Uniform
+        );
+        assert!(CursorStateBinding::Uniform.required_features().is_empty());
+    }
+
+    #[test]
+    fn test_uniform_shader_declares_a_uniform() {
+        let source = include_str!("../../res/shaders/textured.frag.wgsl");
+        let uniform = CursorStateBinding::Uniform.fragment_shader_source(source);
+
+        assert!(!uniform.contains("var<push_constant>"));
+        assert!(uniform.contains("var<uniform> c: PushConstants;"));
+    }
+}
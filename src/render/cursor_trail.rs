@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use bytemuck::{cast_slice, Pod, Zeroable};
+use winit::dpi::PhysicalSize;
+
+use crate::render::vertex::Vertex;
+use crate::space_converters::XY;
+
+// side of the quad left at the newest position, older ones shrink along with their alpha
+const TRAIL_QUAD_PX: f32 = 6.0;
+const TRAIL_MAX_ALPHA: f32 = 0.6;
+
+// last mouse positions, oldest first, at most `capacity` of them
+pub struct TrailHistory {
+    positions: VecDeque<XY>,
+    capacity: usize,
+}
+
+impl TrailHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            positions: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    // a mouse that didn't move adds nothing, the trail then shrinks to the cursor
+    pub fn push(&mut self, position: &XY) {
+        if self.capacity == 0 {
+            return;
+        }
+        let moved = self
+            .positions
+            .back()
+            .is_none_or(|last| last.0 != position.0 || last.1 != position.1);
+        if moved {
+            if self.positions.len() == self.capacity {
+                self.positions.pop_front();
+            }
+            self.positions.push_back(position.clone());
+        } else if self.positions.len() > 1 {
+            self.positions.pop_front();
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    // alpha grows from the oldest to the newest position
+    fn faded(&self) -> impl Iterator<Item = (&XY, f32)> {
+        let count = self.positions.len() as f32;
+        self.positions
+            .iter()
+            .enumerate()
+            .map(move |(i, position)| (position, TRAIL_MAX_ALPHA * (i + 1) as f32 / count))
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TrailVertex {
+    position: [f32; 2],
+    alpha: f32,
+}
+
+impl TrailVertex {
+    const DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32,
+        ],
+    };
+}
+
+fn trail_vertices(history: &TrailHistory, window_size: &PhysicalSize<u32>) -> Vec<TrailVertex> {
+    let mut vertices = vec![];
+    for (XY(x, y), alpha) in history.faded() {
+        let half = TRAIL_QUAD_PX * alpha / TRAIL_MAX_ALPHA / 2.0;
+        let corner = |dx: f32, dy: f32| TrailVertex {
+            position: Vertex::ndc_vertex(x + dx, y + dy, window_size, false)
+                .position
+                .into(),
+            alpha,
+        };
+        let (top_left, bot_left, bot_right, top_right) = (
+            corner(-half, -half),
+            corner(-half, half),
+            corner(half, half),
+            corner(half, -half),
+        );
+        vertices.extend([
+            bot_left, bot_right, top_left, top_left, bot_right, top_right,
+        ]);
+    }
+    vertices
+}
+
+pub struct TrailRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+}
+
+impl TrailRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, capacity: usize) -> Self {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cursor Trail Vertex Buffer"),
+            size: (size_of::<TrailVertex>() * 6 * capacity.max(1)) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cursor Trail Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../../res/shaders/trail.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cursor Trail Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[TrailVertex::DESC],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+        }
+    }
+
+    // sets its own pipeline, the caller has to set its pipeline again afterwards
+    pub fn draw(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        queue: &wgpu::Queue,
+        history: &TrailHistory,
+        window_size: &PhysicalSize<u32>,
+    ) {
+        let vertices = trail_vertices(history, window_size);
+        if vertices.is_empty() {
+            return;
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, cast_slice(&vertices));
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trail_keeps_the_last_n_positions() {
+        let mut history = TrailHistory::new(3);
+        for x in 0..5 {
+            history.push(&XY(x as f32, 0.0));
+        }
+
+        assert_eq!(history.len(), 3);
+        let xs: Vec<f32> = history.faded().map(|(position, _)| position.0).collect();
+        assert_eq!(xs, vec![2.0, 3.0, 4.0]);
+        let alphas: Vec<f32> = history.faded().map(|(_, alpha)| alpha).collect();
+        assert!(alphas[0] < alphas[1] && alphas[1] < alphas[2]);
+        assert_eq!(alphas[2], TRAIL_MAX_ALPHA);
+    }
+
+    #[test]
+    fn test_trail_shrinks_while_the_mouse_rests() {
+        let mut history = TrailHistory::new(3);
+        history.push(&XY(1.0, 0.0));
+        history.push(&XY(2.0, 0.0));
+
+        history.push(&XY(2.0, 0.0));
+        assert_eq!(history.len(), 1);
+
+        let mut off = TrailHistory::new(0);
+        off.push(&XY(1.0, 0.0));
+        assert_eq!(off.len(), 0);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod background;
+pub mod cursor_state;
+pub mod cursor_trail;
 pub mod render;
 pub mod text_system;
 pub(crate) mod vertex;
@@ -1,9 +1,11 @@
 use std::collections::{HashMap, HashSet};
 use std::iter;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use bytemuck::cast_slice;
 use glyphon::Resolution;
+use strum::IntoEnumIterator;
 use wgpu::util::DeviceExt;
 use wgpu::{
     MemoryHints, PipelineLayout, RenderPipeline, ShaderModule, SurfaceConfiguration, TextureFormat,
@@ -12,15 +14,31 @@ use wgpu::{
 use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
-use crate::game_entities::{Board, Game, Panel, SelectedShape, UI};
+use crate::game_config::BoardOrigin;
+use crate::game_entities::{
+    BaseShapeType, Board, Cell, ClearedLines, Game, GameState, Panel, PanelRefresh,
+    PanelRefreshPhase, SelectedShape, Shape, ShapeState, ShapeType, UI,
+};
+use crate::goal;
 use crate::input::Input;
-use crate::render::text_system::TextSystem;
+use crate::key_bindings::{key_name, Action};
+use crate::profiler::{FramePhase, Profiler};
+use crate::render::background::{steady_pulse, BackgroundRenderer};
+use crate::render::cursor_state::{CursorStateBinder, CursorStateBinding};
+use crate::render::cursor_trail::{TrailHistory, TrailRenderer};
+use crate::render::text_system::{
+    HudValues, LegendEntry, PlacementBadge, TextSystem, TutorialPrompt,
+};
 use crate::render::vertex::{
     generate_board_vertices, generate_panel_vertices, normalize_screen_to_ndc, CursorState, Vertex,
 };
+use crate::solver;
 use crate::space_converters::{
-    over_board, render_board, render_panel, to_cell_space, CellCoord, Edge, XY,
+    aimed_placement, board_index_writes, clamp_to_board, off_play_area, over_board,
+    render_bonus_cells, render_line_gaps, render_line_outlines, render_lines, render_panel,
+    render_shape_colors, to_cell_space, CellCoord, Edge, XY,
 };
+use crate::tutorial::TutorialStep;
 
 const FONT_BYTES: &[u8] = include_bytes!("../../res/DejaVuSans.ttf");
 
@@ -31,6 +49,7 @@ pub struct UserRenderConfig {
     pub panel_cols: usize,
     pub panel_rows: usize,
     pub board_size_cols: usize,
+    pub board_size_rows: usize,
 
     // pixel space settings
     pub cursor_size: f32,
@@ -42,13 +61,126 @@ pub struct UserRenderConfig {
 
     // number of the frames to show after no game state changes
     pub lingering_frames: u8,
+    // same, for the states where nothing animates (game over etc.)
+    pub idle_lingering_frames: u8,
+    // small preview of all four rotations of the panel shape under the cursor
+    pub show_rotation_preview: bool,
+    // small box on the board cell under the mouse while a shape is selected
+    pub show_snap_indicator: bool,
+    // "Filled: N%" line in the HUD
+    pub show_fill_percentage: bool,
+    // used instead of `window.scale_factor()`, for setups that report a wrong one
+    pub scale_override: Option<f64>,
+    // the more lines cleared at once, the warmer the flash. White for any clear otherwise.
+    pub line_flash_by_count: bool,
+    // placed cells and the shape under the cursor in a colour per shape type, seeded cells keep
+    // the plain one
+    pub color_by_shape: bool,
+    // dot where each panel shape is anchored when it's picked up
+    pub show_panel_origin_markers: bool,
+    // debug listing of the panel shapes with their size and number of valid placements
+    pub show_strategy_hud: bool,
+    // under every panel shape, how many places on the board it fits
+    pub show_placement_badges: bool,
+    // what the colours on the board stand for, only the ones that can show up
+    pub show_legend: bool,
+    // faint copy of the best placement the solver finds, it gives the game away so it's off
+    pub show_best_move_ghost: bool,
+    // marks the empty cells of lines that this many cells or fewer would complete
+    pub near_full_hint: Option<usize>,
+    // while aiming, lines the shape would complete where it is are lit up
+    pub aim_clear_hint: bool,
+    // no frames are drawn while the window is hidden, e.g. minimized or behind another window
+    pub pause_render_when_occluded: bool,
+    // a drag-resize sends a burst of sizes, only the last one before a frame reconfigures the
+    // surface
+    pub coalesce_resizes: bool,
+    // how long the held shape takes to turn into a new orientation, none snaps right away
+    pub rotation_animation: Option<Duration>,
+    // full lines stay on the board, tinted, for this long before they are emptied. None empties
+    // them right away.
+    pub clear_animation: Option<Duration>,
+    // length of each half of a panel refresh, the old shapes sliding out and the new ones in
+    pub panel_refresh_animation: Option<Duration>,
+    // pass the cursor state in a uniform buffer even when push constants are supported
+    pub force_uniform_cursor_state: bool,
+    // "Goal: N/M" line and a bar under it showing how close the level goal is
+    pub show_goal_progress: bool,
+    // startup window placement, see `WindowPlacement`. Fullscreen uses the monitor's resolution.
+    pub fullscreen: bool,
+    pub window_position: Option<(i32, i32)>,
+    // the window is created hidden and only shown once the first frame is drawn, instead of
+    // flashing whatever its buffer held
+    pub render_before_visible: bool,
+    // clears of at least `slow_motion_min_lines` lines replay their flash in slow motion for
+    // `slow_motion_duration` of real time
+    pub slow_motion_clears: bool,
+    pub slow_motion_min_lines: usize,
+    pub slow_motion_duration: Duration,
+    // corners of the board and panel backgrounds, 0 for sharp ones
+    pub background_corner_radius_px: f32,
+    // points per scoring category on the game over screen
+    pub show_score_breakdown: bool,
+    // top ten scores on the game over screen
+    pub show_leaderboard: bool,
+    // how many of the visible panel shapes fit somewhere, while playing
+    pub show_placeable_count: bool,
+    // fading quads along the last `cursor_trail_length` mouse positions
+    pub show_cursor_trail: bool,
+    pub cursor_trail_length: usize,
+    // frames the update, render and present timings are kept for, shown with the frame stats
+    // key. 0 doesn't time anything.
+    pub frame_stats_window: usize,
+    // the board border pulses red while at least `danger_zone_threshold` of the cells are filled
+    pub show_danger_zone: bool,
+    pub danger_zone_threshold: f32,
+    // rows and cols at least this full get a pulsing outline, none turns it off
+    pub edge_glow_threshold: Option<f32>,
+    // panel and board on the right, HUD on the left. Set through `mirrored`, which moves them.
+    pub layout_mirror: bool,
+    // the drawn cursor eases toward the mouse with this time constant, none follows it exactly
+    pub cursor_smoothing: Option<Duration>,
+    // touchscreen taps and drags are handled like the mouse, see `Input::update_touch`
+    pub touch_input: bool,
+    // accessibility, also handy in tests: no flashes, slides, turns, pulses, cursor smoothing or
+    // trail, everything changes at once. Kept in the settings file.
+    pub reduce_motion: bool,
+    // with the cursor off the board and panel, the shadow still shows at the nearest board edge
+    pub snap_preview_to_board: bool,
+    // HUD text grows with the window, see `text_scale`
+    pub scale_text: bool,
+    // graphics APIs wgpu may pick from, see `backends_from_name`
+    pub backends: wgpu::Backends,
 }
 const SCREEN_WIDTH: u32 = 1200;
 const SCREEN_HEIGHT: u32 = 800;
+const IDLE_LINGERING_FRAMES: u8 = 2;
+const LINE_FLASH_BLINK: Duration = Duration::from_millis(100);
+const SLOW_MOTION_DURATION: Duration = Duration::from_millis(1200);
+const ROTATION_PREVIEW_SCALE: f32 = 0.4;
+// part of the cell left empty on each side of the snap indicator box
+const SNAP_INDICATOR_INSET: f32 = 0.3;
+// the cursor shape takes the first 5 quads of the cursor buffer, the snap box goes after them
+const SNAP_INDICATOR_VERTEX_OFFSET: usize = 6 * 5;
+const MAX_PANEL_ORIGIN_MARKERS: usize = 16;
+// goal progress bar, in the HUD column under the target score
+const GOAL_BAR_LEFT_PX: f32 = 800.0;
+const GOAL_BAR_TOP_PX: f32 = 270.0;
+const GOAL_BAR_WIDTH_PX: f32 = 200.0;
+const GOAL_BAR_HEIGHT_PX: f32 = 16.0;
+// level intro thumbnail of the board, in the HUD column
+const THUMBNAIL_LEFT_PX: f32 = 800.0;
+const THUMBNAIL_TOP_PX: f32 = 400.0;
+const THUMBNAIL_SCALE: f32 = 0.5;
+// height of the tutorial text above the panel or board it points at
+const TUTORIAL_PROMPT_RAISE_PX: f32 = 32.0;
+// between the bottom of the panel and the placement badges
+const PLACEMENT_BADGE_GAP_PX: f32 = 4.0;
 
 impl Default for UserRenderConfig {
     fn default() -> Self {
-        Self::new(12, 5, 10, 10.0, 30.0, 100.0, 100.0, 100.0, 100.0, 10)
+        // as wide as the 3 shapes `GameConfig::default` deals can get
+        Self::new(14, 5, 10, 10.0, 30.0, 100.0, 100.0, 100.0, 100.0, 10)
     }
 }
 
@@ -74,6 +206,7 @@ impl UserRenderConfig {
             panel_cols,
             panel_rows,
             board_size_cols: board_size,
+            board_size_rows: board_size,
             cursor_size,
             cell_size_px,
             board_offset_x_px,
@@ -81,10 +214,147 @@ impl UserRenderConfig {
             panel_offset_x_px,
             panel_offset_y_px, // Correctly computed here
             lingering_frames,
+            idle_lingering_frames: IDLE_LINGERING_FRAMES,
+            show_rotation_preview: false,
+            show_snap_indicator: true,
+            show_fill_percentage: true,
+            scale_override: None,
+            line_flash_by_count: true,
+            color_by_shape: true,
+            show_panel_origin_markers: false,
+            show_strategy_hud: false,
+            show_placement_badges: false,
+            show_legend: false,
+            show_best_move_ghost: false,
+            near_full_hint: None,
+            aim_clear_hint: true,
+            pause_render_when_occluded: true,
+            coalesce_resizes: true,
+            rotation_animation: Some(Duration::from_millis(90)),
+            clear_animation: Some(Duration::from_millis(250)),
+            panel_refresh_animation: Some(Duration::from_millis(150)),
+            force_uniform_cursor_state: false,
+            show_goal_progress: true,
+            fullscreen: false,
+            window_position: None,
+            render_before_visible: true,
+            slow_motion_clears: true,
+            slow_motion_min_lines: 3,
+            slow_motion_duration: SLOW_MOTION_DURATION,
+            background_corner_radius_px: 8.0,
+            show_score_breakdown: true,
+            show_leaderboard: true,
+            show_placeable_count: true,
+            show_cursor_trail: false,
+            cursor_trail_length: 8,
+            frame_stats_window: 240,
+            show_danger_zone: true,
+            danger_zone_threshold: 0.8,
+            edge_glow_threshold: None,
+            layout_mirror: false,
+            cursor_smoothing: None,
+            touch_input: true,
+            reduce_motion: false,
+            snap_preview_to_board: false,
+            scale_text: true,
+            backends: wgpu::Backends::all(),
+        }
+    }
+
+    // same layout for a board of another size, the panel moves along with the board's bottom edge
+    pub fn resized_board(&self, cols: usize, rows: usize) -> Self {
+        let delta_cols = cols as f32 - self.board_size_cols as f32;
+        let delta_rows = rows as f32 - self.board_size_rows as f32;
+        // a mirrored board keeps its right edge
+        let board_offset_x_px = if self.layout_mirror {
+            self.board_offset_x_px - self.cell_size_px * delta_cols
+        } else {
+            self.board_offset_x_px
+        };
+        Self {
+            board_size_cols: cols,
+            board_size_rows: rows,
+            board_offset_x_px,
+            panel_offset_y_px: self.panel_offset_y_px + self.cell_size_px * delta_rows,
+            ..self.clone()
+        }
+    }
+
+    // a panel wide enough for `shapes` shapes side by side, see `Panel::cols_for`. A mirrored panel
+    // keeps its right edge.
+    pub fn with_panel_shapes(&self, shapes: usize) -> Self {
+        let panel_cols = Panel::cols_for(shapes);
+        let delta = panel_cols as f32 - self.panel_cols as f32;
+        let panel_offset_x_px = if self.layout_mirror {
+            self.panel_offset_x_px - self.cell_size_px * delta
+        } else {
+            self.panel_offset_x_px
+        };
+        Self {
+            panel_cols,
+            panel_offset_x_px,
+            ..self.clone()
+        }
+    }
+
+    // the same layout reflected around the window center, mirroring it again gives it back.
+    // Hit-testing goes by these offsets, so it follows along.
+    pub fn mirrored(&self) -> Self {
+        let window_width = self.window_size.width as f32;
+        Self {
+            board_offset_x_px: mirror_x(
+                self.board_offset_x_px,
+                self.cell_size_px * self.board_size_cols as f32,
+                window_width,
+            ),
+            panel_offset_x_px: mirror_x(
+                self.panel_offset_x_px,
+                self.cell_size_px * self.panel_cols as f32,
+                window_width,
+            ),
+            layout_mirror: !self.layout_mirror,
+            ..self.clone()
+        }
+    }
+
+    // left edge of something `width` wide that's at `left` in the regular layout
+    pub fn layout_left(&self, left: f32, width: f32) -> f32 {
+        if self.layout_mirror {
+            mirror_x(left, width, self.window_size.width as f32)
+        } else {
+            left
         }
     }
 }
 
+// HUD text is laid out for the logical window size, this is how much bigger the surface is. The
+// shorter side decides, so text never runs off a window that's wider or taller than the design.
+pub fn text_scale(cfg: &UserRenderConfig, physical_size: PhysicalSize<u32>) -> f32 {
+    if !cfg.scale_text {
+        return 1.0;
+    }
+    let horizontal = physical_size.width as f32 / cfg.window_size.width as f32;
+    let vertical = physical_size.height as f32 / cfg.window_size.height as f32;
+    horizontal.min(vertical)
+}
+
+// `auto` lets wgpu pick whatever the platform has, the others force a single API
+pub fn backends_from_name(name: &str) -> Option<wgpu::Backends> {
+    match name.trim().to_lowercase().as_str() {
+        "auto" => Some(wgpu::Backends::all()),
+        "vulkan" => Some(wgpu::Backends::VULKAN),
+        "metal" => Some(wgpu::Backends::METAL),
+        "dx12" => Some(wgpu::Backends::DX12),
+        "gl" => Some(wgpu::Backends::GL),
+        _ => None,
+    }
+}
+
+// left edge after reflecting `left`..`left + width` around the middle of the window
+pub fn mirror_x(left: f32, width: f32, window_width: f32) -> f32 {
+    window_width - left - width
+}
+
 pub struct Render<'a> {
     pub surface: wgpu::Surface<'a>,
     surface_config: SurfaceConfiguration,
@@ -97,12 +367,56 @@ pub struct Render<'a> {
 
     static_vertex_buffer: wgpu::Buffer,
     cursor_vertex_buffer: wgpu::Buffer,
+    rotation_preview_vertex_buffer: wgpu::Buffer,
+    panel_origin_vertex_buffer: wgpu::Buffer,
+    goal_bar_vertex_buffer: wgpu::Buffer,
+    pause_vertex_buffer: wgpu::Buffer,
+    best_move_vertex_buffer: wgpu::Buffer,
+    panel_refresh_vertex_buffer: wgpu::Buffer,
+    thumbnail_vertex_buffer: wgpu::Buffer,
 
     static_index_buffer: wgpu::Buffer,
     contour_index_buffer: wgpu::Buffer,
+    edge_glow_index_buffer: wgpu::Buffer,
+    shape_color_index_buffer: wgpu::Buffer,
+    clearing_index_buffer: wgpu::Buffer,
+    near_full_index_buffer: wgpu::Buffer,
+    aim_clear_index_buffer: wgpu::Buffer,
+    bonus_index_buffer: wgpu::Buffer,
+    // panel part of the static index buffer, it's only rewritten when the panel changes
+    panel_index_count: u32,
 
     user_render_config: UserRenderConfig,
     text_system: TextSystem,
+    cursor_state: CursorStateBinder,
+    background: BackgroundRenderer,
+    trail: TrailRenderer,
+    trail_history: TrailHistory,
+    // the window can't be seen, nothing is drawn until it can. The game itself keeps running.
+    occluded: bool,
+    pending_resize: PendingResize,
+    profiler: Profiler,
+    // timings overlay, toggled with the frame stats key
+    show_frame_stats: bool,
+}
+
+// latest window size that the surface wasn't configured for yet
+#[derive(Debug, Default)]
+struct PendingResize {
+    size: Option<PhysicalSize<u32>>,
+}
+
+impl PendingResize {
+    // a minimized window reports a zero size, the surface can't be configured for it
+    fn request(&mut self, size: PhysicalSize<u32>) {
+        if size.width > 0 && size.height > 0 {
+            self.size = Some(size);
+        }
+    }
+
+    fn take(&mut self) -> Option<PhysicalSize<u32>> {
+        self.size.take()
+    }
 }
 
 impl<'a> Render<'a> {
@@ -114,7 +428,7 @@ impl<'a> Render<'a> {
         // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             #[cfg(not(target_arch = "wasm32"))]
-            backends: wgpu::Backends::VULKAN, // VULKAN
+            backends: render_config.backends,
             #[cfg(target_arch = "wasm32")]
             backends: wgpu::Backends::GL,
             ..Default::default()
@@ -130,20 +444,30 @@ impl<'a> Render<'a> {
             .await
             .unwrap();
 
+        let cursor_state_binding = CursorStateBinding::select(
+            adapter.features(),
+            render_config.force_uniform_cursor_state,
+        );
+        println!("Cursor state is passed with {:?}", cursor_state_binding);
+        let max_push_constant_size = match cursor_state_binding {
+            CursorStateBinding::PushConstants => 128,
+            CursorStateBinding::Uniform => 0,
+        };
+
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::PUSH_CONSTANTS,
+                    required_features: cursor_state_binding.required_features(),
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web, we'll have to disable some.
                     required_limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits {
-                            max_push_constant_size: 128,
+                            max_push_constant_size,
                             ..wgpu::Limits::downlevel_webgl2_defaults()
                         }
                     } else {
                         wgpu::Limits {
-                            max_push_constant_size: 128,
+                            max_push_constant_size,
                             ..Default::default()
                         }
                     },
@@ -164,9 +488,8 @@ impl<'a> Render<'a> {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
 
-        let scale_factor = window.scale_factor(); // Get DPI scale
-        let physical_width = (render_config.window_size.width as f64 * scale_factor) as u32;
-        let physical_height = (render_config.window_size.height as f64 * scale_factor) as u32;
+        let physical_size = physical_window_size(&render_config, window.scale_factor());
+        let (physical_width, physical_height) = (physical_size.width, physical_size.height);
 
         let surface_config = SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
@@ -179,20 +502,23 @@ impl<'a> Render<'a> {
             view_formats: vec![],
         };
 
+        let cursor_state = CursorStateBinder::new(&device, cursor_state_binding);
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Triangle render Pipeline Layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[wgpu::PushConstantRange {
-                    stages: wgpu::ShaderStages::FRAGMENT,
-                    range: 0..4,
-                }],
+                bind_group_layouts: &cursor_state.bind_group_layouts(),
+                push_constant_ranges: &cursor_state.push_constant_ranges(),
             });
 
         let vertex_shader_module = device
             .create_shader_module(wgpu::include_wgsl!("../../res/shaders/textured.vert.wgsl"));
-        let fragment_shader_module = device
-            .create_shader_module(wgpu::include_wgsl!("../../res/shaders/textured.frag.wgsl"));
+        let fragment_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("textured.frag.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(
+                cursor_state_binding
+                    .fragment_shader_source(include_str!("../../res/shaders/textured.frag.wgsl")),
+            ),
+        });
 
         let point_render_pipeline = create_pipeline(
             &device,
@@ -220,37 +546,44 @@ impl<'a> Render<'a> {
             wgpu::PrimitiveTopology::LineStrip,
         );
 
-        let board_vertices = normalize_screen_to_ndc(
-            generate_board_vertices(&render_config),
-            render_config.window_size,
-        );
-        let panel_vertices = normalize_screen_to_ndc(
-            generate_panel_vertices(&render_config),
-            render_config.window_size,
+        let background = BackgroundRenderer::new(&device, surface_config.format);
+        let trail = TrailRenderer::new(
+            &device,
+            surface_config.format,
+            render_config.cursor_trail_length,
         );
-
-        let mut static_vertices = vec![];
-        static_vertices.extend(board_vertices);
-        static_vertices.extend(panel_vertices);
-
-        let static_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Static Vertex Buffer"),
-            contents: cast_slice(&static_vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let profiler = Profiler::new(render_config.frame_stats_window);
+        // no trail with `reduce_motion`
+        let trail_length = if render_config.show_cursor_trail && !render_config.reduce_motion {
+            render_config.cursor_trail_length
+        } else {
+            0
+        };
+        let trail_history = TrailHistory::new(trail_length);
+        let static_vertex_buffer = create_static_vertex_buffer(&device, &render_config);
 
         let cursor_vertex_buffer = create_cursor_buffer(&device);
-
-        let static_index_buffer = create_index_buffer(
-            &device,
-            render_config.board_size_cols * render_config.board_size_cols * 6 + 120,
-        );
+        let rotation_preview_vertex_buffer = create_rotation_preview_buffer(&device);
+        let panel_origin_vertex_buffer = create_panel_origin_buffer(&device);
+        let goal_bar_vertex_buffer = create_goal_bar_buffer(&device);
+        let pause_vertex_buffer = create_pause_buffer(&device);
+        let best_move_vertex_buffer = create_best_move_buffer(&device);
+        let panel_refresh_vertex_buffer = create_panel_refresh_buffer(&device, &render_config);
+        let thumbnail_vertex_buffer = create_thumbnail_buffer(&device, &render_config);
+
+        let static_index_buffer = create_static_index_buffer(&device, &render_config);
         let contour_index_buffer = create_index_buffer(&device, 20);
+        let edge_glow_index_buffer = create_edge_glow_index_buffer(&device, &render_config);
+        let clearing_index_buffer = create_line_flash_index_buffer(&device, &render_config);
+        let shape_color_index_buffer = create_line_flash_index_buffer(&device, &render_config);
+        let near_full_index_buffer = create_line_flash_index_buffer(&device, &render_config);
+        let aim_clear_index_buffer = create_line_flash_index_buffer(&device, &render_config);
+        let bonus_index_buffer = create_line_flash_index_buffer(&device, &render_config);
 
         surface.configure(&device, &surface_config);
         let resolution = Resolution {
             width: physical_width,
-            height: physical_width,
+            height: physical_height,
         };
 
         let device = Rc::new(device);
@@ -260,6 +593,10 @@ impl<'a> Render<'a> {
             queue.clone(),
             TextureFormat::Rgba8UnormSrgb,
             resolution,
+            render_config
+                .layout_mirror
+                .then_some(render_config.window_size.width as f32),
+            text_scale(&render_config, physical_size),
         );
 
         Self {
@@ -272,36 +609,102 @@ impl<'a> Render<'a> {
             contour_pipeline,
             static_vertex_buffer,
             cursor_vertex_buffer,
+            rotation_preview_vertex_buffer,
+            panel_origin_vertex_buffer,
+            goal_bar_vertex_buffer,
+            pause_vertex_buffer,
+            best_move_vertex_buffer,
+            panel_refresh_vertex_buffer,
+            thumbnail_vertex_buffer,
             static_index_buffer,
             contour_index_buffer,
+            edge_glow_index_buffer,
+            clearing_index_buffer,
+            shape_color_index_buffer,
+            near_full_index_buffer,
+            aim_clear_index_buffer,
+            bonus_index_buffer,
+            panel_index_count: 0,
             user_render_config: render_config,
             text_system,
+            cursor_state,
+            background,
+            trail,
+            trail_history,
+            occluded: false,
+            pending_resize: PendingResize::default(),
+            profiler,
+            show_frame_stats: false,
         }
     }
 
+    pub fn toggle_frame_stats(&mut self) {
+        self.show_frame_stats = !self.show_frame_stats;
+    }
+
+    // the update is timed by the caller, rendering and presenting in `render_state`
+    pub fn record_update_time(&mut self, duration: Duration) {
+        self.profiler.record(FramePhase::Update, duration);
+    }
+
+    // ignored unless `pause_render_when_occluded`
+    pub fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded && self.user_render_config.pause_render_when_occluded;
+    }
+
+    // with `coalesce_resizes` the surface is reconfigured by the next `render_state`
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
+        self.pending_resize.request(new_size);
+        if !self.user_render_config.coalesce_resizes {
+            self.apply_pending_resize();
+        }
+    }
+
+    fn apply_pending_resize(&mut self) {
+        if let Some(new_size) = self.pending_resize.take() {
             self.surface_config.width = new_size.width;
             self.surface_config.height = new_size.height;
             self.surface.configure(&self.device, &self.surface_config);
+            self.text_system
+                .resize(new_size, text_scale(&self.user_render_config, new_size));
         }
     }
 
+    // the board got another size (endless growth), everything sized by it has to be reallocated
+    pub fn resize_board(&mut self, render_config: UserRenderConfig) {
+        self.static_vertex_buffer = create_static_vertex_buffer(&self.device, &render_config);
+        self.static_index_buffer = create_static_index_buffer(&self.device, &render_config);
+        self.clearing_index_buffer = create_line_flash_index_buffer(&self.device, &render_config);
+        self.shape_color_index_buffer =
+            create_line_flash_index_buffer(&self.device, &render_config);
+        self.near_full_index_buffer = create_line_flash_index_buffer(&self.device, &render_config);
+        self.aim_clear_index_buffer = create_line_flash_index_buffer(&self.device, &render_config);
+        self.bonus_index_buffer = create_line_flash_index_buffer(&self.device, &render_config);
+        self.edge_glow_index_buffer = create_edge_glow_index_buffer(&self.device, &render_config);
+        self.thumbnail_vertex_buffer = create_thumbnail_buffer(&self.device, &render_config);
+        self.user_render_config = render_config;
+    }
+
     pub fn render_state(&mut self, state: &mut Game, input: &Input) {
+        self.apply_pending_resize();
         if skip_render(
+            self.occluded,
             &mut state.ui,
             &state.selected_shape,
+            state.game_state,
+            state.pause.is_some(),
             &self.user_render_config,
         ) {
             return;
         }
+        let render_start = Instant::now();
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
         let board_vertex_number = (self.user_render_config.board_size_cols + 1)
-            * (self.user_render_config.board_size_cols + 1);
+            * (self.user_render_config.board_size_rows + 1);
         let panel_vertex_number =
             (self.user_render_config.panel_cols + 1) * (self.user_render_config.panel_rows + 1);
         let static_vertex_number = board_vertex_number + panel_vertex_number;
@@ -321,59 +724,276 @@ impl<'a> Render<'a> {
                     occlusion_query_set: None,
                 });
 
+                self.background.draw(
+                    &mut render_pass,
+                    &self.queue,
+                    &self.user_render_config,
+                    PhysicalSize::new(self.surface_config.width, self.surface_config.height),
+                    state.ui.danger_zone.map_or(0.0, |elapsed| {
+                        steady_pulse(&self.user_render_config, elapsed)
+                    }),
+                );
+
                 // DRAW GRID (point pipeline)
                 render_pass.set_pipeline(&self.point_render_pipeline);
-                render_pass.set_push_constants(
-                    wgpu::ShaderStages::FRAGMENT,
-                    0,
-                    cast_slice(&[CursorState::NotACursor as u32]),
-                );
+                self.cursor_state
+                    .set(&mut render_pass, CursorState::NotACursor);
 
                 render_pass.set_vertex_buffer(0, self.static_vertex_buffer.slice(..));
                 render_pass.draw(0..static_vertex_number as u32, 0..1);
 
+                draw_panel_origin_markers(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    &state.panel,
+                    &self.user_render_config,
+                    &self.panel_origin_vertex_buffer,
+                    &self.queue,
+                );
+
                 // DRAW SHADOW (line pipeline)
                 draw_cursor_shadow(
                     &mut render_pass,
+                    &self.cursor_state,
                     state,
                     &input,
                     &self.user_render_config,
-                    &self.contour_index_buffer,
-                    &self.static_vertex_buffer,
-                    &self.queue,
-                    &self.contour_pipeline,
+                    &OutlinePass {
+                        index_buffer: &self.contour_index_buffer,
+                        static_vertex_buffer: &self.static_vertex_buffer,
+                        queue: &self.queue,
+                        pipeline: &self.contour_pipeline,
+                    },
+                );
+
+                draw_edge_glow(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
+                    &self.user_render_config,
+                    &OutlinePass {
+                        index_buffer: &self.edge_glow_index_buffer,
+                        static_vertex_buffer: &self.static_vertex_buffer,
+                        queue: &self.queue,
+                        pipeline: &self.contour_pipeline,
+                    },
                 );
 
                 // DRAW cells: board and panel (triangle pipeline)
                 draw_panel_and_board(
                     &mut render_pass,
-                    &state.board,
+                    &mut state.board,
+                    state.config.board_origin,
                     &state.panel,
                     &self.user_render_config,
                     &self.static_index_buffer,
                     &self.static_vertex_buffer,
                     &self.queue,
                     &mut state.ui,
+                    &mut self.panel_index_count,
                     &self.triangle_render_pipeline,
                 );
 
+                draw_shape_colors(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    &state.board,
+                    state.config.board_origin,
+                    &self.user_render_config,
+                    &self.shape_color_index_buffer,
+                    &self.queue,
+                );
+
+                draw_bonus_cells(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    &state.board,
+                    state.config.board_origin,
+                    &self.bonus_index_buffer,
+                    &self.queue,
+                );
+
+                draw_near_full_hint(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
+                    &self.user_render_config,
+                    &self.near_full_index_buffer,
+                    &self.queue,
+                );
+
+                draw_aim_clear_hint(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
+                    &input,
+                    &self.user_render_config,
+                    &self.aim_clear_index_buffer,
+                    &self.queue,
+                );
+
+                draw_clearing(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
+                    &self.user_render_config,
+                    &self.clearing_index_buffer,
+                    &self.queue,
+                );
+
+                // the ones below bring their own vertex buffer
+                draw_panel_refresh(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    &state.panel,
+                    &state.ui,
+                    &self.user_render_config,
+                    &self.panel_refresh_vertex_buffer,
+                    &self.queue,
+                );
+
+                draw_best_move_ghost(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
+                    &self.user_render_config,
+                    &self.best_move_vertex_buffer,
+                    &self.queue,
+                );
+
+                draw_goal_bar(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    goal::progress_toward_goal(state),
+                    &self.user_render_config,
+                    &self.goal_bar_vertex_buffer,
+                    &self.queue,
+                );
+
+                if state.game_state == GameState::LevelIntro {
+                    draw_board_thumbnail(
+                        &mut render_pass,
+                        &self.cursor_state,
+                        &state.board,
+                        state.config.board_origin,
+                        &self.user_render_config,
+                        &self.thumbnail_vertex_buffer,
+                        &self.queue,
+                    );
+                }
+
+                draw_rotation_preview(
+                    &mut render_pass,
+                    state,
+                    &self.user_render_config,
+                    &self.rotation_preview_vertex_buffer,
+                    &self.queue,
+                );
+
+                draw_pause_overlay(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
+                    &self.user_render_config,
+                    &self.pause_vertex_buffer,
+                    &self.queue,
+                );
+
+                if input.is_cursor_positioned() {
+                    self.trail_history.push(input.cursor_position());
+                }
+                self.trail.draw(
+                    &mut render_pass,
+                    &self.queue,
+                    &self.trail_history,
+                    &self.user_render_config.window_size,
+                );
+                render_pass.set_pipeline(&self.triangle_render_pipeline);
+                self.cursor_state
+                    .set(&mut render_pass, CursorState::NotACursor);
+
                 // Triangle pipeline
                 draw_cursor(
                     &mut render_pass,
+                    &self.cursor_state,
+                    &input,
+                    &self.user_render_config,
+                    state.selected_shape.as_ref().map(|shape| HeldShape {
+                        shape,
+                        rotation: rotation_progress(&state.ui, &self.user_render_config),
+                    }),
+                    &self.cursor_vertex_buffer,
+                    &self.queue,
+                );
+
+                draw_snap_indicator(
+                    &mut render_pass,
+                    &self.cursor_state,
+                    state,
                     &input,
                     &self.user_render_config,
-                    &state.selected_shape,
                     &self.cursor_vertex_buffer,
                     &self.queue,
                 );
 
-                self.text_system
-                    .render_score(&state.stats, &mut render_pass);
+                let mut hud = HudValues::new(&state.stats);
+                if self.user_render_config.show_fill_percentage {
+                    hud.fill_percent = Some((state.board.fill_fraction() * 100.0).round() as u8);
+                }
+                if self.user_render_config.show_goal_progress {
+                    hud.goal = Some(goal::goal_text(state));
+                }
+                if self.user_render_config.show_placeable_count
+                    && state.game_state == GameState::Playing
+                {
+                    hud.placeable = Some(solver::placeable_shape_count(state));
+                }
+                hud.daily = state.daily.map(|date| date.to_string());
+                hud.tutorial = state
+                    .tutorial
+                    .map(|step| tutorial_prompt(step, &self.user_render_config));
+                if self.user_render_config.show_score_breakdown
+                    && state.game_state == GameState::GameOver
+                {
+                    hud.score_breakdown = Some(state.ledger);
+                    // the menu has its own retry item
+                    if state.config.allow_retry && state.menu.is_none() {
+                        let key = input.key_bindings.key_for(Action::Retry);
+                        hud.retry_key = Some(key_name(key));
+                    }
+                }
+                if self.user_render_config.show_leaderboard
+                    && state.game_state == GameState::GameOver
+                {
+                    hud.leaderboard = Some(state.leaderboard.entries().to_vec());
+                }
+                hud.menu = state.menu.clone();
+                hud.paused = state.pause.is_some();
+                if self.user_render_config.show_legend {
+                    hud.legend = Some(legend_entries(state, &self.user_render_config));
+                }
+                if self.user_render_config.show_strategy_hud {
+                    hud.strategy = Some(solver::panel_shape_info(state));
+                }
+                if self.user_render_config.show_placement_badges
+                    && state.game_state == GameState::Playing
+                {
+                    hud.placement_badges = Some(placement_badges(state, &self.user_render_config));
+                }
+                if self.show_frame_stats {
+                    hud.frame_stats = Some(self.profiler.overlay_text());
+                }
+                self.text_system.render_score(hud, &mut render_pass);
                 drop(render_pass);
 
                 // self.staging_belt.finish();
                 self.queue.submit(iter::once(encoder.finish()));
+                self.profiler
+                    .record(FramePhase::Render, render_start.elapsed());
+                let present_start = Instant::now();
                 frame.present();
+                self.profiler
+                    .record(FramePhase::Present, present_start.elapsed());
             }
             Err(wgpu::SurfaceError::Outdated) => {
                 log::info!("Outdated surface texture");
@@ -386,12 +1006,52 @@ impl<'a> Render<'a> {
     }
 }
 
+// scale the layout is drawn with, mouse positions have to be divided by it. `scale_override`
+// wins over what the window reports.
+pub fn render_scale(render_config: &UserRenderConfig, window_scale: f64) -> f64 {
+    render_config.scale_override.unwrap_or(window_scale)
+}
+
+// window size scaled by the DPI scale
+fn physical_window_size(render_config: &UserRenderConfig, window_scale: f64) -> PhysicalSize<u32> {
+    let scale_factor = render_scale(render_config, window_scale);
+    PhysicalSize::new(
+        (render_config.window_size.width as f64 * scale_factor) as u32,
+        (render_config.window_size.height as f64 * scale_factor) as u32,
+    )
+}
+
+fn lingering_frames_for(game_state: GameState, cfg: &UserRenderConfig) -> u8 {
+    match game_state {
+        GameState::LevelIntro | GameState::Playing | GameState::MoveToNextLevel => {
+            cfg.lingering_frames
+        }
+        GameState::GameOver => cfg.idle_lingering_frames,
+    }
+}
+
 fn skip_render(
+    occluded: bool,
     ui: &mut UI,
     selected_shape: &Option<SelectedShape>,
+    game_state: GameState,
+    paused: bool,
     cfg: &UserRenderConfig,
 ) -> bool {
-    let can_skip = !ui.need_to_update_panel && !ui.need_to_update_panel && selected_shape.is_none();
+    // the update flags are left alone, so everything that changed in the meantime is drawn
+    // once the window is visible again
+    if occluded {
+        return true;
+    }
+    let can_skip = !ui.need_to_update_board
+        && !ui.need_to_update_panel
+        && selected_shape.is_none()
+        && ui.danger_zone.is_none()
+        && ui.edge_glow.is_none()
+        && ui.panel_refresh.is_none()
+        // nothing else changes when the game is paused or resumed, the overlay still has to
+        // come and go
+        && ui.shown_paused == paused;
 
     if can_skip {
         if ui.lingering_frames > 0 {
@@ -399,158 +1059,889 @@ fn skip_render(
             return false;
         }
     } else {
-        ui.lingering_frames = cfg.lingering_frames;
+        ui.lingering_frames = lingering_frames_for(game_state, cfg);
     }
+    ui.shown_paused = paused;
 
     return can_skip;
 }
 
+// the selected shape drawn at the cursor, with the turn it's in the middle of
+struct HeldShape<'a> {
+    shape: &'a SelectedShape,
+    rotation: Option<(ShapeType, f32)>,
+}
+
 fn draw_cursor(
     render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
     input: &Input,
     user_render_config: &UserRenderConfig,
-    selected_shape: &Option<SelectedShape>,
+    held: Option<HeldShape>,
     cursor_vertex_buffer: &wgpu::Buffer,
     queue: &wgpu::Queue,
 ) {
-    if let Some(shape) = selected_shape {
+    // nothing to draw in the corner until the mouse was seen over the window
+    if !input.is_cursor_positioned() {
+        return;
+    }
+    if let Some(HeldShape { shape, rotation }) = held {
         let cursor_shape_vertices = render_cursor_shape(
-            &input.mouse_position,
+            input.cursor_position(),
             shape,
+            rotation,
             user_render_config.cell_size_px,
             &user_render_config.window_size,
         );
         queue.write_buffer(&cursor_vertex_buffer, 0, cast_slice(&cursor_shape_vertices));
         render_pass.set_vertex_buffer(0, cursor_vertex_buffer.slice(..));
+        // red like the cursor when there's nowhere to put it from here
+        let color = cursor_shape_color(
+            shape,
+            off_play_area(&input.mouse_position, user_render_config),
+            user_render_config,
+        );
+        cursor_state.set(render_pass, color);
         render_pass.draw(0..cursor_shape_vertices.len() as u32, 0..1);
+        cursor_state.set(render_pass, CursorState::NotACursor);
     } else {
         let new_cursor_vertices = render_cursor(
-            &input.mouse_position,
+            input.cursor_position(),
             &user_render_config.cursor_size,
             &user_render_config.window_size,
         );
         queue.write_buffer(&cursor_vertex_buffer, 0, cast_slice(&new_cursor_vertices));
         render_pass.set_vertex_buffer(0, cursor_vertex_buffer.slice(..));
-        render_pass.set_push_constants(
-            wgpu::ShaderStages::FRAGMENT,
-            0,
-            cast_slice(&[CursorState::Cursor as u32]),
-        );
+        cursor_state.set(render_pass, CursorState::Cursor);
         render_pass.draw(0..6, 0..1);
     }
 }
 
+// the colour the shape gets once it's placed, so it's recognised on the board
+fn cursor_shape_color(
+    shape: &SelectedShape,
+    off_area: bool,
+    user_render_config: &UserRenderConfig,
+) -> CursorState {
+    if off_area {
+        CursorState::Cursor
+    } else if user_render_config.color_by_shape {
+        CursorState::for_shape(shape.shape_type.base_shape_type())
+    } else {
+        CursorState::NotACursor
+    }
+}
+
+// drawn on top of the cursor shape, the hovered cell is usually covered by it
+fn draw_snap_indicator(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    input: &Input,
+    render_config: &UserRenderConfig,
+    cursor_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !render_config.show_snap_indicator || state.selected_shape.is_none() {
+        return;
+    }
+    let Some(vertices) = render_snap_indicator(&input.mouse_position, render_config) else {
+        return;
+    };
+
+    let offset = (size_of::<Vertex>() * SNAP_INDICATOR_VERTEX_OFFSET) as wgpu::BufferAddress;
+    queue.write_buffer(cursor_vertex_buffer, offset, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, cursor_vertex_buffer.slice(offset..));
+    cursor_state.set(render_pass, CursorState::NotACursor);
+    render_pass.draw(0..vertices.len() as u32, 0..1);
+}
+
+// inset quad of the board cell under the mouse, none when the mouse is off the board
+fn render_snap_indicator(mouse_pos: &XY, render_config: &UserRenderConfig) -> Option<Vec<Vertex>> {
+    if !over_board(mouse_pos, render_config) {
+        return None;
+    }
+    let cell_px = render_config.cell_size_px;
+    let cell = to_cell_space(
+        XY(
+            render_config.board_offset_x_px,
+            render_config.board_offset_y_px,
+        ),
+        cell_px,
+        mouse_pos,
+    );
+    let inset = cell_px * SNAP_INDICATOR_INSET;
+    let zero = XY(
+        render_config.board_offset_x_px + cell.col as f32 * cell_px + inset,
+        render_config.board_offset_y_px + cell.row as f32 * cell_px + inset,
+    );
+    Some(render_shape_cells(
+        &[(0, 0)],
+        &zero,
+        cell_px - 2.0 * inset,
+        &render_config.window_size,
+    ))
+}
+
+// the board takes a fixed slot per cell at the start of the index buffer, the panel follows it
 fn draw_panel_and_board(
     render_pass: &mut wgpu::RenderPass<'_>,
-    board: &Board,
+    board: &mut Board,
+    origin: BoardOrigin,
     panel: &Panel,
     user_render_config: &UserRenderConfig,
     static_index_buffer: &wgpu::Buffer,
     static_vertex_buffer: &wgpu::Buffer,
     queue: &wgpu::Queue,
     ui: &mut UI,
+    panel_index_count: &mut u32,
     triangle_render_pipeline: &RenderPipeline,
 ) {
     render_pass.set_pipeline(triangle_render_pipeline);
-
-    let board_index_offset =
-        (user_render_config.board_size_cols + 1) * (user_render_config.board_size_cols + 1);
-    let board_indices = render_board(board);
-    let panel_indices = render_panel(panel, user_render_config.panel_cols, board_index_offset);
-    let mut board_and_panel_indices: Vec<u32> = vec![];
-    board_and_panel_indices.extend(board_indices);
-    board_and_panel_indices.extend(panel_indices);
-
     render_pass.set_vertex_buffer(0, static_vertex_buffer.slice(..));
 
-    if ui.need_to_update_board || ui.need_to_update_panel {
-        println!("Updating board or panel");
+    let index_size = size_of::<u32>() as wgpu::BufferAddress;
+    if ui.need_to_update_board {
+        for (offset, indices) in board_index_writes(board, origin) {
+            queue.write_buffer(
+                static_index_buffer,
+                offset as wgpu::BufferAddress * index_size,
+                cast_slice(&indices),
+            );
+        }
+        ui.need_to_update_board = false;
+    }
+
+    let board_slots = board.grid.len() * 6;
+    if ui.need_to_update_panel {
+        let board_index_offset =
+            (user_render_config.board_size_cols + 1) * (user_render_config.board_size_rows + 1);
+        let panel_indices = render_panel(panel, user_render_config.panel_cols, board_index_offset);
         queue.write_buffer(
-            &static_index_buffer,
-            0,
-            cast_slice(&board_and_panel_indices),
+            static_index_buffer,
+            board_slots as wgpu::BufferAddress * index_size,
+            cast_slice(&panel_indices),
         );
-        ui.need_to_update_board = false;
+        *panel_index_count = panel_indices.len() as u32;
         ui.need_to_update_panel = false;
     }
+    // the sliding shapes are drawn instead while the panel refreshes
+    let panel_count = match ui.panel_refresh {
+        Some(_) => 0,
+        None => *panel_index_count,
+    };
     render_pass.set_index_buffer(static_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-    render_pass.draw_indexed(0..board_and_panel_indices.len() as u32, 0, 0..1);
+    render_pass.draw_indexed(0..board_slots as u32 + panel_count, 0, 0..1);
 }
 
-fn draw_cursor_shadow(
+// expects the triangle pipeline to be set
+fn draw_panel_refresh(
     render_pass: &mut wgpu::RenderPass<'_>,
-    state: &Game,
-    input: &Input,
+    cursor_state: &CursorStateBinder,
+    panel: &Panel,
+    ui: &UI,
     render_config: &UserRenderConfig,
-    contour_index_buffer: &wgpu::Buffer,
-    static_vertex_buffer: &wgpu::Buffer,
+    panel_refresh_vertex_buffer: &wgpu::Buffer,
     queue: &wgpu::Queue,
-    contour_pipeline: &wgpu::RenderPipeline,
 ) {
-    if let Some(selected_shape) = &state.selected_shape {
-        if over_board(&input.mouse_position, render_config) {
-            // println!("Shape {:?} is selected", selected_shape.shape_type);
-            let contour_indices =
-                render_contour(&selected_shape, &input.mouse_position, render_config);
-            render_pass.set_pipeline(contour_pipeline);
-            render_pass.set_vertex_buffer(0, static_vertex_buffer.slice(..));
-            queue.write_buffer(&contour_index_buffer, 0, cast_slice(&contour_indices));
-            render_pass.set_index_buffer(contour_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..contour_indices.len() as u32, 0, 0..1);
-        };
+    let (Some(refresh), Some(duration)) =
+        (&ui.panel_refresh, render_config.panel_refresh_animation)
+    else {
+        return;
+    };
+    let vertices = render_panel_refresh(refresh, panel, duration, render_config);
+    if vertices.is_empty() {
+        return;
     }
+    queue.write_buffer(panel_refresh_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, panel_refresh_vertex_buffer.slice(..));
+    cursor_state.set(render_pass, CursorState::NotACursor);
+    render_pass.draw(0..vertices.len() as u32, 0..1);
 }
 
-fn render_contour(
-    shape: &SelectedShape,
-    mouse_position: &XY,
+// the panel shapes moved down by up to the panel height: the old ones going away, the new
+// ones coming up into place
+fn render_panel_refresh(
+    refresh: &PanelRefresh,
+    panel: &Panel,
+    phase_duration: Duration,
     render_config: &UserRenderConfig,
-) -> Vec<u32> {
-    let placement_xy_0 = mouse_position.apply_offset(&shape.anchor_offset);
-    let placement_0_cell = to_cell_space(
-        XY(
-            render_config.board_offset_x_px,
-            render_config.board_offset_y_px,
-        ),
-        render_config.cell_size_px,
-        &placement_xy_0,
-    );
-    let mut visible_cells = Vec::new();
-    for (dx, dy) in shape.shape_type.cells() {
-        let nx = placement_0_cell.col.wrapping_add(dx as i16);
-        let ny = placement_0_cell.row.wrapping_add(dy as i16);
-        if nx >= 0
-            && nx < render_config.board_size_cols as i16
-            && ny >= 0
-            && ny < render_config.board_size_cols as i16
-        {
-            visible_cells.push(CellCoord::new(nx, ny));
-        }
-    }
-    let mut edge_set: HashSet<Edge> = HashSet::new();
+) -> Vec<Vertex> {
+    let progress = refresh.progress(phase_duration);
+    let (cells, lowered) = match refresh.phase {
+        PanelRefreshPhase::SlideOut => (refresh.outgoing.clone(), progress),
+        PanelRefreshPhase::SlideIn => (panel.visible_cells(), 1.0 - progress),
+    };
+    let cell_px = render_config.cell_size_px;
+    let slide_px = lowered * render_config.panel_rows as f32 * cell_px;
+    let top_lefts: Vec<XY> = cells
+        .iter()
+        .map(|cell| {
+            XY(
+                render_config.panel_offset_x_px + cell.col as f32 * cell_px,
+                render_config.panel_offset_y_px + cell.row as f32 * cell_px + slide_px,
+            )
+        })
+        .collect();
+    render_cell_quads(&top_lefts, cell_px, &render_config.window_size)
+}
 
-    for cell in &visible_cells {
-        let edges = Edge::around_cell(cell, render_config.board_size_cols);
-        for edge in &edges {
-            if !edge_set.insert(*edge) {
-                edge_set.remove(edge);
-            }
-        }
+// expects the triangle pipeline to be set
+fn draw_best_move_ghost(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    render_config: &UserRenderConfig,
+    best_move_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !render_config.show_best_move_ghost || state.game_state != GameState::Playing {
+        return;
     }
-    if edge_set.is_empty() {
-        return vec![];
+    let cells = best_move_ghost_cells(state);
+    if cells.is_empty() {
+        return;
     }
-
-    let contour_edges: Vec<Edge> = edge_set.into_iter().collect();
-    order_edges_for_linestrip(contour_edges)
+    let cell_px = render_config.cell_size_px;
+    let top_lefts: Vec<XY> = cells
+        .iter()
+        .map(|cell| {
+            XY(
+                render_config.board_offset_x_px + cell.col as f32 * cell_px,
+                render_config.board_offset_y_px + cell.row as f32 * cell_px,
+            )
+        })
+        .collect();
+    let vertices = render_cell_quads(&top_lefts, cell_px, &render_config.window_size);
+    queue.write_buffer(best_move_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, best_move_vertex_buffer.slice(..));
+    cursor_state.set(render_pass, CursorState::Ghost);
+    render_pass.draw(0..vertices.len() as u32, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
 }
 
-fn order_edges_for_linestrip(edges: Vec<Edge>) -> Vec<u32> {
-    let mut ordered_vertices = Vec::new();
-    let mut visited = HashSet::new();
-    let mut edge_map: HashMap<u32, Vec<u32>> = HashMap::new();
+// board cells, as seen on screen, of the move `solver::suggest_best_move` suggests
+fn best_move_ghost_cells(state: &Game) -> Vec<CellCoord> {
+    let Some((_, shape, cell)) = solver::suggest_best_move(state) else {
+        return vec![];
+    };
+    let (cols, rows) = (state.board.cols, state.board.rows);
+    shape
+        .cells_at(&cell)
+        .iter()
+        .map(|c| c.wrapped(cols, rows))
+        .map(|c| {
+            let visual_row = state.config.board_origin.visual_row(c.row as usize, rows);
+            CellCoord::new(c.col, visual_row as i16)
+        })
+        .collect()
+}
 
-    // Build adjacency map
+// expects the triangle pipeline and the static vertex buffer to be set. All the groups go into
+// the buffer at once, then a draw per shape type.
+fn draw_shape_colors(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    board: &Board,
+    origin: BoardOrigin,
+    user_render_config: &UserRenderConfig,
+    shape_color_index_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !user_render_config.color_by_shape {
+        return;
+    }
+    let groups = render_shape_colors(board, origin);
+    if groups.is_empty() {
+        return;
+    }
+    let indices: Vec<u32> = groups
+        .iter()
+        .flat_map(|(_, ix)| ix.iter().copied())
+        .collect();
+    queue.write_buffer(shape_color_index_buffer, 0, cast_slice(&indices));
+    render_pass.set_index_buffer(
+        shape_color_index_buffer.slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+    let mut start = 0;
+    for (shape, group) in &groups {
+        let end = start + group.len() as u32;
+        cursor_state.set(render_pass, CursorState::for_shape(*shape));
+        render_pass.draw_indexed(start..end, 0, 0..1);
+        start = end;
+    }
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// expects the triangle pipeline and the static vertex buffer to be set
+fn draw_bonus_cells(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    board: &Board,
+    origin: BoardOrigin,
+    bonus_index_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    let indices = render_bonus_cells(board, origin);
+    if indices.is_empty() {
+        return;
+    }
+    queue.write_buffer(bonus_index_buffer, 0, cast_slice(&indices));
+    cursor_state.set(render_pass, CursorState::HighlightGold);
+    render_pass.set_index_buffer(bonus_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// expects the triangle pipeline and the static vertex buffer to be set
+fn draw_near_full_hint(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    user_render_config: &UserRenderConfig,
+    near_full_index_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    let Some(max_missing) = user_render_config.near_full_hint else {
+        return;
+    };
+    if state.game_state != GameState::Playing {
+        return;
+    }
+    let lines = state.board.near_full_lines(max_missing);
+    let indices = render_line_gaps(&state.board, &lines, state.config.board_origin);
+    if indices.is_empty() {
+        return;
+    }
+    queue.write_buffer(near_full_index_buffer, 0, cast_slice(&indices));
+    cursor_state.set(render_pass, CursorState::Hint);
+    render_pass.set_index_buffer(near_full_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// expects the triangle pipeline and the static vertex buffer to be set
+fn draw_aim_clear_hint(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    input: &Input,
+    user_render_config: &UserRenderConfig,
+    aim_clear_index_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !user_render_config.aim_clear_hint || state.game_state != GameState::Playing {
+        return;
+    }
+    let lines = aimed_clear_lines(state, &input.mouse_position, user_render_config);
+    if lines.is_empty() {
+        return;
+    }
+    let indices = render_lines(
+        &lines,
+        user_render_config.board_size_cols,
+        user_render_config.board_size_rows,
+        state.config.board_origin,
+    );
+    queue.write_buffer(aim_clear_index_buffer, 0, cast_slice(&indices));
+    cursor_state.set(render_pass, CursorState::HighlightWarm);
+    render_pass.set_index_buffer(aim_clear_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// what placing the selected shape at the mouse would clear, nothing while it can't go there
+fn aimed_clear_lines(
+    state: &Game,
+    mouse_position: &XY,
+    render_config: &UserRenderConfig,
+) -> ClearedLines {
+    let Some(selected) = &state.selected_shape else {
+        return ClearedLines::default();
+    };
+    if !over_board(mouse_position, render_config) {
+        return ClearedLines::default();
+    }
+    let (shape, cell) = aimed_placement(state, selected, mouse_position, render_config);
+    if !state.is_valid_placement(&shape, &cell) {
+        return ClearedLines::default();
+    }
+    state.lines_completed_by(&shape, &cell)
+}
+
+// the lines of a running clear, blinking while they're still on the board until the animation is
+// over. Expects the triangle pipeline and the static vertex buffer to be set.
+fn draw_clearing(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    user_render_config: &UserRenderConfig,
+    clearing_index_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    let Some(clearing) = &state.clearing else {
+        return;
+    };
+    if !line_flash_visible(clearing.elapsed) {
+        return;
+    }
+
+    let indices = render_lines(
+        &clearing.lines,
+        user_render_config.board_size_cols,
+        user_render_config.board_size_rows,
+        state.config.board_origin,
+    );
+    let color = if user_render_config.line_flash_by_count {
+        line_flash_color(clearing.lines.rows.len() + clearing.lines.cols.len())
+    } else {
+        CursorState::Highlight
+    };
+    queue.write_buffer(clearing_index_buffer, 0, cast_slice(&indices));
+    cursor_state.set(render_pass, color);
+    render_pass.set_index_buffer(clearing_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// a line per colour the board can be drawn with in this game, also marked by the name for when
+// the colours are hard to tell apart
+fn legend_entries(state: &Game, render_config: &UserRenderConfig) -> Vec<LegendEntry> {
+    let mut entries = vec![LegendEntry {
+        color: CursorState::NotACursor.rgb(),
+        label: "Filled cell",
+    }];
+    if render_config.color_by_shape {
+        entries.extend(BaseShapeType::iter().map(|shape| LegendEntry {
+            color: CursorState::for_shape(shape).rgb(),
+            label: shape_label(shape),
+        }));
+    }
+    if state.config.bonus_cells > 0 {
+        entries.push(LegendEntry {
+            color: CursorState::HighlightGold.rgb(),
+            label: "Bonus cell",
+        });
+    }
+    if render_config.near_full_hint.is_some() {
+        entries.push(LegendEntry {
+            color: CursorState::Hint.rgb(),
+            label: "Completes a line",
+        });
+    }
+    if render_config.aim_clear_hint {
+        entries.push(LegendEntry {
+            color: CursorState::HighlightWarm.rgb(),
+            label: "Clears when placed",
+        });
+    }
+    if render_config.show_best_move_ghost {
+        entries.push(LegendEntry {
+            color: CursorState::Ghost.rgb(),
+            label: "Best move",
+        });
+    }
+    entries.push(LegendEntry {
+        color: CursorState::Highlight.rgb(),
+        label: "Cleared line",
+    });
+    entries
+}
+
+// what a placed shape's colour is called in the legend with `color_by_shape`
+fn shape_label(shape: BaseShapeType) -> &'static str {
+    match shape {
+        BaseShapeType::T1 => "T shape",
+        BaseShapeType::L1 => "L shape",
+        BaseShapeType::I1 => "Line of 4",
+        BaseShapeType::O => "Single cell",
+        BaseShapeType::OO => "Square",
+        BaseShapeType::S => "S shape",
+        BaseShapeType::Z => "Z shape",
+        BaseShapeType::J => "J shape",
+        BaseShapeType::I3 => "Line of 3",
+    }
+}
+
+// a badge right under the panel below the left edge of every visible shape
+fn placement_badges(state: &Game, render_config: &UserRenderConfig) -> Vec<PlacementBadge> {
+    let top = render_config.panel_offset_y_px
+        + render_config.panel_rows as f32 * render_config.cell_size_px
+        + PLACEMENT_BADGE_GAP_PX;
+    solver::panel_shape_info(state)
+        .into_iter()
+        .map(|info| {
+            let col = state.panel.shape_choice[info.index].col_offset_in_panel_basis;
+            PlacementBadge {
+                placements: info.placements,
+                left: render_config.panel_offset_x_px + col as f32 * render_config.cell_size_px,
+                top,
+            }
+        })
+        .collect()
+}
+
+fn tutorial_prompt(step: TutorialStep, render_config: &UserRenderConfig) -> TutorialPrompt {
+    let (left, top) = match step {
+        TutorialStep::SelectShape => (
+            render_config.panel_offset_x_px,
+            render_config.panel_offset_y_px,
+        ),
+        TutorialStep::PlaceShape => (
+            render_config.board_offset_x_px,
+            render_config.board_offset_y_px,
+        ),
+    };
+    TutorialPrompt {
+        text: step.prompt(),
+        left,
+        top: top - TUTORIAL_PROMPT_RAISE_PX,
+    }
+}
+
+// expects the triangle pipeline to be set. The board and the panel stay visible under it.
+fn draw_pause_overlay(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    render_config: &UserRenderConfig,
+    pause_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if state.pause.is_none() {
+        return;
+    }
+    let size = render_config.window_size;
+    let vertices = render_rect(XY(0.0, 0.0), size.width as f32, size.height as f32, &size);
+    queue.write_buffer(pause_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, pause_vertex_buffer.slice(..));
+    cursor_state.set(render_pass, CursorState::Dim);
+    render_pass.draw(0..6, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// expects the triangle pipeline to be set. The whole bar is drawn first, the reached part in gold
+// on top of it.
+fn draw_goal_bar(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    progress: f32,
+    render_config: &UserRenderConfig,
+    goal_bar_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !render_config.show_goal_progress {
+        return;
+    }
+    let left = render_config.layout_left(GOAL_BAR_LEFT_PX, GOAL_BAR_WIDTH_PX);
+    let vertices = render_goal_bar(progress, left, &render_config.window_size);
+    queue.write_buffer(goal_bar_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, goal_bar_vertex_buffer.slice(..));
+    cursor_state.set(render_pass, CursorState::NotACursor);
+    render_pass.draw(0..6, 0..1);
+    if progress > 0.0 {
+        cursor_state.set(render_pass, CursorState::HighlightGold);
+        render_pass.draw(6..12, 0..1);
+        cursor_state.set(render_pass, CursorState::NotACursor);
+    }
+}
+
+// expects the triangle pipeline to be set
+fn draw_board_thumbnail(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    board: &Board,
+    board_origin: BoardOrigin,
+    render_config: &UserRenderConfig,
+    thumbnail_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    let top_left = XY(
+        render_config.layout_left(
+            THUMBNAIL_LEFT_PX,
+            render_config.cell_size_px * THUMBNAIL_SCALE * board.cols as f32,
+        ),
+        THUMBNAIL_TOP_PX,
+    );
+    let vertices = render_board_thumbnail(
+        board,
+        board_origin,
+        top_left,
+        render_config.cell_size_px * THUMBNAIL_SCALE,
+        &render_config.window_size,
+    );
+    queue.write_buffer(thumbnail_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, thumbnail_vertex_buffer.slice(..));
+    cursor_state.set(render_pass, CursorState::Highlight);
+    render_pass.draw(0..6, 0..1);
+    cursor_state.set(render_pass, CursorState::HighlightGold);
+    render_pass.draw(6..vertices.len() as u32, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// the board scaled down to `cell_px` cells: its whole area first, then a quad per filled cell
+fn render_board_thumbnail(
+    board: &Board,
+    board_origin: BoardOrigin,
+    top_left: XY,
+    cell_px: f32,
+    physical_size: &PhysicalSize<u32>,
+) -> Vec<Vertex> {
+    let mut vertices = render_rect(
+        top_left.clone(),
+        cell_px * board.cols as f32,
+        cell_px * board.rows as f32,
+        physical_size,
+    );
+    for row in 0..board.rows {
+        for col in 0..board.cols {
+            if board.get(col, row) != Some(&Cell::Filled) {
+                continue;
+            }
+            let visual_row = board_origin.visual_row(row, board.rows);
+            vertices.extend(render_rect(
+                XY(
+                    top_left.0 + col as f32 * cell_px,
+                    top_left.1 + visual_row as f32 * cell_px,
+                ),
+                cell_px,
+                cell_px,
+                physical_size,
+            ));
+        }
+    }
+    vertices
+}
+
+// background quad followed by the filled quad, `progress` of its width
+fn render_goal_bar(progress: f32, left: f32, physical_size: &PhysicalSize<u32>) -> Vec<Vertex> {
+    let mut vertices = render_rect(
+        XY(left, GOAL_BAR_TOP_PX),
+        GOAL_BAR_WIDTH_PX,
+        GOAL_BAR_HEIGHT_PX,
+        physical_size,
+    );
+    vertices.extend(render_rect(
+        XY(left, GOAL_BAR_TOP_PX),
+        GOAL_BAR_WIDTH_PX * progress.clamp(0.0, 1.0),
+        GOAL_BAR_HEIGHT_PX,
+        physical_size,
+    ));
+    vertices
+}
+
+pub(crate) fn render_rect(
+    top_left: XY,
+    width: f32,
+    height: f32,
+    physical_size: &PhysicalSize<u32>,
+) -> Vec<Vertex> {
+    let XY(x, y) = top_left;
+    let top_left = Vertex::ndc_vertex(x, y, physical_size, true);
+    let bot_left = Vertex::ndc_vertex(x, y + height, physical_size, true);
+    let bot_right = Vertex::ndc_vertex(x + width, y + height, physical_size, true);
+    let top_right = Vertex::ndc_vertex(x + width, y, physical_size, true);
+    vec![
+        bot_left, bot_right, top_left, top_left, bot_right, top_right,
+    ]
+}
+
+fn line_flash_color(lines_cleared: usize) -> CursorState {
+    match lines_cleared {
+        0 | 1 => CursorState::Highlight,
+        2 => CursorState::HighlightWarm,
+        _ => CursorState::HighlightGold,
+    }
+}
+
+// blinking: on for one `LINE_FLASH_BLINK`, off for the next
+fn line_flash_visible(elapsed: Duration) -> bool {
+    (elapsed.as_millis() / LINE_FLASH_BLINK.as_millis()).is_multiple_of(2)
+}
+
+// outlines of the nearly full lines, between a dim and a warm colour in time with the pulse
+// outlines over the board vertices, with the line pipeline and indices written per frame
+struct OutlinePass<'a> {
+    index_buffer: &'a wgpu::Buffer,
+    static_vertex_buffer: &'a wgpu::Buffer,
+    queue: &'a wgpu::Queue,
+    pipeline: &'a wgpu::RenderPipeline,
+}
+
+impl OutlinePass<'_> {
+    // sets up the pass to draw `indices`
+    fn bind(&self, render_pass: &mut wgpu::RenderPass<'_>, indices: &[u32]) {
+        render_pass.set_pipeline(self.pipeline);
+        render_pass.set_vertex_buffer(0, self.static_vertex_buffer.slice(..));
+        self.queue
+            .write_buffer(self.index_buffer, 0, cast_slice(indices));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    }
+}
+
+fn draw_edge_glow(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    render_config: &UserRenderConfig,
+    outline: &OutlinePass,
+) {
+    let (Some(threshold), Some(elapsed)) = (render_config.edge_glow_threshold, state.ui.edge_glow)
+    else {
+        return;
+    };
+    let lines = state.board.lines_filled_over(threshold);
+    let indices = render_line_outlines(
+        &lines,
+        render_config.board_size_cols,
+        render_config.board_size_rows,
+        state.config.board_origin,
+    );
+    if indices.is_empty() {
+        return;
+    }
+    let color = if steady_pulse(render_config, elapsed) > 0.5 {
+        CursorState::HighlightWarm
+    } else {
+        CursorState::Hint
+    };
+    outline.bind(render_pass, &indices);
+    cursor_state.set(render_pass, color);
+    // a strip per line, drawn together they'd be joined up
+    for line in 0..(indices.len() / 5) as u32 {
+        render_pass.draw_indexed(line * 5..line * 5 + 5, 0, 0..1);
+    }
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+fn draw_cursor_shadow(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    state: &Game,
+    input: &Input,
+    render_config: &UserRenderConfig,
+    outline: &OutlinePass,
+) {
+    if let Some(selected_shape) = &state.selected_shape {
+        let at = if over_board(&input.mouse_position, render_config) {
+            Some(input.mouse_position.clone())
+        } else if render_config.snap_preview_to_board
+            && off_play_area(&input.mouse_position, render_config)
+        {
+            Some(clamp_to_board(&input.mouse_position, render_config))
+        } else {
+            None
+        };
+        if let Some(at) = at {
+            // println!("Shape {:?} is selected", selected_shape.shape_type);
+            let strips =
+                render_contour(selected_shape, &at, render_config, state.config.wraparound);
+            outline.bind(render_pass, &strips.concat());
+            cursor_state.set(
+                render_pass,
+                shadow_color(state, selected_shape, &at, render_config),
+            );
+            // drawn together the parts of a wrapped shape would be joined up
+            let mut start = 0;
+            for strip in &strips {
+                let end = start + strip.len() as u32;
+                render_pass.draw_indexed(start..end, 0, 0..1);
+                start = end;
+            }
+            cursor_state.set(render_pass, CursorState::NotACursor);
+        };
+    }
+}
+
+// green when a click would place the shape where its shadow is, red otherwise. A shape with some
+// cells past the edge can't be placed, so its shadow is red as a whole, and so is any shadow
+// while cleared lines are still on the board.
+fn shadow_color(
+    state: &Game,
+    selected: &SelectedShape,
+    at: &XY,
+    render_config: &UserRenderConfig,
+) -> CursorState {
+    let (shape, cell) = aimed_placement(state, selected, at, render_config);
+    if state.accepts_placement(&shape, &cell) {
+        CursorState::ValidPlacement
+    } else {
+        CursorState::Cursor
+    }
+}
+
+// a line strip per part of the shape on the board. With `wraparound` the cells past an edge are
+// outlined at the opposite edge, where they'd land.
+fn render_contour(
+    shape: &SelectedShape,
+    mouse_position: &XY,
+    render_config: &UserRenderConfig,
+    wraparound: bool,
+) -> Vec<Vec<u32>> {
+    let placement_xy_0 = mouse_position.apply_offset(&shape.anchor_offset);
+    let placement_0_cell = to_cell_space(
+        XY(
+            render_config.board_offset_x_px,
+            render_config.board_offset_y_px,
+        ),
+        render_config.cell_size_px,
+        &placement_xy_0,
+    );
+    let mut visible_cells = Vec::new();
+    let (cols, rows) = (render_config.board_size_cols, render_config.board_size_rows);
+    for mut cell in shape.shape_type.cells_at(&placement_0_cell) {
+        if wraparound {
+            cell = cell.wrapped(cols, rows);
+        }
+        let CellCoord { col: nx, row: ny } = cell;
+        if nx >= 0 && nx < cols as i16 && ny >= 0 && ny < rows as i16 {
+            visible_cells.push(CellCoord::new(nx, ny));
+        }
+    }
+    connected_parts(visible_cells)
+        .iter()
+        .map(|part| {
+            let mut edge_set: HashSet<Edge> = HashSet::new();
+            for cell in part {
+                let edges = Edge::around_cell(cell, cols);
+                for edge in &edges {
+                    if !edge_set.insert(*edge) {
+                        edge_set.remove(edge);
+                    }
+                }
+            }
+            order_edges_for_linestrip(edge_set.into_iter().collect())
+        })
+        .collect()
+}
+
+// cells in groups that touch each other side by side, a wrapped shape comes apart at the edge
+fn connected_parts(mut cells: Vec<CellCoord>) -> Vec<Vec<CellCoord>> {
+    let mut parts = Vec::new();
+    while let Some(start) = cells.pop() {
+        let mut part = vec![start];
+        let mut ix = 0;
+        while ix < part.len() {
+            let CellCoord { col, row } = part[ix];
+            let (touching, rest): (Vec<_>, Vec<_>) = cells
+                .into_iter()
+                .partition(|c| (c.col - col).abs() + (c.row - row).abs() == 1);
+            part.extend(touching);
+            cells = rest;
+            ix += 1;
+        }
+        parts.push(part);
+    }
+    parts
+}
+
+fn order_edges_for_linestrip(edges: Vec<Edge>) -> Vec<u32> {
+    let mut ordered_vertices = Vec::new();
+    let mut visited = HashSet::new();
+    let mut edge_map: HashMap<u32, Vec<u32>> = HashMap::new();
+
+    // Build adjacency map
     for edge in &edges {
         edge_map.entry(edge.0).or_insert_with(Vec::new).push(edge.1);
         edge_map.entry(edge.1).or_insert_with(Vec::new).push(edge.0);
@@ -621,43 +2012,103 @@ fn render_cursor(
     ]
 }
 
+// `rotation` is the orientation the shape is turning from and how far it got, from 0 to 1
 fn render_cursor_shape(
     mouse_pos: &XY,
     selected_shape: &SelectedShape,
+    rotation: Option<(ShapeType, f32)>,
     cell_size_px: f32,
     physical_size: &PhysicalSize<u32>,
 ) -> Vec<Vertex> {
+    let mut top_lefts = cursor_shape_cells(mouse_pos, selected_shape, cell_size_px);
+    if let Some((from, progress)) = rotation {
+        let zero = mouse_pos.apply_offset(&selected_shape.anchor_offset);
+        top_lefts = lerp_cells(
+            &shape_cell_corners(&zero, &from, cell_size_px),
+            &top_lefts,
+            progress,
+        );
+    }
+    render_cell_quads(&top_lefts, cell_size_px, physical_size)
+}
+
+// orientation the held shape is turning from and how far along it is
+fn rotation_progress(ui: &UI, render_config: &UserRenderConfig) -> Option<(ShapeType, f32)> {
+    let animation = ui.rotation_animation.as_ref()?;
+    let duration = render_config.rotation_animation?;
+    let progress = animation.elapsed.as_secs_f32() / duration.as_secs_f32();
+    Some((animation.from, progress.min(1.0)))
+}
+
+// cells move in a straight line from where they were to where they are now. `cells()` keeps
+// the base cell order in every orientation, so the same index is the same cell.
+fn lerp_cells(from: &[XY], to: &[XY], progress: f32) -> Vec<XY> {
+    from.iter()
+        .zip(to)
+        .map(|(from, to)| {
+            XY(
+                from.0 + (to.0 - from.0) * progress,
+                from.1 + (to.1 - from.1) * progress,
+            )
+        })
+        .collect()
+}
+
+// pixel top-left corners of the held shape's cells. They come from `ShapeType::cells_at`, the
+// same as the board cells it lands on, so a rotated shape lands where it's drawn.
+fn cursor_shape_cells(
+    mouse_pos: &XY,
+    selected_shape: &SelectedShape,
+    cell_size_px: f32,
+) -> Vec<XY> {
     let zero = mouse_pos.apply_offset(&selected_shape.anchor_offset);
-    let cells = selected_shape.shape_type.cells();
+    shape_cell_corners(&zero, &selected_shape.shape_type, cell_size_px)
+}
+
+fn shape_cell_corners(zero: &XY, shape: &ShapeType, cell_size_px: f32) -> Vec<XY> {
+    shape
+        .cells_at(&CellCoord::new(0, 0))
+        .iter()
+        .map(|cell| {
+            XY(
+                zero.0 + cell.col as f32 * cell_size_px,
+                zero.1 + cell.row as f32 * cell_size_px,
+            )
+        })
+        .collect()
+}
 
+// quads for the shape cells, `zero` being the pixel position of the shape's top-left corner
+fn render_shape_cells(
+    cells: &[(usize, usize)],
+    zero: &XY,
+    cell_size_px: f32,
+    physical_size: &PhysicalSize<u32>,
+) -> Vec<Vertex> {
+    let top_lefts: Vec<XY> = cells
+        .iter()
+        .map(|cell| {
+            XY(
+                zero.0 + cell.0 as f32 * cell_size_px,
+                zero.1 + cell.1 as f32 * cell_size_px,
+            )
+        })
+        .collect();
+    render_cell_quads(&top_lefts, cell_size_px, physical_size)
+}
+
+// one quad per cell, given the pixel position of its top-left corner
+fn render_cell_quads(
+    top_lefts: &[XY],
+    cell_size_px: f32,
+    physical_size: &PhysicalSize<u32>,
+) -> Vec<Vertex> {
     let mut vertex_result: Vec<Vertex> = vec![];
-    for cell in cells {
-        let cell_x_offset = cell.0 as f32 * cell_size_px;
-        let cell_y_offset = cell.1 as f32 * cell_size_px;
-        let top_left = Vertex::ndc_vertex(
-            zero.0 + cell_x_offset,
-            zero.1 + cell_y_offset,
-            physical_size,
-            true,
-        );
-        let bot_left = Vertex::ndc_vertex(
-            zero.0 + cell_x_offset,
-            zero.1 + cell_size_px + cell_y_offset,
-            physical_size,
-            true,
-        );
-        let bot_right = Vertex::ndc_vertex(
-            zero.0 + cell_size_px + cell_x_offset,
-            zero.1 + cell_size_px + cell_y_offset,
-            physical_size,
-            true,
-        );
-        let top_right = Vertex::ndc_vertex(
-            zero.0 + cell_size_px + cell_x_offset,
-            zero.1 + cell_y_offset,
-            physical_size,
-            true,
-        );
+    for XY(x, y) in top_lefts {
+        let top_left = Vertex::ndc_vertex(*x, *y, physical_size, true);
+        let bot_left = Vertex::ndc_vertex(*x, y + cell_size_px, physical_size, true);
+        let bot_right = Vertex::ndc_vertex(x + cell_size_px, y + cell_size_px, physical_size, true);
+        let top_right = Vertex::ndc_vertex(x + cell_size_px, *y, physical_size, true);
         vertex_result.extend(&[
             bot_left, bot_right, top_left, top_left, bot_right, top_right,
         ])
@@ -665,17 +2116,242 @@ fn render_cursor_shape(
     vertex_result
 }
 
+// pixel position of the top-left of the shape's box in the panel, i.e. where its anchor offset is
+// measured from
+fn panel_origin_px(shape: &Shape, render_config: &UserRenderConfig) -> XY {
+    XY(
+        render_config.panel_offset_x_px
+            + shape.col_offset_in_panel_basis as f32 * render_config.cell_size_px,
+        render_config.panel_offset_y_px,
+    )
+}
+
+// expects the point pipeline to be set, drawn over the grid points in the cursor color
+fn draw_panel_origin_markers(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    cursor_state: &CursorStateBinder,
+    panel: &Panel,
+    render_config: &UserRenderConfig,
+    panel_origin_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !render_config.show_panel_origin_markers {
+        return;
+    }
+    let vertices: Vec<Vertex> = panel
+        .shape_choice
+        .iter()
+        .filter(|shape| shape.state == ShapeState::VISIBLE)
+        .take(MAX_PANEL_ORIGIN_MARKERS)
+        .map(|shape| {
+            let XY(x, y) = panel_origin_px(shape, render_config);
+            Vertex::ndc_vertex(x, y, &render_config.window_size, false)
+        })
+        .collect();
+
+    queue.write_buffer(panel_origin_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, panel_origin_vertex_buffer.slice(..));
+    cursor_state.set(render_pass, CursorState::Cursor);
+    render_pass.draw(0..vertices.len() as u32, 0..1);
+    cursor_state.set(render_pass, CursorState::NotACursor);
+}
+
+// the four orientations of the hovered panel shape, drawn small to the right of the panel
+fn render_rotation_preview(
+    shape_type: &ShapeType,
+    render_config: &UserRenderConfig,
+) -> Vec<Vertex> {
+    let mini_cell_px = render_config.cell_size_px * ROTATION_PREVIEW_SCALE;
+    let x0 = render_config.panel_offset_x_px
+        + (render_config.panel_cols + 1) as f32 * render_config.cell_size_px;
+    let y0 = render_config.panel_offset_y_px;
+
+    shape_type
+        .all_rotations()
+        .iter()
+        .enumerate()
+        .flat_map(|(i, rotated)| {
+            // 4 cells is the longest side of any shape, plus a gap
+            let zero = XY(x0 + (i * 5) as f32 * mini_cell_px, y0);
+            render_shape_cells(
+                &rotated.cells(),
+                &zero,
+                mini_cell_px,
+                &render_config.window_size,
+            )
+        })
+        .collect()
+}
+
+fn draw_rotation_preview(
+    render_pass: &mut wgpu::RenderPass<'_>,
+    state: &Game,
+    render_config: &UserRenderConfig,
+    rotation_preview_vertex_buffer: &wgpu::Buffer,
+    queue: &wgpu::Queue,
+) {
+    if !render_config.show_rotation_preview {
+        return;
+    }
+    let Some(shape) = state
+        .ui
+        .hovered_panel_shape
+        .and_then(|ix| state.panel.shape_choice.get(ix))
+    else {
+        return;
+    };
+
+    let vertices = render_rotation_preview(&shape.kind, render_config);
+    queue.write_buffer(rotation_preview_vertex_buffer, 0, cast_slice(&vertices));
+    render_pass.set_vertex_buffer(0, rotation_preview_vertex_buffer.slice(..));
+    render_pass.draw(0..vertices.len() as u32, 0..1);
+}
+
 fn create_cursor_buffer(device: &wgpu::Device) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Cursor Vertex Buffer"),
         // 6 vertices because of quad. If switch to index rendering - could keep it as 4
         //todo, currently we use the same buffer to render cursor shape. Could change it in the future.
-        size: (size_of::<Vertex>() * 6 * 5) as wgpu::BufferAddress,
+        // plus one quad for the snap indicator
+        size: (size_of::<Vertex>() * (SNAP_INDICATOR_VERTEX_OFFSET + 6)) as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST, // COPY_DST so we can update it
         mapped_at_creation: false,
     })
 }
 
+fn create_panel_origin_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Panel Origin Vertex Buffer"),
+        size: (size_of::<Vertex>() * MAX_PANEL_ORIGIN_MARKERS) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_pause_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Pause Overlay Vertex Buffer"),
+        // a quad over the whole window
+        size: (size_of::<Vertex>() * 6) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_goal_bar_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Goal Bar Vertex Buffer"),
+        // two quads
+        size: (size_of::<Vertex>() * 12) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// room for a quad on every panel cell
+fn create_panel_refresh_buffer(
+    device: &wgpu::Device,
+    render_config: &UserRenderConfig,
+) -> wgpu::Buffer {
+    let cells = render_config.panel_cols * render_config.panel_rows;
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Panel Refresh Vertex Buffer"),
+        size: (size_of::<Vertex>() * 6 * cells.max(1)) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_best_move_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Best Move Vertex Buffer"),
+        // a quad for each cell of the largest shape
+        size: (size_of::<Vertex>() * 6 * 4) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+// room for the thumbnail of a completely filled board
+fn create_thumbnail_buffer(
+    device: &wgpu::Device,
+    render_config: &UserRenderConfig,
+) -> wgpu::Buffer {
+    let cells = render_config.board_size_cols * render_config.board_size_rows;
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Board Thumbnail Vertex Buffer"),
+        size: (size_of::<Vertex>() * 6 * (cells + 1)) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_rotation_preview_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Rotation Preview Vertex Buffer"),
+        // 4 rotations of at most 4 cells, 6 vertices each
+        size: (size_of::<Vertex>() * 6 * 4 * 4) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_static_vertex_buffer(
+    device: &wgpu::Device,
+    render_config: &UserRenderConfig,
+) -> wgpu::Buffer {
+    let board_vertices = normalize_screen_to_ndc(
+        generate_board_vertices(render_config),
+        render_config.window_size,
+    );
+    let panel_vertices = normalize_screen_to_ndc(
+        generate_panel_vertices(render_config),
+        render_config.window_size,
+    );
+
+    let mut static_vertices = vec![];
+    static_vertices.extend(board_vertices);
+    static_vertices.extend(panel_vertices);
+
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Static Vertex Buffer"),
+        contents: cast_slice(&static_vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    })
+}
+
+// a slot of 6 indices for every board cell, then the panel shapes. They can't cover more than the
+// whole panel, however many of them are dealt.
+fn create_static_index_buffer(
+    device: &wgpu::Device,
+    render_config: &UserRenderConfig,
+) -> wgpu::Buffer {
+    let board_cells = render_config.board_size_cols * render_config.board_size_rows;
+    let panel_cells = render_config.panel_cols * render_config.panel_rows;
+    create_index_buffer(device, (board_cells + panel_cells) * 6)
+}
+
+fn create_line_flash_index_buffer(
+    device: &wgpu::Device,
+    render_config: &UserRenderConfig,
+) -> wgpu::Buffer {
+    create_index_buffer(
+        device,
+        render_config.board_size_cols * render_config.board_size_rows * 6,
+    )
+}
+
+// a closed loop of 5 per row and col
+fn create_edge_glow_index_buffer(
+    device: &wgpu::Device,
+    render_config: &UserRenderConfig,
+) -> wgpu::Buffer {
+    create_index_buffer(
+        device,
+        (render_config.board_size_cols + render_config.board_size_rows) * 5,
+    )
+}
+
 fn create_index_buffer(device: &wgpu::Device, max_indices: usize) -> wgpu::Buffer {
     device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Dynamic Index Buffer"),
@@ -707,10 +2383,8 @@ fn create_pipeline(
             entry_point: Some("fs_main"),
             targets: &[Some(wgpu::ColorTargetState {
                 format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::REPLACE,
-                }),
+                // every colour is opaque but the pause overlay
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -741,8 +2415,10 @@ fn create_pipeline(
 
 #[cfg(test)]
 mod tests {
-    use crate::game_entities::BaseShapeType;
+    use crate::game_entities::{ClearingAnimation, RotationAnimation, ShapeRot, ShapeType};
     use crate::space_converters::OffsetXY;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     use super::*;
 
@@ -755,25 +2431,74 @@ mod tests {
             panel_offset_x_px: 0.0,
             cell_size_px: 10.0,
             board_size_cols: 10,
+            board_size_rows: 10,
             panel_rows: 0,
             cursor_size: 0.0,
             panel_offset_y_px: 0.0,
+            lingering_frames: 0,
+            idle_lingering_frames: 0,
+            show_rotation_preview: false,
+            show_snap_indicator: false,
+            show_fill_percentage: false,
+            scale_override: None,
+            line_flash_by_count: false,
+            color_by_shape: false,
+            show_panel_origin_markers: false,
+            show_strategy_hud: false,
+            show_placement_badges: false,
+            show_legend: false,
+            show_best_move_ghost: false,
+            near_full_hint: None,
+            aim_clear_hint: false,
+            pause_render_when_occluded: true,
+            coalesce_resizes: true,
+            rotation_animation: None,
+            clear_animation: None,
+            panel_refresh_animation: None,
+            force_uniform_cursor_state: false,
+            show_goal_progress: false,
+            fullscreen: false,
+            window_position: None,
+            render_before_visible: true,
+            slow_motion_clears: false,
+            slow_motion_min_lines: 0,
+            slow_motion_duration: Duration::ZERO,
+            background_corner_radius_px: 0.0,
+            show_score_breakdown: false,
+            show_leaderboard: false,
+            show_placeable_count: false,
+            show_cursor_trail: false,
+            cursor_trail_length: 0,
+            frame_stats_window: 0,
+            show_danger_zone: false,
+            danger_zone_threshold: 1.0,
+            edge_glow_threshold: None,
+            layout_mirror: false,
+            cursor_smoothing: None,
+            touch_input: true,
+            reduce_motion: false,
+            snap_preview_to_board: false,
+            scale_text: true,
+            backends: wgpu::Backends::all(),
         }
     }
 
     #[test]
     fn test_render_contour_single_cell() {
         let shape = SelectedShape {
-            shape_type: BaseShapeType::O,
+            shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
             anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
         }; // 1x1 shape
         let mouse_position = XY(15.0, 15.0);
         let render_config = mock_render_config();
 
-        let contour = render_contour(&shape, &mouse_position, &render_config);
+        let contour = render_contour(&shape, &mouse_position, &render_config, false);
 
+        assert_eq!(contour.len(), 1);
         assert_eq!(
-            contour.len(),
+            contour[0].len(),
             5,
             "A single cell should have 4 contour edges"
         );
@@ -782,22 +2507,511 @@ mod tests {
     #[test]
     fn test_render_contour_l_shape() {
         let shape = SelectedShape {
-            shape_type: BaseShapeType::L1,
+            shape_type: ShapeType::new(BaseShapeType::L1, false, ShapeRot::No),
             anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
         }; // L-shape
         let mouse_position = XY(15.0, 15.0);
         let render_config = mock_render_config();
 
-        let contour = render_contour(&shape, &mouse_position, &render_config);
+        let contour = render_contour(&shape, &mouse_position, &render_config, false);
         print!("contour {:?}", contour);
 
         assert_eq!(
-            contour.len(),
+            contour[0].len(),
             11,
             "L-shape should have a valid contour with correct edges"
         );
     }
 
+    #[test]
+    fn test_wrapped_contour_is_a_strip_per_part() {
+        let shape = SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::T1, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        };
+        let render_config = mock_render_config();
+        // the bottom right cell is past the right edge, it wraps to column 0
+        let mouse_position = XY(85.0, 15.0);
+
+        let contour = render_contour(&shape, &mouse_position, &render_config, true);
+
+        let mut lengths: Vec<usize> = contour.iter().map(|strip| strip.len()).collect();
+        lengths.sort();
+        // a single cell, and the 3 cell corner
+        assert_eq!(lengths, vec![5, 9]);
+        // cut off at the edge without it
+        let contour = render_contour(&shape, &mouse_position, &render_config, false);
+        assert_eq!(contour.len(), 1);
+    }
+
+    #[test]
+    fn test_shadow_hanging_over_the_edge_is_invalid() {
+        let render_config = mock_render_config();
+        let mut game = Game::new_level(render_config.board_size_cols, 1, 0);
+        game.board = Board::new(render_config.board_size_cols);
+        // 3 wide, in the last two columns the right one is past the edge
+        let shape = SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::T1, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        };
+        let color_at = |game: &Game, col: f32| {
+            shadow_color(game, &shape, &XY(col * 10.0 + 5.0, 15.0), &render_config)
+        };
+
+        assert_eq!(color_at(&game, 7.0), CursorState::ValidPlacement);
+        assert_eq!(color_at(&game, 8.0), CursorState::Cursor);
+
+        // the part past the edge comes back on the other side
+        game.config.wraparound = true;
+        assert_eq!(color_at(&game, 8.0), CursorState::ValidPlacement);
+        // red as well where the board is taken
+        game.board.set_cell(8, 2, Cell::Filled);
+        assert_eq!(color_at(&game, 7.0), CursorState::Cursor);
+
+        // and anywhere while a clear is still playing out, nothing goes down until it's done
+        game.clearing = Some(ClearingAnimation {
+            lines: ClearedLines {
+                rows: vec![9],
+                cols: vec![],
+            },
+            elapsed: Duration::ZERO,
+        });
+        assert_eq!(color_at(&game, 0.0), CursorState::Cursor);
+    }
+
+    #[test]
+    fn test_rotated_cursor_shape_is_drawn_where_it_lands() {
+        let shape = SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::T1, false, ShapeRot::Cw90),
+            anchor_offset: OffsetXY(-7, -12),
+            picked_up_from: None,
+            panel_index: None,
+        };
+        let render_config = UserRenderConfig {
+            board_offset_x_px: 5.0,
+            board_offset_y_px: 8.0,
+            ..mock_render_config()
+        };
+        let mouse_position = XY(44.0, 51.0);
+        let board_top_left = XY(
+            render_config.board_offset_x_px,
+            render_config.board_offset_y_px,
+        );
+        let cell_px = render_config.cell_size_px;
+
+        // the way `PlacementSystem` finds where the shape goes
+        let origin = to_cell_space(
+            board_top_left.clone(),
+            cell_px,
+            &mouse_position.apply_offset(&shape.anchor_offset),
+        );
+        let placed = shape.shape_type.cells_at(&origin);
+        let drawn: Vec<CellCoord> = cursor_shape_cells(&mouse_position, &shape, cell_px)
+            .iter()
+            .map(|top_left| to_cell_space(board_top_left.clone(), cell_px, top_left))
+            .collect();
+
+        assert_eq!(drawn, placed);
+        assert_eq!(placed.len(), 4);
+    }
+
+    #[test]
+    fn test_rotation_animation_moves_cells_between_orientations() {
+        let from = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+        let to = from.rotated_cw();
+        let zero = XY(100.0, 100.0);
+        let start = shape_cell_corners(&zero, &from, 10.0);
+        let end = shape_cell_corners(&zero, &to, 10.0);
+
+        let halfway = lerp_cells(&start, &end, 0.5);
+
+        assert_eq!(halfway.len(), 4);
+        let mut moved = 0;
+        for ((a, b), mid) in start.iter().zip(&end).zip(&halfway) {
+            assert_eq!(mid.0, (a.0 + b.0) / 2.0);
+            assert_eq!(mid.1, (a.1 + b.1) / 2.0);
+            if (a.0, a.1) != (b.0, b.1) {
+                moved += 1;
+                assert!((mid.0, mid.1) != (a.0, a.1) && (mid.0, mid.1) != (b.0, b.1));
+            }
+        }
+        assert!(moved > 0);
+        let done = lerp_cells(&start, &end, 1.0);
+        assert!(done.iter().zip(&end).all(|(d, e)| (d.0, d.1) == (e.0, e.1)));
+
+        let ui = UI {
+            rotation_animation: Some(RotationAnimation {
+                from,
+                elapsed: Duration::from_millis(45),
+            }),
+            ..Game::new_level(4, 1, 0).ui
+        };
+        let render_config = UserRenderConfig {
+            rotation_animation: Some(Duration::from_millis(90)),
+            ..mock_render_config()
+        };
+        assert_eq!(rotation_progress(&ui, &render_config), Some((from, 0.5)));
+        assert_eq!(rotation_progress(&ui, &mock_render_config()), None);
+    }
+
+    #[test]
+    fn test_lingering_frames_for_state() {
+        let render_config = UserRenderConfig {
+            lingering_frames: 10,
+            idle_lingering_frames: 2,
+            ..mock_render_config()
+        };
+
+        assert_eq!(lingering_frames_for(GameState::Playing, &render_config), 10);
+        assert_eq!(
+            lingering_frames_for(GameState::MoveToNextLevel, &render_config),
+            10
+        );
+        assert_eq!(lingering_frames_for(GameState::GameOver, &render_config), 2);
+    }
+
+    #[test]
+    fn test_resizes_within_a_frame_configure_the_last_size() {
+        let mut pending = PendingResize::default();
+        pending.request(PhysicalSize::new(800, 600));
+        pending.request(PhysicalSize::new(820, 610));
+        pending.request(PhysicalSize::new(840, 620));
+        // minimized in between
+        pending.request(PhysicalSize::new(0, 0));
+
+        assert_eq!(pending.take(), Some(PhysicalSize::new(840, 620)));
+        // nothing left for the next frame
+        assert_eq!(pending.take(), None);
+    }
+
+    #[test]
+    fn test_nothing_is_rendered_while_occluded() {
+        let render_config = mock_render_config();
+        let mut game = Game::new_level(4, 1, 0);
+        game.ui.need_to_update_board = true;
+
+        for _ in 0..3 {
+            assert!(skip_render(
+                true,
+                &mut game.ui,
+                &None,
+                GameState::Playing,
+                false,
+                &render_config
+            ));
+        }
+        assert!(game.ui.need_to_update_board);
+        assert!(!skip_render(
+            false,
+            &mut game.ui,
+            &None,
+            GameState::Playing,
+            false,
+            &render_config
+        ));
+    }
+
+    #[test]
+    fn test_pausing_an_idle_game_draws_the_overlay() {
+        let render_config = mock_render_config();
+        let mut game = Game::new_level(4, 1, 0);
+        game.ui.need_to_update_board = false;
+        game.ui.need_to_update_panel = false;
+        game.ui.lingering_frames = 0;
+        let skip = |game: &mut Game, paused: bool| {
+            skip_render(
+                false,
+                &mut game.ui,
+                &None,
+                GameState::Playing,
+                paused,
+                &render_config,
+            )
+        };
+        assert!(skip(&mut game, false));
+
+        assert!(!skip(&mut game, true));
+        // idle again once the lingering frames are drawn
+        while !skip(&mut game, true) {}
+        assert!(!skip(&mut game, false));
+    }
+
+    #[test]
+    fn test_placement_badges_count_turned_shapes_when_rotation_is_on() {
+        let render_config = mock_render_config();
+        let mut game = Game::new_level(3, 1, 0);
+        // only the right column is free, the lying line fits it once turned
+        game.board = Board::from_ascii("##.\n##.\n##.\n").unwrap();
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![ShapeType::new(
+            BaseShapeType::I3,
+            false,
+            ShapeRot::Cw90,
+        )]));
+
+        game.config.rotation = false;
+        assert_eq!(placement_badges(&game, &render_config)[0].placements, 0);
+
+        game.config.rotation = true;
+        assert_eq!(placement_badges(&game, &render_config)[0].placements, 1);
+    }
+
+    #[test]
+    fn test_placement_badges_count_where_each_shape_fits() {
+        let render_config = mock_render_config();
+        let mut game = Game::new_level(3, 1, 0);
+        game.board = Board::from_ascii("#..\n...\n..#\n").unwrap();
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![
+            ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::OO, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::I1, false, ShapeRot::No),
+        ]));
+
+        let badges = placement_badges(&game, &render_config);
+
+        // 7 empty cells, 2 free squares and no room for a line of four
+        assert_eq!(
+            badges.iter().map(|b| b.placements).collect::<Vec<_>>(),
+            vec![7, 2, 0]
+        );
+        // the square starts two panel cells in, after the dot and a gap
+        assert_eq!(
+            badges[1].left - badges[0].left,
+            2.0 * render_config.cell_size_px
+        );
+        assert!(badges.iter().all(|b| b.top == badges[0].top));
+        assert!(badges[0].top > render_config.panel_offset_y_px);
+
+        game.panel.shape_choice[0].set_state(ShapeState::PLACED);
+        assert_eq!(placement_badges(&game, &render_config).len(), 2);
+    }
+
+    #[test]
+    fn test_legend_lists_the_colours_in_use() {
+        let mut render_config = mock_render_config();
+        let mut game = Game::new_level(4, 1, 0);
+        let labels = |game: &Game, cfg: &UserRenderConfig| -> Vec<&'static str> {
+            legend_entries(game, cfg).iter().map(|e| e.label).collect()
+        };
+        assert_eq!(
+            labels(&game, &render_config),
+            vec!["Filled cell", "Cleared line"]
+        );
+
+        game.config.bonus_cells = 2;
+        render_config.near_full_hint = Some(1);
+        let entries = legend_entries(&game, &render_config);
+
+        assert_eq!(
+            entries.iter().map(|e| e.label).collect::<Vec<_>>(),
+            vec![
+                "Filled cell",
+                "Bonus cell",
+                "Completes a line",
+                "Cleared line"
+            ]
+        );
+        assert_eq!(entries[1].color, CursorState::HighlightGold.rgb());
+        assert_eq!(entries[2].color, CursorState::Hint.rgb());
+
+        render_config.color_by_shape = true;
+        let entries = legend_entries(&game, &render_config);
+
+        assert_eq!(entries[1].label, "T shape");
+        assert_eq!(entries[1].color, CursorState::ShapeT1.rgb());
+        assert_eq!(entries.len(), 4 + BaseShapeType::iter().count());
+    }
+
+    #[test]
+    fn test_best_move_ghost_shows_the_suggested_move() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::from_ascii("....\n....\n#.##\n#.##\n").unwrap();
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![square, line]));
+
+        let (_, shape, cell) = solver::suggest_best_move(&game).unwrap();
+        assert_eq!((shape, cell), (line, CellCoord::new(1, 0)));
+        assert_eq!(best_move_ghost_cells(&game), shape.cells_at(&cell),);
+
+        // board rows 0 and 1 are at the bottom of the screen when rows count from there
+        game.config.board_origin = BoardOrigin::BottomLeft;
+        game.board = Board::from_ascii("#..#\n#..#\n....\n....\n").unwrap();
+        let mut ghost = best_move_ghost_cells(&game);
+        ghost.sort_by_key(|c| (c.row, c.col));
+        assert_eq!(
+            ghost,
+            vec![
+                CellCoord::new(1, 2),
+                CellCoord::new(2, 2),
+                CellCoord::new(1, 3),
+                CellCoord::new(2, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snap_indicator_quad_on_hovered_cell() {
+        let mut config = mock_render_config();
+        config.window_size = PhysicalSize::new(200, 200);
+        config.board_offset_x_px = 20.0;
+        config.board_offset_y_px = 40.0;
+
+        // cell (2, 1) spans x 40..50, y 50..60, the box is inset by 3px on each side
+        let vertices = render_snap_indicator(&XY(47.0, 52.0), &config).unwrap();
+        let xs: HashSet<i32> = vertices
+            .iter()
+            .map(|v| ((v.position.x + 1.0) / 2.0 * 200.0).round() as i32)
+            .collect();
+        let ys: HashSet<i32> = vertices
+            .iter()
+            .map(|v| ((1.0 - v.position.y) / 2.0 * 200.0).round() as i32)
+            .collect();
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(xs, HashSet::from([43, 47]));
+        assert_eq!(ys, HashSet::from([53, 57]));
+        assert!(render_snap_indicator(&XY(10.0, 52.0), &config).is_none());
+    }
+
+    #[test]
+    fn test_scale_override_wins_over_window_scale() {
+        let mut config = mock_render_config();
+        config.window_size = PhysicalSize::new(100, 50);
+
+        assert_eq!(
+            physical_window_size(&config, 2.0),
+            PhysicalSize::new(200, 100)
+        );
+        config.scale_override = Some(1.5);
+        assert_eq!(
+            physical_window_size(&config, 2.0),
+            PhysicalSize::new(150, 75)
+        );
+    }
+
+    #[test]
+    fn test_line_flash_color_by_lines_cleared() {
+        assert_eq!(line_flash_color(1), CursorState::Highlight);
+        assert_eq!(line_flash_color(2), CursorState::HighlightWarm);
+        assert_eq!(line_flash_color(4), CursorState::HighlightGold);
+    }
+
+    #[test]
+    fn test_clearing_lines_blink_while_still_on_the_board() {
+        assert!(line_flash_visible(Duration::ZERO));
+        assert!(!line_flash_visible(LINE_FLASH_BLINK));
+        assert!(line_flash_visible(LINE_FLASH_BLINK * 2));
+    }
+
+    #[test]
+    fn test_goal_bar_fills_with_progress() {
+        let size = PhysicalSize::new(1200, 800);
+        let bar_right = |progress: f32| {
+            render_goal_bar(progress, GOAL_BAR_LEFT_PX, &size)[7]
+                .position
+                .x
+        };
+        let full_right = render_goal_bar(1.0, GOAL_BAR_LEFT_PX, &size)[1].position.x;
+
+        assert_eq!(render_goal_bar(0.5, GOAL_BAR_LEFT_PX, &size).len(), 12);
+        assert_eq!(bar_right(1.0), full_right);
+        assert_eq!(bar_right(2.0), full_right);
+        assert!(bar_right(0.25) < bar_right(0.5));
+    }
+
+    #[test]
+    fn test_panel_origin_marker_position() {
+        let mut config = mock_render_config();
+        config.panel_offset_x_px = 100.0;
+        config.panel_offset_y_px = 400.0;
+        let shapes = Shape::lay_out_in_panel(vec![
+            ShapeType::new(BaseShapeType::T1, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::L1, false, ShapeRot::No),
+        ]);
+
+        // T is 3 cells wide plus a gap, so the L starts at col 4
+        assert_eq!(panel_origin_px(&shapes[0], &config).0, 100.0);
+        assert_eq!(panel_origin_px(&shapes[1], &config).0, 140.0);
+        assert_eq!(panel_origin_px(&shapes[1], &config).1, 400.0);
+    }
+
+    #[test]
+    fn test_resized_board_moves_panel_down() {
+        let config = UserRenderConfig::default();
+        let grown = config.resized_board(config.board_size_cols + 2, config.board_size_rows + 1);
+
+        assert_eq!(grown.board_size_cols, 12);
+        assert_eq!(grown.board_size_rows, 11);
+        // only the extra row pushes the panel down
+        assert_eq!(
+            grown.panel_offset_y_px,
+            config.panel_offset_y_px + config.cell_size_px
+        );
+    }
+
+    #[test]
+    fn test_many_panel_shapes_fit_the_panel_index_slots() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for n in 1..=8 {
+            let config = UserRenderConfig::default().with_panel_shapes(n);
+            let panel = Panel::generate_for_n(&mut rng, n);
+
+            let indices = render_panel(&panel, config.panel_cols, 0);
+
+            assert!(indices.len() <= config.panel_cols * config.panel_rows * 6);
+        }
+    }
+
+    #[test]
+    fn test_backend_names() {
+        assert_eq!(backends_from_name("auto"), Some(wgpu::Backends::all()));
+        assert_eq!(backends_from_name("Vulkan"), Some(wgpu::Backends::VULKAN));
+        assert_eq!(backends_from_name("metal"), Some(wgpu::Backends::METAL));
+        assert_eq!(backends_from_name(" dx12 "), Some(wgpu::Backends::DX12));
+        assert_eq!(backends_from_name("gl"), Some(wgpu::Backends::GL));
+        assert_eq!(backends_from_name("glide"), None);
+        assert_eq!(UserRenderConfig::default().backends, wgpu::Backends::all());
+    }
+
+    #[test]
+    fn test_mirroring_twice_restores_the_layout() {
+        let config = UserRenderConfig::default();
+        let mirrored = config.mirrored();
+
+        assert!(mirrored.layout_mirror);
+        // the board's right edge is where its left edge was, seen from the other side
+        assert_eq!(
+            mirrored.board_offset_x_px + config.cell_size_px * config.board_size_cols as f32,
+            config.window_size.width as f32 - config.board_offset_x_px
+        );
+        assert_eq!(mirrored.layout_left(800.0, 200.0), 200.0);
+
+        let restored = mirrored.mirrored();
+        assert!(!restored.layout_mirror);
+        assert_eq!(restored.board_offset_x_px, config.board_offset_x_px);
+        assert_eq!(restored.panel_offset_x_px, config.panel_offset_x_px);
+        assert_eq!(restored.layout_left(800.0, 200.0), 800.0);
+    }
+
+    #[test]
+    fn test_rotation_preview_has_all_four_rotations() {
+        let render_config = UserRenderConfig {
+            window_size: PhysicalSize::new(1000, 1000),
+            ..mock_render_config()
+        };
+        let shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+
+        let vertices = render_rotation_preview(&shape, &render_config);
+
+        assert_eq!(vertices.len(), 4 * 4 * 6);
+    }
+
     #[test]
     fn test_order_edges_for_linestrip() {
         let edges = vec![
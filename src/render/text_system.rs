@@ -1,11 +1,16 @@
 use std::rc::Rc;
 
-use crate::game_entities::GameStats;
+use crate::game_entities::{GameStats, ScoreLedger};
+use crate::leaderboard::LeaderboardEntry;
+use crate::menu::MenuState;
+use crate::render::render::mirror_x;
+use crate::solver::PanelShapeInfo;
 use glyphon::{
     Attrs, Buffer, Cache, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache,
     TextArea, TextAtlas, TextBounds, TextRenderer, Viewport,
 };
 use wgpu::{MultisampleState, RenderPass};
+use winit::dpi::PhysicalSize;
 
 pub struct TextSystem {
     pub font_system: FontSystem,
@@ -13,8 +18,30 @@ pub struct TextSystem {
     pub atlas: TextAtlas,
     pub renderer: TextRenderer,
     score_buffer: Buffer,
+    best_score_buffer: Buffer,
+    combo_buffer: Buffer,
+    paused_buffer: Buffer,
     target_score_buffer: Buffer,
     level_buffer: Buffer,
+    fill_buffer: Buffer,
+    placeable_buffer: Buffer,
+    goal_buffer: Buffer,
+    daily_buffer: Buffer,
+    tutorial_buffer: Buffer,
+    breakdown_buffer: Buffer,
+    leaderboard_buffer: Buffer,
+    strategy_buffer: Buffer,
+    menu_buffer: Buffer,
+    frame_stats_buffer: Buffer,
+    legend_buffer: Buffer,
+    // one per panel shape with a badge, added as more are needed
+    badge_buffers: Vec<Buffer>,
+    // values the buffers were last shaped with, so unchanged stats reuse the prepared glyphs
+    last_rendered: Option<HudValues>,
+    // window width to mirror the HUD positions in, see `UserRenderConfig::layout_mirror`
+    mirror_width: Option<f32>,
+    // positions and sizes are multiplied by this, see `render::text_scale`
+    scale: f32,
     device: Rc<wgpu::Device>,
     queue: Rc<wgpu::Queue>,
     viewport: Viewport,
@@ -26,6 +53,8 @@ impl TextSystem {
         queue: Rc<wgpu::Queue>,
         format: wgpu::TextureFormat,
         resolution: Resolution,
+        mirror_width: Option<f32>,
+        scale: f32,
     ) -> Self {
         let mut font_system = FontSystem::new();
         let swash_cache = SwashCache::new();
@@ -40,11 +69,40 @@ impl TextSystem {
             None,
         );
         let mut score_buffer = Buffer::new(&mut font_system, Metrics::new(30.0, 40.0));
+        let mut best_score_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut combo_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut paused_buffer = Buffer::new(&mut font_system, Metrics::new(48.0, 60.0));
         let mut target_score_buffer = Buffer::new(&mut font_system, Metrics::new(30.0, 40.0));
         let mut level_buffer = Buffer::new(&mut font_system, Metrics::new(30.0, 40.0));
+        let mut fill_buffer = Buffer::new(&mut font_system, Metrics::new(30.0, 40.0));
+        let mut placeable_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut goal_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut daily_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut tutorial_buffer = Buffer::new(&mut font_system, Metrics::new(24.0, 30.0));
+        let mut breakdown_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut leaderboard_buffer = Buffer::new(&mut font_system, Metrics::new(16.0, 20.0));
+        let mut strategy_buffer = Buffer::new(&mut font_system, Metrics::new(20.0, 26.0));
+        let mut menu_buffer = Buffer::new(&mut font_system, Metrics::new(24.0, 30.0));
+        let mut frame_stats_buffer = Buffer::new(&mut font_system, Metrics::new(16.0, 20.0));
+        let mut legend_buffer = Buffer::new(&mut font_system, Metrics::new(16.0, 20.0));
         score_buffer.set_size(&mut font_system, Some(200.0), Some(50.0));
+        best_score_buffer.set_size(&mut font_system, Some(200.0), Some(30.0));
+        combo_buffer.set_size(&mut font_system, Some(200.0), Some(30.0));
+        paused_buffer.set_size(&mut font_system, Some(300.0), Some(70.0));
         target_score_buffer.set_size(&mut font_system, Some(200.0), Some(50.0));
         level_buffer.set_size(&mut font_system, Some(200.0), Some(50.0));
+        fill_buffer.set_size(&mut font_system, Some(200.0), Some(50.0));
+        placeable_buffer.set_size(&mut font_system, Some(250.0), Some(30.0));
+        goal_buffer.set_size(&mut font_system, Some(250.0), Some(30.0));
+        daily_buffer.set_size(&mut font_system, Some(250.0), Some(30.0));
+        tutorial_buffer.set_size(&mut font_system, Some(400.0), Some(35.0));
+        breakdown_buffer.set_size(&mut font_system, Some(300.0), Some(230.0));
+        leaderboard_buffer.set_size(&mut font_system, Some(350.0), Some(230.0));
+        strategy_buffer.set_size(&mut font_system, Some(350.0), Some(300.0));
+        menu_buffer.set_size(&mut font_system, Some(300.0), Some(100.0));
+        frame_stats_buffer.set_size(&mut font_system, Some(400.0), Some(70.0));
+        // room for a line per shape colour as well
+        legend_buffer.set_size(&mut font_system, Some(300.0), Some(320.0));
 
         Self {
             font_system,
@@ -52,25 +110,82 @@ impl TextSystem {
             atlas,
             renderer,
             score_buffer,
+            best_score_buffer,
+            combo_buffer,
+            paused_buffer,
             level_buffer,
             target_score_buffer,
+            fill_buffer,
+            placeable_buffer,
+            goal_buffer,
+            daily_buffer,
+            tutorial_buffer,
+            breakdown_buffer,
+            leaderboard_buffer,
+            strategy_buffer,
+            menu_buffer,
+            frame_stats_buffer,
+            legend_buffer,
+            badge_buffers: vec![],
+            last_rendered: None,
+            mirror_width,
+            scale,
             device,
             queue,
             viewport,
         }
     }
 
-    pub fn render_score(&mut self, game_stats: &GameStats, render_pass: &mut RenderPass) {
+    pub fn resize(&mut self, physical_size: PhysicalSize<u32>, scale: f32) {
+        self.viewport.update(
+            self.queue.as_ref(),
+            Resolution {
+                width: physical_size.width,
+                height: physical_size.height,
+            },
+        );
+        self.scale = scale;
+        self.last_rendered = None;
+    }
+
+    pub fn render_score(&mut self, values: HudValues, render_pass: &mut RenderPass) {
+        if needs_reshape(&mut self.last_rendered, &values) {
+            self.prepare_score(&values);
+        }
+
+        self.renderer
+            .render(&self.atlas, &self.viewport, render_pass)
+            .unwrap();
+    }
+
+    fn prepare_score(&mut self, values: &HudValues) {
+        let mirror_width = self.mirror_width;
         self.score_buffer.set_text(
             &mut self.font_system,
-            &format!("Score: {}", game_stats.current_score),
+            &format!("Score: {}", values.score),
             Attrs::new().family(Family::SansSerif),
             Shaping::Advanced,
         );
         let score_text = TextArea {
             buffer: &mut self.score_buffer,
-            left: 800.0, // X Position (left corner)
-            top: 100.0,   // Y Position (top corner)
+            left: hud_left(mirror_width, 800.0, 200.0),
+            top: 100.0, // Y Position (top corner)
+            scale: 1.0,
+            bounds: TextBounds::default(),
+            default_color: Color::rgba(0, 255, 0, 255),
+            custom_glyphs: &[],
+        };
+
+        self.best_score_buffer.set_text(
+            &mut self.font_system,
+            &format!("Best: {}", values.best_score),
+            Attrs::new().family(Family::SansSerif),
+            Shaping::Advanced,
+        );
+        let best_score_text = TextArea {
+            buffer: &mut self.best_score_buffer,
+            left: hud_left(mirror_width, 800.0, 200.0),
+            top: 145.0, // Y Position (top corner), under the score
             scale: 1.0,
             bounds: TextBounds::default(),
             default_color: Color::rgba(0, 255, 0, 255),
@@ -79,15 +194,15 @@ impl TextSystem {
 
         self.target_score_buffer.set_text(
             &mut self.font_system,
-            &format!("Target: {}", game_stats.target_score),
+            &format!("Target: {}", values.target_score),
             Attrs::new().family(Family::SansSerif),
             Shaping::Advanced,
         );
 
         let target_score_text = TextArea {
             buffer: &mut self.target_score_buffer,
-            left: 800.0, // X Position (left corner)
-            top: 200.0,   // Y Position (top corner)
+            left: hud_left(mirror_width, 800.0, 200.0),
+            top: 200.0, // Y Position (top corner)
             scale: 1.0,
             bounds: TextBounds::default(),
             default_color: Color::rgba(0, 255, 0, 255),
@@ -96,36 +211,565 @@ impl TextSystem {
 
         self.level_buffer.set_text(
             &mut self.font_system,
-            &format!("Level: {}", game_stats.level),
+            &format!("Level: {}", values.level),
             Attrs::new().family(Family::SansSerif),
             Shaping::Advanced,
         );
 
         let level_text = TextArea {
             buffer: &mut self.level_buffer,
-            left: 500.0, // X Position (left corner)
-            top: 25.0,   // Y Position (top corner)
+            left: hud_left(mirror_width, 500.0, 400.0),
+            top: 25.0, // Y Position (top corner)
             scale: 2.0,
             bounds: TextBounds::default(),
             default_color: Color::rgba(0, 255, 0, 255),
             custom_glyphs: &[],
         };
 
+        let mut text_areas = vec![score_text, best_score_text, target_score_text, level_text];
+        if values.combo > 1 {
+            self.combo_buffer.set_text(
+                &mut self.font_system,
+                &format!("Combo x{}", values.combo),
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.combo_buffer,
+                left: hud_left(mirror_width, 800.0, 200.0),
+                top: 250.0, // Y Position (top corner), under the target
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(255, 200, 0, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(percent) = values.fill_percent {
+            self.fill_buffer.set_text(
+                &mut self.font_system,
+                &format!("Filled: {}%", percent),
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.fill_buffer,
+                left: hud_left(mirror_width, 800.0, 200.0),
+                top: 300.0, // Y Position (top corner)
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(0, 255, 0, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some((placeable, visible)) = values.placeable {
+            self.placeable_buffer.set_text(
+                &mut self.font_system,
+                &format!("Placeable: {}/{}", placeable, visible),
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            // the last shape that fits is as close as it gets to game over
+            let color = match placeable {
+                0 | 1 => Color::rgba(255, 60, 60, 255),
+                _ => Color::rgba(0, 255, 0, 255),
+            };
+            text_areas.push(TextArea {
+                buffer: &mut self.placeable_buffer,
+                left: hud_left(mirror_width, 800.0, 250.0),
+                top: 345.0, // Y Position (top corner), under the fill percentage
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: color,
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(goal) = &values.goal {
+            self.goal_buffer.set_text(
+                &mut self.font_system,
+                goal,
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.goal_buffer,
+                left: hud_left(mirror_width, 800.0, 250.0),
+                top: 240.0, // Y Position (top corner), right above the goal bar
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(0, 255, 0, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(date) = &values.daily {
+            self.daily_buffer.set_text(
+                &mut self.font_system,
+                &format!("Daily: {}", date),
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.daily_buffer,
+                left: hud_left(mirror_width, 800.0, 250.0),
+                top: 60.0, // Y Position (top corner), above the score
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(255, 220, 80, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(prompt) = &values.tutorial {
+            self.tutorial_buffer.set_text(
+                &mut self.font_system,
+                prompt.text,
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.tutorial_buffer,
+                left: prompt.left,
+                top: prompt.top,
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(255, 220, 80, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(ledger) = &values.score_breakdown {
+            self.breakdown_buffer.set_text(
+                &mut self.font_system,
+                &breakdown_text(ledger, values.retry_key.as_deref()),
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.breakdown_buffer,
+                left: hud_left(mirror_width, 800.0, 300.0),
+                top: 350.0, // Y Position (top corner)
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(0, 255, 0, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(entries) = &values.leaderboard {
+            self.leaderboard_buffer.set_text(
+                &mut self.font_system,
+                &leaderboard_text(entries),
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.leaderboard_buffer,
+                left: hud_left(mirror_width, 800.0, 350.0),
+                top: 560.0, // Y Position (top corner)
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(255, 220, 80, 255),
+                custom_glyphs: &[],
+            });
+        }
+        if let Some(strategy) = &values.strategy {
+            self.strategy_buffer.set_text(
+                &mut self.font_system,
+                &strategy_text(strategy),
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.strategy_buffer,
+                left: hud_left(mirror_width, 800.0, 350.0),
+                top: 380.0, // Y Position (top corner)
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(200, 200, 200, 255),
+                custom_glyphs: &[],
+            });
+        }
+
+        if let Some(menu) = &values.menu {
+            let lines = menu_lines(menu);
+            let default_attrs = Attrs::new().family(Family::Monospace);
+            let selected_attrs = default_attrs.color(Color::rgba(255, 220, 80, 255));
+            self.menu_buffer.set_rich_text(
+                &mut self.font_system,
+                lines.iter().map(|(line, selected)| {
+                    let attrs = if *selected {
+                        selected_attrs
+                    } else {
+                        default_attrs
+                    };
+                    (line.as_str(), attrs)
+                }),
+                default_attrs,
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.menu_buffer,
+                left: hud_left(mirror_width, 450.0, 300.0),
+                top: 350.0, // Y Position (top corner), right of the board
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(200, 200, 200, 255),
+                custom_glyphs: &[],
+            });
+        }
+
+        if values.paused {
+            self.paused_buffer.set_text(
+                &mut self.font_system,
+                "PAUSED",
+                Attrs::new().family(Family::SansSerif),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.paused_buffer,
+                left: hud_left(mirror_width, 500.0, 300.0),
+                top: 370.0, // Y Position (top corner), the middle of the window
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(255, 255, 255, 255),
+                custom_glyphs: &[],
+            });
+        }
+
+        if let Some(frame_stats) = &values.frame_stats {
+            self.frame_stats_buffer.set_text(
+                &mut self.font_system,
+                frame_stats,
+                Attrs::new().family(Family::Monospace),
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.frame_stats_buffer,
+                left: hud_left(mirror_width, 400.0, 400.0),
+                top: 700.0, // Y Position (top corner), under the panel
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(200, 200, 200, 255),
+                custom_glyphs: &[],
+            });
+        }
+
+        if let Some(legend) = &values.legend {
+            let default_attrs = Attrs::new().family(Family::Monospace);
+            let spans = legend_spans(legend);
+            self.legend_buffer.set_rich_text(
+                &mut self.font_system,
+                spans.iter().map(|(text, color)| match color {
+                    Some((r, g, b)) => (text.as_str(), default_attrs.color(Color::rgb(*r, *g, *b))),
+                    None => (text.as_str(), default_attrs),
+                }),
+                default_attrs,
+                Shaping::Advanced,
+            );
+            text_areas.push(TextArea {
+                buffer: &mut self.legend_buffer,
+                left: hud_left(mirror_width, 450.0, 300.0),
+                top: 100.0, // Y Position (top corner), right of the board
+                scale: 1.0,
+                bounds: TextBounds::default(),
+                default_color: Color::rgba(200, 200, 200, 255),
+                custom_glyphs: &[],
+            });
+        }
+
+        if let Some(badges) = &values.placement_badges {
+            while self.badge_buffers.len() < badges.len() {
+                let mut buffer = Buffer::new(&mut self.font_system, Metrics::new(16.0, 20.0));
+                buffer.set_size(&mut self.font_system, Some(40.0), Some(20.0));
+                self.badge_buffers.push(buffer);
+            }
+            for (buffer, badge) in self.badge_buffers.iter_mut().zip(badges) {
+                buffer.set_text(
+                    &mut self.font_system,
+                    &badge.placements.to_string(),
+                    Attrs::new().family(Family::Monospace),
+                    Shaping::Advanced,
+                );
+                // nowhere to go, the same red as a low placeable count
+                let color = match badge.placements {
+                    0 => Color::rgba(255, 60, 60, 255),
+                    _ => Color::rgba(200, 200, 200, 255),
+                };
+                text_areas.push(TextArea {
+                    buffer,
+                    left: badge.left,
+                    top: badge.top,
+                    scale: 1.0,
+                    bounds: TextBounds::default(),
+                    default_color: color,
+                    custom_glyphs: &[],
+                });
+            }
+        }
+
+        for area in text_areas.iter_mut() {
+            scale_text_area(area, self.scale);
+        }
+
         if let Err(e) = self.renderer.prepare(
             &self.device,
             &self.queue,
             &mut self.font_system,
             &mut self.atlas,
             &self.viewport,
-            vec![score_text, target_score_text, level_text],
+            text_areas,
             &mut self.swash_cache,
         ) {
             println!("❌ Error in renderer.prepare: {:?}", e);
+            // force the next frame to try again
+            self.last_rendered = None;
         }
+    }
+}
 
-        self.renderer
-            .render(&self.atlas, &self.viewport, render_pass)
-            .unwrap();
+// everything the HUD shows, the text is only re-shaped when one of these changes
+#[derive(Clone, PartialEq, Debug)]
+pub struct HudValues {
+    score: i32,
+    // the best game so far, this one included
+    best_score: i32,
+    target_score: i32,
+    level: u16,
+    // clears in a row, shown from the second one on
+    combo: u32,
+    pub fill_percent: Option<u8>,
+    // (placeable, visible) panel shapes
+    pub placeable: Option<(usize, usize)>,
+    pub goal: Option<String>,
+    // date of the daily challenge
+    pub daily: Option<String>,
+    pub tutorial: Option<TutorialPrompt>,
+    // where the points came from, shown at game over
+    pub score_breakdown: Option<ScoreLedger>,
+    // key that plays the level again, under the breakdown
+    pub retry_key: Option<String>,
+    pub leaderboard: Option<Vec<LeaderboardEntry>>,
+    pub strategy: Option<Vec<PanelShapeInfo>>,
+    pub menu: Option<MenuState>,
+    // over the dimmed board, see `Game::pause`
+    pub paused: bool,
+    // timings overlay, see `profiler::Profiler::overlay_text`
+    pub frame_stats: Option<String>,
+    pub legend: Option<Vec<LegendEntry>>,
+    pub placement_badges: Option<Vec<PlacementBadge>>,
+}
+
+impl HudValues {
+    pub fn new(game_stats: &GameStats) -> Self {
+        Self {
+            score: game_stats.current_score,
+            best_score: game_stats.best_score.max(game_stats.total_score),
+            target_score: game_stats.target_score,
+            level: game_stats.level,
+            combo: game_stats.combo,
+            fill_percent: None,
+            placeable: None,
+            goal: None,
+            daily: None,
+            tutorial: None,
+            score_breakdown: None,
+            retry_key: None,
+            leaderboard: None,
+            strategy: None,
+            menu: None,
+            paused: false,
+            frame_stats: None,
+            legend: None,
+            placement_badges: None,
+        }
     }
+}
+
+// a colour on the board and what it means
+#[derive(Clone, PartialEq, Debug)]
+pub struct LegendEntry {
+    pub color: (u8, u8, u8),
+    pub label: &'static str,
+}
+
+// number of valid placements, drawn under a panel shape
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlacementBadge {
+    pub placements: usize,
+    pub left: f32,
+    pub top: f32,
+}
+
+// tutorial text, placed right above what it points at
+#[derive(Clone, PartialEq, Debug)]
+pub struct TutorialPrompt {
+    pub text: &'static str,
+    pub left: f32,
+    pub top: f32,
+}
+
+fn breakdown_text(ledger: &ScoreLedger, retry_key: Option<&str>) -> String {
+    let mut text = String::from("Game over\n");
+    for (category, points) in ledger.breakdown() {
+        text.push_str(&format!("{:<15}{:>5}\n", category, points));
+    }
+    text.push_str(&format!("{:<15}{:>5}\n", "Total", ledger.total()));
+    if let Some(key) = retry_key {
+        text.push_str(&format!("\nPress {} to retry the level\n", key));
+    }
+    text
+}
+
+// a line per item and whether it's the selected one, which is also marked for when the colours
+// are hard to tell apart
+fn menu_lines(menu: &MenuState) -> Vec<(String, bool)> {
+    menu.items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let selected = i == menu.selected;
+            let marker = if selected { ">" } else { " " };
+            (format!("{} {}\n", marker, item.label), selected)
+        })
+        .collect()
+}
+
+// text and its colour, none for the default one
+type ColoredSpan = (String, Option<(u8, u8, u8)>);
+
+// a swatch in the entry's colour and the label after it, one entry per line
+fn legend_spans(entries: &[LegendEntry]) -> Vec<ColoredSpan> {
+    entries
+        .iter()
+        .flat_map(|entry| {
+            [
+                ("\u{25A0} ".to_string(), Some(entry.color)),
+                (format!("{}\n", entry.label), None),
+            ]
+        })
+        .collect()
+}
+
+fn leaderboard_text(entries: &[LeaderboardEntry]) -> String {
+    let mut text = String::from("Top scores\n");
+    for (place, entry) in entries.iter().enumerate() {
+        text.push_str(&format!(
+            "{:>2}. {:>6}  level {:<3} {}\n",
+            place + 1,
+            entry.score,
+            entry.level,
+            entry.date
+        ));
+    }
+    text
+}
+
+fn strategy_text(shapes: &[PanelShapeInfo]) -> String {
+    shapes
+        .iter()
+        .map(|shape| {
+            format!(
+                "Shape {}: {} cells, {} spots\n",
+                shape.index + 1,
+                shape.cells,
+                shape.placements
+            )
+        })
+        .collect()
+}
+
+fn scale_text_area(area: &mut TextArea, scale: f32) {
+    area.left *= scale;
+    area.top *= scale;
+    area.scale *= scale;
+}
+
+// left edge of a HUD text `width` wide that's at `left` in the regular layout
+fn hud_left(mirror_width: Option<f32>, left: f32, width: f32) -> f32 {
+    match mirror_width {
+        Some(window_width) => mirror_x(left, width, window_width),
+        None => left,
+    }
+}
+
+// returns true (and remembers the new values) when the text has to be re-shaped and re-prepared
+fn needs_reshape(last_rendered: &mut Option<HudValues>, values: &HudValues) -> bool {
+    if last_rendered.as_ref() == Some(values) {
+        return false;
+    }
+    *last_rendered = Some(values.clone());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::render::{text_scale, UserRenderConfig};
+
+    fn stats(current_score: i32) -> HudValues {
+        let stats = GameStats {
+            level: 1,
+            target_score: 10,
+            current_score,
+            total_score: current_score,
+            lines_cleared: 0,
+            cells_cleared: 0,
+            combo: 0,
+            best_score: 0,
+        };
+        HudValues::new(&stats)
+    }
+
+    #[test]
+    fn test_identical_stats_skip_reshape() {
+        let mut last_rendered = None;
+
+        assert!(needs_reshape(&mut last_rendered, &stats(3)));
+        assert!(!needs_reshape(&mut last_rendered, &stats(3)));
+        assert!(needs_reshape(&mut last_rendered, &stats(4)));
+        assert!(!needs_reshape(&mut last_rendered, &stats(4)));
+    }
+
+    #[test]
+    fn test_menu_marks_the_selected_item() {
+        let mut menu = MenuState::game_over(true);
+        menu.down();
+
+        let lines = menu_lines(&menu);
 
+        assert_eq!(
+            lines,
+            vec![
+                ("  Retry level\n".to_string(), false),
+                ("> Quit\n".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_doubled_window_doubles_text() {
+        let config = UserRenderConfig {
+            window_size: PhysicalSize::new(1200, 800),
+            ..UserRenderConfig::default()
+        };
+        let scale = text_scale(&config, PhysicalSize::new(2400, 1600));
+        assert_eq!(scale, 2.0);
+        // the shorter side decides
+        let wide = text_scale(&config, PhysicalSize::new(4800, 1600));
+        assert_eq!(wide, 2.0);
+
+        let mut font_system = FontSystem::new_with_locale_and_db(
+            "en-US".to_string(),
+            glyphon::fontdb::Database::new(),
+        );
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(30.0, 40.0));
+        let mut area = TextArea {
+            buffer: &mut buffer,
+            left: 800.0,
+            top: 100.0,
+            scale: 1.5,
+            bounds: TextBounds::default(),
+            default_color: Color::rgba(0, 255, 0, 255),
+            custom_glyphs: &[],
+        };
+        scale_text_area(&mut area, scale);
+
+        assert_eq!((area.left, area.top, area.scale), (1600.0, 200.0, 3.0));
+    }
 }
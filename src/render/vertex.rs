@@ -1,4 +1,6 @@
+use crate::game_entities::BaseShapeType;
 use crate::render::render::UserRenderConfig;
+use strum_macros::{EnumCount, EnumIter};
 use winit::dpi::PhysicalSize;
 
 #[derive(Copy, Clone, Debug)]
@@ -69,7 +71,7 @@ pub fn generate_panel_vertices(user_render_config: &UserRenderConfig) -> Vec<Ver
 pub fn generate_board_vertices(user_render_config: &UserRenderConfig) -> Vec<Vertex> {
     let mut vertices = Vec::new();
 
-    for row in 0..=user_render_config.board_size_cols {
+    for row in 0..=user_render_config.board_size_rows {
         for col in 0..=user_render_config.board_size_cols {
             let x =
                 col as f32 * user_render_config.cell_size_px + user_render_config.board_offset_x_px;
@@ -83,8 +85,70 @@ pub fn generate_board_vertices(user_render_config: &UserRenderConfig) -> Vec<Ver
 }
 
 #[repr(u32)] // Ensures it's represented as a u32 in memory
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, EnumCount, EnumIter)]
 pub enum CursorState {
     NotACursor = 0,
     Cursor = 1,
+    Highlight = 2,
+    // brighter highlights for clearing several lines at once
+    HighlightWarm = 3,
+    HighlightGold = 4,
+    // faint, for cells that aren't there yet
+    Ghost = 5,
+    // the cells missing from nearly full lines
+    Hint = 6,
+    // placed cells in the colour of the shape they came from, see `for_shape`
+    ShapeT1 = 7,
+    ShapeL1 = 8,
+    ShapeI1 = 9,
+    ShapeO = 10,
+    ShapeOO = 11,
+    ShapeS = 12,
+    ShapeZ = 13,
+    ShapeJ = 14,
+    ShapeI3 = 15,
+    // see-through black over everything while the game is paused
+    Dim = 16,
+    // the shadow of a shape that can be placed where it is, `Cursor` red when it can't
+    ValidPlacement = 17,
+}
+
+impl CursorState {
+    // the colour `textured.frag.wgsl` draws it with, for the legend
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            CursorState::NotACursor => (128, 77, 0),
+            CursorState::Cursor => (255, 0, 0),
+            CursorState::Highlight => (255, 255, 255),
+            CursorState::HighlightWarm => (255, 230, 128),
+            CursorState::HighlightGold => (255, 204, 0),
+            CursorState::Ghost => (66, 41, 5),
+            CursorState::Hint => (77, 71, 61),
+            CursorState::ShapeT1 => (163, 82, 194),
+            CursorState::ShapeL1 => (224, 122, 31),
+            CursorState::ShapeI1 => (61, 184, 214),
+            CursorState::ShapeO => (194, 102, 82),
+            CursorState::ShapeOO => (214, 194, 51),
+            CursorState::ShapeS => (92, 173, 71),
+            CursorState::ShapeZ => (214, 92, 153),
+            CursorState::ShapeJ => (71, 102, 204),
+            CursorState::ShapeI3 => (122, 204, 194),
+            CursorState::Dim => (0, 0, 0),
+            CursorState::ValidPlacement => (51, 217, 77),
+        }
+    }
+
+    pub fn for_shape(shape: BaseShapeType) -> Self {
+        match shape {
+            BaseShapeType::T1 => CursorState::ShapeT1,
+            BaseShapeType::L1 => CursorState::ShapeL1,
+            BaseShapeType::I1 => CursorState::ShapeI1,
+            BaseShapeType::O => CursorState::ShapeO,
+            BaseShapeType::OO => CursorState::ShapeOO,
+            BaseShapeType::S => CursorState::ShapeS,
+            BaseShapeType::Z => CursorState::ShapeZ,
+            BaseShapeType::J => CursorState::ShapeJ,
+            BaseShapeType::I3 => CursorState::ShapeI3,
+        }
+    }
 }
@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use crate::game_config::GameConfig;
+use crate::game_entities::{Board, Game};
+use crate::settings::{settings_path, SettingsFile};
+
+// called with the game state every time an autosave is due
+pub type SaveHook = Box<dyn Fn(&Game)>;
+
+// next to the settings file
+pub fn recovery_path() -> PathBuf {
+    settings_path().with_file_name("recovery.txt")
+}
+
+// level, scores and board in the settings file format. The panel isn't saved, a new one is dealt
+// on load.
+pub fn save_game(game: &Game) -> SettingsFile {
+    let mut save = SettingsFile::default();
+    save.set("level", game.stats.level.to_string());
+    save.set("target_score", game.stats.target_score.to_string());
+    save.set("current_score", game.stats.current_score.to_string());
+    save.set("total_score", game.stats.total_score.to_string());
+    save.set("placements", game.placements.to_string());
+    // one line per row doesn't fit a `key=value` line, rows are separated by `|` instead
+    // lines still being cleared are saved as gone
+    let mut board = game.board.clone();
+    if let Some(clearing) = &game.clearing {
+        board.clear_lines(&clearing.lines);
+    }
+    save.set("board", board.to_ascii().trim_end().replace('\n', "|"));
+    save
+}
+
+pub fn load_game(save: &SettingsFile, config: GameConfig) -> Option<Game> {
+    let board = Board::from_ascii(&save.get("board")?.replace('|', "\n"))?;
+    let level = save.get("level")?.parse().ok()?;
+    let total_score = save.get("total_score")?.parse().ok()?;
+
+    let mut game = Game::new_level_with_config(config, board.cols, level, total_score);
+    game.stats.target_score = save.get("target_score")?.parse().ok()?;
+    game.stats.current_score = save.get("current_score")?.parse().ok()?;
+    game.placements = save.get("placements")?.parse().ok()?;
+    game.board = board;
+    Some(game)
+}
+
+// writes the recovery file, a failed autosave shouldn't stop the game
+pub fn write_recovery(game: &Game) {
+    let path = recovery_path();
+    match save_game(game).write(&path) {
+        Ok(()) => println!("Autosaved to {:?}", path),
+        Err(e) => println!("Could not autosave to {:?}: {}", path, e),
+    }
+}
+
+// once the run is over, or the game was closed normally
+pub fn remove_recovery(path: &Path) {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => println!("Could not remove the autosave {:?}: {}", path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_entities::Cell;
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let mut game = Game::new_level(5, 3, 40);
+        game.board = Board::new(5);
+        game.board.set_cell(1, 2, Cell::Filled);
+        game.board.set_cell(4, 0, Cell::Filled);
+        game.stats.current_score = 7;
+        game.placements = 12;
+
+        let text = save_game(&game).to_text();
+        let loaded = load_game(&SettingsFile::parse(&text), GameConfig::default()).unwrap();
+
+        assert_eq!(loaded.board.to_ascii(), game.board.to_ascii());
+        assert_eq!(loaded.stats.level, 3);
+        assert_eq!(loaded.stats.target_score, game.stats.target_score);
+        assert_eq!(loaded.stats.current_score, 7);
+        assert_eq!(loaded.stats.total_score, 40);
+        assert_eq!(loaded.placements, 12);
+    }
+
+    #[test]
+    fn test_broken_save_is_not_loaded() {
+        let save = SettingsFile::parse("level=3\nboard=#.|.#\n");
+
+        assert!(load_game(&save, GameConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_removing_the_recovery_file() {
+        let path = crate::settings::temp_settings_path("removed_recovery");
+        save_game(&Game::new_level(5, 1, 0)).write(&path).unwrap();
+
+        remove_recovery(&path);
+        assert!(!path.exists());
+        // already gone, nothing to complain about
+        remove_recovery(&path);
+    }
+}
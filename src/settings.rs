@@ -0,0 +1,295 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::game_entities::BaseShapeType;
+use crate::key_bindings::KeyBindings;
+use crate::leaderboard::Leaderboard;
+use crate::sound;
+
+// bumped whenever a setting is renamed or changes format, see `migrate`
+pub const SETTINGS_VERSION: u32 = 2;
+
+// Flat `key=value` settings file shared by everything that's persisted between sessions.
+// Lines that can't be parsed are skipped, so a partially broken file still loads.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SettingsFile {
+    entries: Vec<(String, String)>,
+}
+
+impl SettingsFile {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Self { entries }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// everything persisted between sessions, read from and written to one `SettingsFile`. Whatever
+// is missing keeps its default, keys that aren't known here (say from a newer version) are
+// written back untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub version: u32,
+    pub key_bindings: KeyBindings,
+    pub leaderboard: Leaderboard,
+    // one track per level, see `sound::music_for_level`
+    pub music: Vec<PathBuf>,
+    // clip per shape type, see `SoundPack::load_placement_clips`
+    pub placement_sounds: Vec<(BaseShapeType, PathBuf)>,
+    // output device by name, see `SoundSystem::list_devices`. None plays on the default one.
+    pub audio_device: Option<String>,
+    // see `UserRenderConfig::reduce_motion`
+    pub reduce_motion: bool,
+    // the first level tutorial was played through once, it isn't shown again
+    pub tutorial_done: bool,
+    // the version an older file was upgraded from while loading, it's worth writing back then
+    pub migrated_from: Option<u32>,
+    file: SettingsFile,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::load(SettingsFile::default())
+    }
+}
+
+impl Settings {
+    // a missing or unreadable file gives the defaults
+    pub fn read(path: &Path) -> Self {
+        match SettingsFile::read(path) {
+            Ok(file) => Self::load(file),
+            Err(e) => {
+                println!("No settings loaded from {:?}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn write(&mut self, path: &Path) -> io::Result<()> {
+        self.file.set("version", self.version.to_string());
+        self.key_bindings.save(&mut self.file);
+        self.leaderboard.save(&mut self.file);
+        match &self.audio_device {
+            Some(device) => self.file.set("audio.device", device.clone()),
+            None => {
+                self.file.remove("audio.device");
+            }
+        }
+        self.file.set(
+            "accessibility.reduce_motion",
+            self.reduce_motion.to_string(),
+        );
+        self.file
+            .set("tutorial.done", self.tutorial_done.to_string());
+        self.file.write(path)
+    }
+
+    // files from before versioning have no `version` key, they count as version 1. An empty
+    // file is a fresh one, there's nothing in it to migrate.
+    pub fn load(mut file: SettingsFile) -> Self {
+        let version = if file.is_empty() {
+            SETTINGS_VERSION
+        } else {
+            file.get("version")
+                .and_then(|version| version.parse().ok())
+                .unwrap_or(1)
+        };
+        let migrated_from = (version < SETTINGS_VERSION).then(|| {
+            println!("Upgrading settings from version {}", version);
+            migrate(version, &mut file);
+            version
+        });
+        Self {
+            // a newer file is read as far as it's understood and keeps its version
+            version: version.max(SETTINGS_VERSION),
+            key_bindings: KeyBindings::load(&file),
+            leaderboard: Leaderboard::load(&file),
+            music: sound::music_tracks(&file),
+            placement_sounds: sound::placement_sounds(&file),
+            audio_device: file.get("audio.device").map(str::to_string),
+            reduce_motion: file.get("accessibility.reduce_motion") == Some("true"),
+            tutorial_done: file.get("tutorial.done") == Some("true"),
+            migrated_from,
+            file,
+        }
+    }
+}
+
+// brings the keys of a `from` file up to `SETTINGS_VERSION`, one version at a time
+fn migrate(from: u32, file: &mut SettingsFile) {
+    for version in from..SETTINGS_VERSION {
+        // 1 -> 2: the music tracks moved under a `music.` key like the other groups
+        if version == 1 {
+            if let Some(tracks) = file.remove("music") {
+                file.set("music.tracks", tracks);
+            }
+        }
+    }
+    file.set("version", SETTINGS_VERSION.to_string());
+}
+
+// $XDG_CONFIG_HOME/flip_flop/settings.txt, falling back to ~/.config and then the working dir
+pub fn settings_path() -> PathBuf {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    config_dir.join("flip_flop").join("settings.txt")
+}
+
+#[cfg(test)]
+pub fn temp_settings_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("flip_flop_test_{}", std::process::id()))
+        .join(format!("{}.txt", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_bindings::Action;
+    use winit::keyboard::KeyCode;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let path = temp_settings_path("settings_round_trip");
+        let mut settings = SettingsFile::default();
+        settings.set("a", "1".to_string());
+        settings.set("b", "two".to_string());
+        settings.set("a", "3".to_string());
+
+        settings.write(&path).unwrap();
+        let loaded = SettingsFile::read(&path).unwrap();
+
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.get("a"), Some("3"));
+        assert_eq!(loaded.get("b"), Some("two"));
+        assert_eq!(loaded.get("c"), None);
+    }
+
+    #[test]
+    fn test_broken_lines_are_skipped() {
+        let settings = SettingsFile::parse("# comment\nnot a setting\n key = value \n");
+
+        assert_eq!(settings.get("key"), Some("value"));
+        assert_eq!(settings.to_text(), "key=value\n");
+    }
+
+    #[test]
+    fn test_v1_file_loads_into_v2_settings() {
+        let v1 = SettingsFile::parse(
+            "key.rotate=KeyT\nmusic=a.wav, b.wav\nleaderboard.1=40,2025-01-02,3\nunknown=kept\n",
+        );
+
+        let mut settings = Settings::load(v1);
+
+        assert_eq!(settings.version, 2);
+        assert_eq!(settings.migrated_from, Some(1));
+        assert_eq!(
+            settings.music,
+            vec![PathBuf::from("a.wav"), PathBuf::from("b.wav")]
+        );
+        assert_eq!(settings.key_bindings.key_for(Action::Rotate), KeyCode::KeyT);
+        assert_eq!(settings.leaderboard.entries()[0].score, 40);
+        // missing from v1, so the default
+        assert_eq!(
+            settings.key_bindings.key_for(Action::Restart),
+            KeyBindings::default().key_for(Action::Restart)
+        );
+
+        let path = temp_settings_path("settings_upgrade");
+        settings.audio_device = Some("USB Headset".to_string());
+        settings.write(&path).unwrap();
+        let written = SettingsFile::read(&path).unwrap();
+        assert_eq!(written.get("version"), Some("2"));
+        assert_eq!(written.get("music"), None);
+        assert_eq!(written.get("music.tracks"), Some("a.wav, b.wav"));
+        assert_eq!(written.get("unknown"), Some("kept"));
+        assert_eq!(written.get("audio.device"), Some("USB Headset"));
+        assert_eq!(Settings::load(written).migrated_from, None);
+
+        // a newer file is read as far as it's understood and keeps its version
+        let newer = Settings::load(SettingsFile::parse("version=7\nmusic.tracks=c.wav\n"));
+        assert_eq!(newer.version, 7);
+        assert_eq!(newer.music, vec![PathBuf::from("c.wav")]);
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_file_gives_the_defaults() {
+        let missing = Settings::read(&temp_settings_path("settings_missing"));
+        assert_eq!(missing, Settings::default());
+        assert_eq!(missing.migrated_from, None);
+
+        let path = temp_settings_path("settings_corrupt");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, [0xff, 0xfe, 0x00, 0x9f]).unwrap();
+        assert_eq!(Settings::read(&path), Settings::default());
+
+        let garbage = Settings::load(SettingsFile::parse("}{\nversion=banana\n"));
+        assert_eq!(garbage.key_bindings, KeyBindings::default());
+        assert!(garbage.leaderboard.entries().is_empty());
+        assert!(garbage.music.is_empty());
+        assert_eq!(garbage.audio_device, None);
+        assert!(!garbage.reduce_motion);
+        assert!(!garbage.tutorial_done);
+    }
+
+    #[test]
+    fn test_finished_tutorial_is_remembered() {
+        let path = temp_settings_path("settings_tutorial");
+        let mut settings = Settings::default();
+        assert!(!settings.tutorial_done);
+
+        settings.tutorial_done = true;
+        settings.write(&path).unwrap();
+
+        assert!(Settings::read(&path).tutorial_done);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::game_entities::{BaseShapeType, Game, ShapeRot, ShapeState, ShapeType};
+use crate::space_converters::CellCoord;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use strum::IntoEnumIterator;
+
+// every board cell where the top-left of the shape can be placed
+pub fn valid_placements(game: &Game, shape: &ShapeType) -> Vec<CellCoord> {
+    board_cells(game)
+        .filter(|cell| game.is_valid_placement(shape, cell))
+        .collect()
+}
+
+pub fn has_valid_placement(game: &Game, shape: &ShapeType) -> bool {
+    board_cells(game).any(|cell| game.is_valid_placement(shape, &cell))
+}
+
+// every base type in every rotation, mirrored and not
+pub fn all_shape_types() -> Vec<ShapeType> {
+    BaseShapeType::iter()
+        .flat_map(|base| {
+            ShapeRot::iter().flat_map(move |rot| {
+                [false, true]
+                    .into_iter()
+                    .map(move |mirror| ShapeType::new(base, mirror, rot))
+            })
+        })
+        .collect()
+}
+
+// random shape (any base type unless one is forced, rotation and mirror) that has at least one
+// valid placement
+pub fn any_placeable_shape(game: &Game, rng: &mut impl Rng) -> Option<ShapeType> {
+    let mut candidates = all_shape_types();
+    if let Some(forced) = game.config.forced_shape {
+        candidates.retain(|shape| shape.base_shape_type() == forced);
+    }
+    candidates.shuffle(rng);
+    candidates
+        .into_iter()
+        .find(|shape| has_valid_placement(game, shape))
+}
+
+// (placeable, visible) panel shapes, a quick read on how tight the board is. A shape that fits
+// once the player turns it counts, like in `is_game_over`.
+pub fn placeable_shape_count(game: &Game) -> (usize, usize) {
+    let orientations = Orientations::for_game(game);
+    game.panel
+        .shape_choice
+        .iter()
+        .filter(|shape| shape.state == ShapeState::VISIBLE)
+        .fold((0, 0), |(placeable, visible), shape| {
+            let fits = orientations
+                .of(&shape.kind)
+                .iter()
+                .any(|orientation| has_valid_placement(game, orientation));
+            (placeable + usize::from(fits), visible + 1)
+        })
+}
+
+// which orientations of a panel shape are tried when looking for a place it fits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientations {
+    AsDealt,
+    // what the player can turn it into, see `GameConfig::rotation`
+    Rotations,
+    // every rotation, mirrored and not
+    All,
+}
+
+impl Orientations {
+    // what the game over check tries, the HUD counts placements the same way
+    pub fn for_game(game: &Game) -> Self {
+        if game.config.game_over_checks_orientations {
+            Orientations::All
+        } else if game.config.rotation {
+            Orientations::Rotations
+        } else {
+            Orientations::AsDealt
+        }
+    }
+
+    // orientations covering the same cells are only tried once, e.g. the 4 turns of a square
+    fn of(self, shape: &ShapeType) -> Vec<ShapeType> {
+        let mut orientations = match self {
+            Orientations::AsDealt => vec![*shape],
+            Orientations::Rotations => shape.all_rotations(),
+            Orientations::All => shape.all_orientations(),
+        };
+        let mut seen = vec![];
+        orientations.retain(|orientation| {
+            let mut cells = orientation.cells();
+            cells.sort();
+            let new = !seen.contains(&cells);
+            seen.push(cells);
+            new
+        });
+        orientations
+    }
+}
+
+// some visible panel shape and where it fits in one of its `orientations`, as (panel index,
+// orientation, cell)
+pub fn find_any_valid_placement(
+    game: &Game,
+    orientations: Orientations,
+) -> Option<(usize, ShapeType, CellCoord)> {
+    game.panel
+        .shape_choice
+        .iter()
+        .enumerate()
+        .filter(|(_, shape)| shape.state == ShapeState::VISIBLE)
+        .flat_map(|(index, shape)| {
+            orientations
+                .of(&shape.kind)
+                .into_iter()
+                .map(move |orientation| (index, orientation))
+        })
+        .find_map(|(index, orientation)| {
+            board_cells(game)
+                .find(|cell| game.is_valid_placement(&orientation, cell))
+                .map(|cell| (index, orientation, cell))
+        })
+}
+
+// none of the visible panel shapes fits anywhere, not even turned when the player can turn it.
+// A panel without visible shapes is about to be dealt again, that's not a game over.
+pub fn is_game_over(game: &Game) -> bool {
+    let any_visible = game
+        .panel
+        .shape_choice
+        .iter()
+        .any(|shape| shape.state == ShapeState::VISIBLE);
+    any_visible && find_any_valid_placement(game, Orientations::for_game(game)).is_none()
+}
+
+// placement of a visible panel shape that completes the most lines, ties go to the first one
+// found. As (panel index, shape as placed on the board, top-left board cell).
+pub fn suggest_best_move(game: &Game) -> Option<(usize, ShapeType, CellCoord)> {
+    let mut best: Option<(usize, (usize, ShapeType, CellCoord))> = None;
+    for (index, shape) in game.panel.shape_choice.iter().enumerate() {
+        if shape.state != ShapeState::VISIBLE {
+            continue;
+        }
+        let board_shape = game.board_shape(&shape.kind);
+        for cell in valid_placements(game, &board_shape) {
+            let completed = game.lines_completed_by(&board_shape, &cell);
+            let lines = completed.rows.len() + completed.cols.len();
+            if best.as_ref().is_none_or(|(most, _)| lines > *most) {
+                best = Some((lines, (index, board_shape, cell)));
+            }
+        }
+    }
+    best.map(|(_, placement)| placement)
+}
+
+// what the strategy HUD shows for a visible panel shape
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanelShapeInfo {
+    pub index: usize,
+    pub cells: usize,
+    pub placements: usize,
+}
+
+// `placements` counts the spots in every orientation the player can turn the shape into
+pub fn panel_shape_info(game: &Game) -> Vec<PanelShapeInfo> {
+    let orientations = Orientations::for_game(game);
+    game.panel
+        .shape_choice
+        .iter()
+        .enumerate()
+        .filter(|(_, shape)| shape.state == ShapeState::VISIBLE)
+        .map(|(index, shape)| PanelShapeInfo {
+            index,
+            cells: shape.kind.cells().len(),
+            placements: orientations
+                .of(&shape.kind)
+                .iter()
+                .map(|orientation| {
+                    board_cells(game)
+                        .filter(|cell| game.is_valid_placement(orientation, cell))
+                        .count()
+                })
+                .sum(),
+        })
+        .collect()
+}
+
+fn board_cells(game: &Game) -> impl Iterator<Item = CellCoord> {
+    let (cols, rows) = (game.board.cols as i16, game.board.rows as i16);
+    (0..rows).flat_map(move |row| (0..cols).map(move |col| CellCoord::new(col, row)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_entities::{BaseShapeType, Board, Cell, Panel, Shape, ShapeRot};
+
+    #[test]
+    fn test_valid_placements_on_almost_full_board() {
+        let mut game = Game::new_level(3, 1, 0);
+        game.board = Board::new(3);
+        for row in 0..3 {
+            for col in 0..3 {
+                game.board.set_cell(col, row, Cell::Filled);
+            }
+        }
+        game.board.set_cell(1, 2, Cell::Empty);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+
+        assert_eq!(valid_placements(&game, &single), vec![CellCoord::new(1, 2)]);
+        assert!(!has_valid_placement(&game, &square));
+        let placeable = any_placeable_shape(&game, &mut game.rng.clone()).unwrap();
+        assert_eq!(
+            valid_placements(&game, &placeable),
+            vec![CellCoord::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_best_move_completes_the_most_lines() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::from_ascii("..##\n..##\n##..\n###.\n").unwrap();
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![single, square]));
+
+        // the square finishes the two top rows, the single cell one line at most
+        assert_eq!(
+            suggest_best_move(&game),
+            Some((1, square, CellCoord::new(0, 0)))
+        );
+
+        // its third col and last row are a tie, the first one found wins
+        game.panel.shape_choice[1].set_state(ShapeState::PLACED);
+        assert_eq!(
+            suggest_best_move(&game),
+            Some((0, single, CellCoord::new(2, 2)))
+        );
+    }
+
+    #[test]
+    fn test_panel_shape_info_of_visible_shapes() {
+        let mut game = Game::new_level(3, 1, 0);
+        game.board = Board::new(3);
+        game.board.set_cell(0, 0, Cell::Filled);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![
+            ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::I1, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::OO, false, ShapeRot::No),
+        ]));
+        game.panel.shape_choice[1].set_state(ShapeState::PLACED);
+
+        let info = panel_shape_info(&game);
+
+        assert_eq!(
+            info,
+            vec![
+                PanelShapeInfo {
+                    index: 0,
+                    cells: 1,
+                    placements: 8,
+                },
+                PanelShapeInfo {
+                    index: 2,
+                    cells: 4,
+                    placements: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shape_fitting_only_after_rotation_is_not_game_over() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::new(4);
+        // only the top row is free, the standing line doesn't fit
+        for row in 1..4 {
+            for col in 0..4 {
+                game.board.set_cell(col, row, Cell::Filled);
+            }
+        }
+        let standing = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![standing]));
+        game.config.rotation = false;
+
+        assert!(is_game_over(&game));
+
+        game.config.game_over_checks_orientations = true;
+        assert!(!is_game_over(&game));
+        let (index, orientation, cell) =
+            find_any_valid_placement(&game, Orientations::All).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(orientation.horizontal_cell_size(), 4);
+        assert_eq!(cell, CellCoord::new(0, 0));
+    }
+
+    #[test]
+    fn test_rotatable_shape_is_not_game_over() {
+        let mut game = Game::new_level(3, 1, 0);
+        // the only free cells are an L, a J would have to be mirrored to fill them
+        game.board = Board::from_ascii(".##\n.##\n..#\n").unwrap();
+        let j = ShapeType::new(BaseShapeType::J, false, ShapeRot::No);
+        let l = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        game.config.rotation = true;
+
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![j]));
+        assert!(is_game_over(&game));
+
+        // one turned away from it can be turned back
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![l.rotated_cw()]));
+        assert!(!is_game_over(&game));
+        game.config.rotation = false;
+        assert!(is_game_over(&game));
+    }
+
+    #[test]
+    fn test_placeable_shape_count() {
+        let mut game = Game::new_level(3, 1, 0);
+        game.board = Board::new(3);
+        // a free column and the cell at its bottom left
+        for row in 0..3 {
+            game.board.set_cell(0, row, Cell::Filled);
+            game.board.set_cell(1, row, Cell::Filled);
+        }
+        game.board.set_cell(1, 2, Cell::Empty);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![
+            ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::OO, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::I1, false, ShapeRot::No),
+            ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+        ]));
+        game.panel.shape_choice[3].set_state(ShapeState::PLACED);
+
+        game.config.rotation = false;
+        assert_eq!(placeable_shape_count(&game), (1, 3));
+
+        // the line is too long for a 3x3 board even then
+        game.board = Board::new(3);
+        assert_eq!(placeable_shape_count(&game), (2, 3));
+    }
+
+    #[test]
+    fn test_shapes_that_fit_turned_count_when_rotation_is_on() {
+        let mut game = Game::new_level(3, 1, 0);
+        // only the top row is free, the standing line of 3 fits once it's turned
+        game.board = Board::from_ascii("...\n###\n###\n").unwrap();
+        let standing = ShapeType::new(BaseShapeType::I3, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![standing]));
+
+        game.config.rotation = false;
+        assert_eq!(placeable_shape_count(&game), (0, 1));
+        assert_eq!(panel_shape_info(&game)[0].placements, 0);
+
+        game.config.rotation = true;
+        assert!(!is_game_over(&game));
+        assert_eq!(placeable_shape_count(&game), (1, 1));
+        // turning it by 180 covers the same cells, that's not another placement
+        assert_eq!(panel_shape_info(&game)[0].placements, 1);
+    }
+}
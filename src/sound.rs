@@ -1,5 +1,18 @@
-use std::io::Cursor;
+use crate::events::Event;
+use crate::game_entities::BaseShapeType;
+use crate::settings::SettingsFile;
+use rodio::cpal::traits::HostTrait;
+use rodio::source::{SineWave, TakeDuration};
+use rodio::{DeviceTrait, Source};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use strum::IntoEnumIterator;
 
+const VOLUME: f32 = 0.5;
 const BOUNCE_BYTES: &[u8] = include_bytes!("../res/sounds/4362__noisecollector__pongblipa-4.wav");
 
 pub struct SoundSystem {
@@ -9,13 +22,40 @@ pub struct SoundSystem {
     stream_handle: rodio::OutputStreamHandle,
     sink: rodio::Sink,
     spatial_sink: rodio::SpatialSink,
+    music: Music,
+    // silence everything while the window is in the background
+    pub mute_on_focus_loss: bool,
 }
 
 impl SoundSystem {
     pub fn new() -> Self {
         let (stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
+        Self::with_stream(stream, stream_handle)
+    }
+
+    // names of the output devices there are right now, for picking one in the settings
+    pub fn list_devices() -> Vec<String> {
+        output_devices().into_iter().map(|(name, _)| name).collect()
+    }
+
+    // plays on the output device called `name`, or on the default one when it's gone or fails
+    pub fn with_device(name: &str) -> Self {
+        let Some(device) = pick_device(output_devices(), name) else {
+            println!("Audio device {:?} not found, using the default one", name);
+            return Self::new();
+        };
+        match rodio::OutputStream::try_from_device(&device) {
+            Ok((stream, stream_handle)) => Self::with_stream(stream, stream_handle),
+            Err(e) => {
+                println!("Could not open audio device {:?}: {}", name, e);
+                Self::new()
+            }
+        }
+    }
+
+    fn with_stream(stream: rodio::OutputStream, stream_handle: rodio::OutputStreamHandle) -> Self {
         let sink = rodio::Sink::try_new(&stream_handle).unwrap();
-        sink.set_volume(0.5);
+        sink.set_volume(VOLUME);
 
         let spatial_sink = rodio::SpatialSink::try_new(
             &stream_handle,
@@ -25,14 +65,33 @@ impl SoundSystem {
         )
         .unwrap();
 
+        let music = Music::new(Some(stream_handle.clone()));
+
         Self {
             stream,
             stream_handle,
             sink,
             spatial_sink,
+            music,
+            mute_on_focus_loss: true,
         }
     }
 
+    pub fn set_muted(&mut self, muted: bool) {
+        let volume = if muted { 0.0 } else { VOLUME };
+        self.sink.set_volume(volume);
+        self.spatial_sink.set_volume(volume);
+        self.music.set_muted(muted);
+    }
+
+    pub fn play_music(&mut self, path: &Path) {
+        self.music.play(path);
+    }
+
+    pub fn stop_music(&mut self) {
+        self.music.stop();
+    }
+
     #[inline]
     pub fn queue<S>(&self, sound: S)
     where
@@ -43,6 +102,21 @@ impl SoundSystem {
         self.sink.append(sound);
     }
 
+    // `rate` speeds the clip up (and raises its pitch) or slows it down. One that can't be played
+    // back, zero or not a number, plays the clip as it is.
+    pub fn queue_with_rate<S>(&self, sound: S, rate: f32)
+    where
+        S: rodio::Source + Send + 'static,
+        S::Item: rodio::Sample,
+        S::Item: Send,
+    {
+        if rate.is_finite() && rate > 0.0 && rate != 1.0 {
+            self.sink.append(sound.speed(rate));
+        } else {
+            self.sink.append(sound);
+        }
+    }
+
     #[allow(dead_code)]
     #[inline]
     pub fn queue_spatial<S>(&self, sound: S, position: [f32; 3])
@@ -55,18 +129,373 @@ impl SoundSystem {
     }
 }
 
+// looping background track on its own sink, so it can be swapped without cutting off effects
+struct Music {
+    // none when there's no audio output, the track is still tracked then
+    stream_handle: Option<rodio::OutputStreamHandle>,
+    sink: Option<rodio::Sink>,
+    track: Option<PathBuf>,
+    // a track started while muted only begins playing once unmuted
+    muted: bool,
+}
+
+impl Music {
+    fn new(stream_handle: Option<rodio::OutputStreamHandle>) -> Self {
+        Self {
+            stream_handle,
+            sink: None,
+            track: None,
+            muted: false,
+        }
+    }
+
+    // the same track keeps playing from where it is
+    fn play(&mut self, path: &Path) {
+        if self.track.as_deref() == Some(path) {
+            return;
+        }
+        self.stop();
+        self.track = Some(path.to_path_buf());
+        if !self.muted {
+            self.start();
+        }
+    }
+
+    fn stop(&mut self) {
+        // dropping the sink stops it
+        self.sink = None;
+        self.track = None;
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        match &self.sink {
+            Some(sink) if muted => sink.pause(),
+            Some(sink) => sink.play(),
+            None if !muted => self.start(),
+            None => {}
+        }
+    }
+
+    #[cfg(test)]
+    fn is_playing(&self) -> bool {
+        self.sink.as_ref().is_some_and(|sink| !sink.is_paused())
+    }
+
+    // failing to load the track only means no music
+    fn start(&mut self) {
+        let (Some(track), Some(stream_handle)) = (&self.track, &self.stream_handle) else {
+            return;
+        };
+        let source = File::open(track)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                rodio::Decoder::new_looped(BufReader::new(file)).map_err(|e| e.to_string())
+            });
+        let sink = rodio::Sink::try_new(stream_handle).map_err(|e| e.to_string());
+        match (source, sink) {
+            (Ok(source), Ok(sink)) => {
+                sink.set_volume(VOLUME);
+                sink.append(source);
+                self.sink = Some(sink);
+            }
+            (Err(e), _) | (_, Err(e)) => println!("Could not play music {:?}: {}", track, e),
+        }
+    }
+}
+
+// output devices there are right now, by name
+fn output_devices() -> Vec<(String, rodio::Device)> {
+    match rodio::cpal::default_host().output_devices() {
+        Ok(devices) => devices
+            .filter_map(|device| device.name().ok().map(|name| (name, device)))
+            .collect(),
+        Err(e) => {
+            println!("Could not list audio devices: {}", e);
+            vec![]
+        }
+    }
+}
+
+// the device to open out of the `available` ones, none means the default device
+fn pick_device<D>(available: Vec<(String, D)>, requested: &str) -> Option<D> {
+    available
+        .into_iter()
+        .find(|(name, _)| name == requested)
+        .map(|(_, device)| device)
+}
+
+// `music.tracks` setting: comma separated tracks, one per level, starting over after the last one
+pub fn music_tracks(settings: &SettingsFile) -> Vec<PathBuf> {
+    settings
+        .get("music.tracks")
+        .map(|tracks| {
+            tracks
+                .split(',')
+                .map(str::trim)
+                .filter(|track| !track.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// `sound.placed.<shape>` settings, e.g. `sound.placed.T1=click.wav`: the clip a placed shape
+// of that type plays instead of the bounce
+pub fn placement_sounds(settings: &SettingsFile) -> Vec<(BaseShapeType, PathBuf)> {
+    BaseShapeType::iter()
+        .filter_map(|shape| {
+            settings
+                .get(&format!("sound.placed.{:?}", shape))
+                .filter(|path| !path.is_empty())
+                .map(|path| (shape, PathBuf::from(path)))
+        })
+        .collect()
+}
+
+pub fn music_for_level(tracks: &[PathBuf], level: u16) -> Option<&PathBuf> {
+    if tracks.is_empty() {
+        return None;
+    }
+    tracks.get((level as usize).saturating_sub(1) % tracks.len())
+}
+
+// no clips for selection yet, a short blip is generated instead
+const SELECT_HZ: f32 = 880.0;
+const DESELECT_HZ: f32 = 440.0;
+const BLIP_DURATION: Duration = Duration::from_millis(60);
+const CLEAR_HZ: f32 = 660.0;
+const CLEAR_DURATION: Duration = Duration::from_millis(120);
+// every extra line at once plays the clear a whole tone higher, up to an octave
+const CLEAR_STEP_SEMITONES: f32 = 2.0;
+const CLEAR_MAX_RATE: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sound {
+    // a shape of this type was placed
+    Placed(BaseShapeType),
+    Select,
+    Deselect,
+    // lines cleared at once
+    Clear(usize),
+}
+
+// bytes of a clip, shared by every time it's played
+type Clip = Arc<[u8]>;
+
 pub struct SoundPack {
-    bounce: Cursor<&'static [u8]>,
+    bounce: Clip,
+    // clips played instead of the bounce when a shape of that type is placed
+    placement_clips: HashMap<BaseShapeType, Clip>,
+    // select/deselect blips, placement always plays
+    pub selection_sounds: bool,
+    // the more lines a clear takes, the higher it sounds. Off plays every clear the same.
+    pub clear_pitch_variation: bool,
 }
 
 impl SoundPack {
     pub fn new() -> Self {
         Self {
-            bounce: Cursor::new(BOUNCE_BYTES),
+            bounce: Clip::from(BOUNCE_BYTES),
+            placement_clips: HashMap::new(),
+            selection_sounds: true,
+            clear_pitch_variation: true,
+        }
+    }
+
+    pub fn sound_for(&self, event: &Event) -> Option<Sound> {
+        match event {
+            Event::SelectedShapePlaced(shape, _) => Some(Sound::Placed(shape.base_shape_type())),
+            Event::ShapeSelected(_, _) if self.selection_sounds => Some(Sound::Select),
+            Event::ShapeDeselected if self.selection_sounds => Some(Sound::Deselect),
+            Event::LinesCleared(lines) => Some(Sound::Clear(*lines)),
+            _ => None,
+        }
+    }
+
+    // reads the clips of `placement_sounds`, a shape whose file can't be read keeps the bounce
+    pub fn load_placement_clips(&mut self, sounds: &[(BaseShapeType, PathBuf)]) {
+        for (shape, path) in sounds {
+            match std::fs::read(path) {
+                Ok(bytes) => {
+                    self.placement_clips.insert(*shape, Clip::from(bytes));
+                }
+                Err(e) => println!(
+                    "Could not load the {:?} placement sound {:?}: {}",
+                    shape, path, e
+                ),
+            }
+        }
+    }
+
+    pub fn bounce(&self) -> rodio::Decoder<Cursor<Clip>> {
+        rodio::Decoder::new(Cursor::new(self.bounce.clone())).unwrap()
+    }
+
+    // the shape's own clip, the bounce when it has none or it can't be decoded
+    pub fn placement(&self, shape: BaseShapeType) -> rodio::Decoder<Cursor<Clip>> {
+        match rodio::Decoder::new(Cursor::new(self.placement_clip(shape).clone())) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                println!("Could not decode the {:?} placement sound: {}", shape, e);
+                self.bounce()
+            }
+        }
+    }
+
+    fn placement_clip(&self, shape: BaseShapeType) -> &Clip {
+        self.placement_clips.get(&shape).unwrap_or(&self.bounce)
+    }
+
+    pub fn select(&self) -> TakeDuration<SineWave> {
+        SineWave::new(SELECT_HZ).take_duration(BLIP_DURATION)
+    }
+
+    pub fn deselect(&self) -> TakeDuration<SineWave> {
+        SineWave::new(DESELECT_HZ).take_duration(BLIP_DURATION)
+    }
+
+    pub fn clear(&self) -> TakeDuration<SineWave> {
+        SineWave::new(CLEAR_HZ).take_duration(CLEAR_DURATION)
+    }
+
+    // playback rate of the clear sound, see `SoundSystem::queue_with_rate`
+    pub fn clear_rate(&self, lines: usize) -> f32 {
+        if !self.clear_pitch_variation {
+            return 1.0;
         }
+        let semitones = lines.saturating_sub(1) as f32 * CLEAR_STEP_SEMITONES;
+        2f32.powf(semitones / 12.0).min(CLEAR_MAX_RATE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_entities::{ShapeRot, ShapeType};
+    use crate::settings::temp_settings_path;
+    use crate::space_converters::{CellCoord, OffsetXY};
+
+    #[test]
+    fn test_event_to_sound() {
+        let mut pack = SoundPack::new();
+        let selected = Event::ShapeSelected(0, OffsetXY(0, 0));
+        let picked_up = Event::PlacedShapePickedUp(CellCoord::new(0, 0), OffsetXY(0, 0));
+
+        assert_eq!(pack.sound_for(&selected), Some(Sound::Select));
+        assert_eq!(
+            pack.sound_for(&Event::ShapeDeselected),
+            Some(Sound::Deselect)
+        );
+        assert_eq!(pack.sound_for(&picked_up), None);
+
+        pack.selection_sounds = false;
+        assert_eq!(pack.sound_for(&selected), None);
+        assert_eq!(pack.sound_for(&Event::ShapeDeselected), None);
+    }
+
+    #[test]
+    fn test_placement_clip_per_shape_type() {
+        const CLICK: &[u8] = b"click";
+        let path = temp_settings_path("click_sound");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, CLICK).unwrap();
+        let settings = SettingsFile::parse(&format!(
+            "sound.placed.OO={}\nsound.placed.T1=missing.wav\n",
+            path.display()
+        ));
+        let mut pack = SoundPack::new();
+        pack.load_placement_clips(&placement_sounds(&settings));
+        let placed = |base| {
+            Event::SelectedShapePlaced(
+                ShapeType::new(base, false, ShapeRot::No),
+                CellCoord::new(0, 0),
+            )
+        };
+
+        assert_eq!(
+            pack.sound_for(&placed(BaseShapeType::OO)),
+            Some(Sound::Placed(BaseShapeType::OO))
+        );
+        assert_eq!(&pack.placement_clip(BaseShapeType::OO)[..], CLICK);
+        // the file is missing, or nothing is set up, they bounce
+        assert_eq!(&pack.placement_clip(BaseShapeType::T1)[..], BOUNCE_BYTES);
+        assert_eq!(&pack.placement_clip(BaseShapeType::L1)[..], BOUNCE_BYTES);
+        // not a clip rodio can play, the bounce stands in
+        let fallback = pack.placement(BaseShapeType::OO);
+        assert_eq!(fallback.sample_rate(), pack.bounce().sample_rate());
     }
 
-    pub fn bounce(&self) -> rodio::Decoder<Cursor<&'static [u8]>> {
-        rodio::Decoder::new(self.bounce.clone()).unwrap()
+    #[test]
+    fn test_bigger_clears_play_higher() {
+        let mut pack = SoundPack::new();
+
+        assert_eq!(
+            pack.sound_for(&Event::LinesCleared(2)),
+            Some(Sound::Clear(2))
+        );
+        assert_eq!(pack.clear_rate(1), 1.0);
+        assert!(pack.clear_rate(1) < pack.clear_rate(2));
+        assert!(pack.clear_rate(2) < pack.clear_rate(3));
+        // a whole tone per line
+        assert!((pack.clear_rate(2) - 1.122).abs() < 0.001);
+        // no higher than an octave
+        assert_eq!(pack.clear_rate(20), CLEAR_MAX_RATE);
+
+        pack.clear_pitch_variation = false;
+        assert_eq!(pack.clear_rate(4), 1.0);
+    }
+
+    #[test]
+    fn test_muting_pauses_the_playing_track() {
+        // a sink that isn't connected to any output
+        let (sink, _output) = rodio::Sink::new_idle();
+        let mut music = Music::new(None);
+        music.track = Some(PathBuf::from("a.wav"));
+        music.sink = Some(sink);
+        assert!(music.is_playing());
+
+        music.set_muted(true);
+        assert!(!music.is_playing());
+        assert_eq!(music.track, Some(PathBuf::from("a.wav")));
+
+        music.set_muted(false);
+        assert!(music.is_playing());
+    }
+
+    #[test]
+    fn test_music_started_while_muted_stays_silent() {
+        let track = Path::new("res/sounds/4362__noisecollector__pongblipa-4.wav");
+        let mut music = Music::new(None);
+        music.set_muted(true);
+
+        music.play(track);
+        assert_eq!(music.track.as_deref(), Some(track));
+        assert!(!music.is_playing());
+
+        music.stop();
+        assert_eq!(music.track, None);
+        assert!(!music.is_playing());
+    }
+
+    #[test]
+    fn test_missing_audio_device_falls_back_to_the_default() {
+        let available = || vec![("Speakers".to_string(), 1), ("USB Headset".to_string(), 2)];
+
+        assert_eq!(pick_device(available(), "USB Headset"), Some(2));
+        // unplugged since it was chosen
+        assert_eq!(pick_device(available(), "HDMI Output"), None);
+        assert_eq!(pick_device(Vec::<(String, u8)>::new(), "Speakers"), None);
+    }
+
+    #[test]
+    fn test_music_tracks_cycle_over_levels() {
+        let settings = SettingsFile::parse("music.tracks=a.wav, b.wav\n");
+        let tracks = music_tracks(&settings);
+
+        assert_eq!(music_for_level(&tracks, 1), Some(&PathBuf::from("a.wav")));
+        assert_eq!(music_for_level(&tracks, 2), Some(&PathBuf::from("b.wav")));
+        assert_eq!(music_for_level(&tracks, 3), Some(&PathBuf::from("a.wav")));
+        assert_eq!(music_for_level(&[], 1), None);
     }
 }
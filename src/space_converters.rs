@@ -1,5 +1,8 @@
+use crate::game_config::BoardOrigin;
 use crate::game_entities::ShapeState::VISIBLE;
-use crate::game_entities::{Board, Cell, Panel};
+use crate::game_entities::{
+    BaseShapeType, Board, Cell, ClearedLines, Game, Panel, SelectedShape, ShapeType,
+};
 use crate::render::render::UserRenderConfig;
 
 // pixel coordinates.
@@ -24,13 +27,21 @@ impl CellCoord {
     pub fn new(col: i16, row: i16) -> Self {
         Self { col, row }
     }
+
+    // the same cell on a board where leaving one edge enters at the opposite one
+    pub fn wrapped(&self, cols: usize, rows: usize) -> Self {
+        Self::new(
+            self.col.rem_euclid(cols as i16),
+            self.row.rem_euclid(rows as i16),
+        )
+    }
 }
 
 #[derive(Hash, Eq, PartialEq, Clone, Copy)]
 pub struct Edge(pub u32, pub u32); // Edge is a pair of vertex indices
 impl Edge {
-    pub fn around_cell(coord: &CellCoord, board_size: usize) -> [Edge; 4] {
-        let ix = cell_to_ix_4(coord, board_size);
+    pub fn around_cell(coord: &CellCoord, cols: usize) -> [Edge; 4] {
+        let ix = cell_to_ix_4(coord, cols);
         [
             Edge(ix[0], ix[1]).canonical(),
             Edge(ix[1], ix[2]).canonical(),
@@ -57,20 +68,12 @@ pub fn to_cell_space(top_left: XY, cell_size: f32, coord: &XY) -> CellCoord {
 
 //shapes -> index_buffer
 pub fn render_panel(panel: &Panel, panel_width_cols: usize, board_index_offset: usize) -> Vec<u32> {
-    let visible_cells: Vec<CellCoord> = panel
-        .shapes_in_cell_space
-        .iter()
-        .filter_map(|(coord, &shape_index)| {
-            panel
-                .shape_choice
-                .get(shape_index)
-                .filter(|shape| shape.state == VISIBLE)
-                .map(|_| coord.clone())
-        })
-        .collect();
-
     // convert grid + dimensions to indices for triangles
-    return to_index_space(visible_cells, panel_width_cols, board_index_offset as u32);
+    return to_index_space(
+        panel.visible_cells(),
+        panel_width_cols,
+        board_index_offset as u32,
+    );
 }
 
 /*
@@ -132,60 +135,287 @@ pub fn cell_to_ix_4(coord: &CellCoord, max_col: usize) -> [u32; 4] {
     ];
 }
 
-// board to index buffer
-pub fn render_board(board: &Board) -> Vec<u32> {
-    let mut indices = Vec::new();
-
-    /*
-            0   1   2   3
-              C0  C1  C2
-            4   5   6   7
-              C3  C4  C5
-            8   9   10  11
-              C6  C7  C8
-            12  13  14  15
-
-    */
-    for row in 0..board.size {
-        for col in 0..board.size {
-            if board.get(col, row).is_some_and(|x| x == &Cell::Filled) {
-                indices.extend(cell_to_ix(
-                    &CellCoord::new(col as i16, row as i16),
-                    board.size,
-                ));
+// board to index buffer. Every cell has its own slot of 6 indices, empty cells are degenerate
+// triangles, so a single changed cell can be uploaded on its own.
+pub fn render_board(board: &Board, origin: BoardOrigin) -> Vec<u32> {
+    (0..board.grid.len())
+        .flat_map(|ix| board_cell_indices(board, ix, origin))
+        .collect()
+}
+
+/*
+        0   1   2   3
+          C0  C1  C2
+        4   5   6   7
+          C3  C4  C5
+        8   9   10  11
+          C6  C7  C8
+        12  13  14  15
+
+*/
+// indices of the cell `ix` (row * cols + col), all zeros when it's empty
+pub fn board_cell_indices(board: &Board, ix: usize, origin: BoardOrigin) -> [u32; 6] {
+    if board.grid[ix] != Cell::Filled {
+        return [0; 6];
+    }
+    let (col, row) = (ix % board.cols, ix / board.cols);
+    let visual_row = origin.visual_row(row, board.rows);
+    cell_to_ix(&CellCoord::new(col as i16, visual_row as i16), board.cols)
+}
+
+// writes to bring the board part of the index buffer up to date: offset (in indices) and data.
+// A new board is written at once, otherwise just the changed cells.
+pub fn board_index_writes(board: &mut Board, origin: BoardOrigin) -> Vec<(usize, Vec<u32>)> {
+    let changed = board.take_changed_cells();
+    if changed.len() == board.grid.len() {
+        return vec![(0, render_board(board, origin))];
+    }
+    changed
+        .into_iter()
+        .map(|ix| (ix * 6, board_cell_indices(board, ix, origin).to_vec()))
+        .collect()
+}
+
+// cells of the given rows and cols to index buffer, every cell once
+pub fn render_lines(
+    lines: &ClearedLines,
+    cols: usize,
+    rows: usize,
+    origin: BoardOrigin,
+) -> Vec<u32> {
+    let mut cells: Vec<CellCoord> = vec![];
+    for &row in &lines.rows {
+        let visual_row = origin.visual_row(row, rows);
+        cells.extend((0..cols).map(|col| CellCoord::new(col as i16, visual_row as i16)));
+    }
+    for &col in &lines.cols {
+        cells.extend(
+            (0..rows)
+                .filter(|row| !lines.rows.contains(row))
+                .map(|row| CellCoord::new(col as i16, origin.visual_row(row, rows) as i16)),
+        );
+    }
+    to_index_space(cells, cols, 0)
+}
+
+// outline of every given row and col as a closed loop of 5 indices, for the line pipeline
+pub fn render_line_outlines(
+    lines: &ClearedLines,
+    cols: usize,
+    rows: usize,
+    origin: BoardOrigin,
+) -> Vec<u32> {
+    let (last_col, last_row) = (cols as i16 - 1, rows as i16 - 1);
+    let mut indices = vec![];
+    // corners of the cells at the ends of the line, clockwise from the top left
+    let mut outline = |top_left: CellCoord, bottom_right: CellCoord| {
+        let corner = |col: i16, row: i16, corner: usize| {
+            cell_to_ix_4(&CellCoord::new(col, row), cols)[corner]
+        };
+        let (left, top) = (top_left.col, top_left.row);
+        let (right, bottom) = (bottom_right.col, bottom_right.row);
+        indices.extend([
+            corner(left, top, 0),
+            corner(right, top, 1),
+            corner(right, bottom, 2),
+            corner(left, bottom, 3),
+            corner(left, top, 0),
+        ]);
+    };
+    for &row in &lines.rows {
+        let visual_row = origin.visual_row(row, rows) as i16;
+        outline(
+            CellCoord::new(0, visual_row),
+            CellCoord::new(last_col, visual_row),
+        );
+    }
+    for &col in &lines.cols {
+        outline(
+            CellCoord::new(col as i16, 0),
+            CellCoord::new(col as i16, last_row),
+        );
+    }
+    indices
+}
+
+// empty cells of the given rows and cols to index buffer, every cell once
+pub fn render_line_gaps(board: &Board, lines: &ClearedLines, origin: BoardOrigin) -> Vec<u32> {
+    let mut cells: Vec<(usize, usize)> = vec![];
+    for &row in &lines.rows {
+        cells.extend((0..board.cols).map(|col| (col, row)));
+    }
+    for &col in &lines.cols {
+        cells.extend(
+            (0..board.rows)
+                .filter(|row| !lines.rows.contains(row))
+                .map(|row| (col, row)),
+        );
+    }
+    let gaps = cells
+        .into_iter()
+        .filter(|&(col, row)| board.get(col, row) == Some(&Cell::Empty))
+        .map(|(col, row)| CellCoord::new(col as i16, origin.visual_row(row, board.rows) as i16))
+        .collect();
+    to_index_space(gaps, board.cols, 0)
+}
+
+// bonus cells to index buffer
+pub fn render_bonus_cells(board: &Board, origin: BoardOrigin) -> Vec<u32> {
+    let cells = board
+        .bonus_cells()
+        .into_iter()
+        .map(|(col, row)| CellCoord::new(col as i16, origin.visual_row(row, board.rows) as i16))
+        .collect();
+    to_index_space(cells, board.cols, 0)
+}
+
+// placed cells to index buffer, grouped by the type of shape they came from. Types with no
+// cells on the board are left out.
+pub fn render_shape_colors(board: &Board, origin: BoardOrigin) -> Vec<(BaseShapeType, Vec<u32>)> {
+    let mut groups: Vec<(BaseShapeType, Vec<CellCoord>)> = vec![];
+    for row in 0..board.rows {
+        for col in 0..board.cols {
+            let Some(shape) = board.shape_type_at(col, row) else {
+                continue;
+            };
+            let cell = CellCoord::new(col as i16, origin.visual_row(row, board.rows) as i16);
+            match groups.iter_mut().find(|(s, _)| *s == shape) {
+                Some((_, cells)) => cells.push(cell),
+                None => groups.push((shape, vec![cell])),
             }
         }
     }
+    groups
+        .into_iter()
+        .map(|(shape, cells)| (shape, to_index_space(cells, board.cols, 0)))
+        .collect()
+}
 
-    indices
+// board shape and top-left board cell the selected shape goes to when clicked at `position`,
+// valid or not
+pub fn aimed_placement(
+    game: &Game,
+    selected: &SelectedShape,
+    position: &XY,
+    cfg: &UserRenderConfig,
+) -> (ShapeType, CellCoord) {
+    let placement_xy_0 = position.apply_offset(&selected.anchor_offset);
+    let placement_0_cell = to_cell_space(
+        XY(cfg.board_offset_x_px, cfg.board_offset_y_px),
+        cfg.cell_size_px,
+        &placement_xy_0,
+    );
+    let (board_shape, mut board_cell) =
+        game.board_placement(&selected.shape_type, &placement_0_cell);
+    if game.config.wraparound {
+        board_cell = board_cell.wrapped(game.board.cols, game.board.rows);
+    }
+    (board_shape, board_cell)
 }
 
 pub fn within_bounds(px: f32, py: f32, x_max: f32, y_max: f32) -> bool {
     px >= 0.0 && px < x_max && py >= 0.0 && py < y_max
 }
 
+// visible panel shape under the given pixel position
+pub fn panel_shape_at(panel: &Panel, position: &XY, cfg: &UserRenderConfig) -> Option<usize> {
+    let px = position.0 - cfg.panel_offset_x_px;
+    let py = position.1 - cfg.panel_offset_y_px;
+    if !within_bounds(
+        px,
+        py,
+        cfg.cell_size_px * cfg.panel_cols as f32,
+        cfg.cell_size_px * cfg.panel_rows as f32,
+    ) {
+        return None;
+    }
+    let cell = CellCoord::new(
+        (px / cfg.cell_size_px) as i16,
+        (py / cfg.cell_size_px) as i16,
+    );
+    panel
+        .shapes_in_cell_space
+        .get(&cell)
+        .copied()
+        .filter(|&ix| panel.shape_choice[ix].state == VISIBLE)
+}
+
 pub fn over_board(position: &XY, cfg: &UserRenderConfig) -> bool {
     let mouse_in_board_basis = position.apply_offset(&OffsetXY(
         -cfg.board_offset_x_px as i16,
         -cfg.board_offset_y_px as i16,
     ));
-    let board_max = cfg.board_size_cols as f32 * cfg.cell_size_px;
     return within_bounds(
         mouse_in_board_basis.0,
         mouse_in_board_basis.1,
-        board_max,
-        board_max,
+        cfg.board_size_cols as f32 * cfg.cell_size_px,
+        cfg.board_size_rows as f32 * cfg.cell_size_px,
     );
 }
 
+pub fn over_panel(position: &XY, cfg: &UserRenderConfig) -> bool {
+    within_bounds(
+        position.0 - cfg.panel_offset_x_px,
+        position.1 - cfg.panel_offset_y_px,
+        cfg.cell_size_px * cfg.panel_cols as f32,
+        cfg.cell_size_px * cfg.panel_rows as f32,
+    )
+}
+
+// over neither the board nor the panel, e.g. over the HUD. A selected shape can't go anywhere
+// from there.
+pub fn off_play_area(position: &XY, cfg: &UserRenderConfig) -> bool {
+    !over_board(position, cfg) && !over_panel(position, cfg)
+}
+
+// closest point on the board, the middle of its edge cells at most
+pub fn clamp_to_board(position: &XY, cfg: &UserRenderConfig) -> XY {
+    let half_cell = cfg.cell_size_px / 2.0;
+    let board_width = cfg.board_size_cols as f32 * cfg.cell_size_px;
+    let board_height = cfg.board_size_rows as f32 * cfg.cell_size_px;
+    XY(
+        position.0.clamp(
+            cfg.board_offset_x_px + half_cell,
+            cfg.board_offset_x_px + board_width - half_cell,
+        ),
+        position.1.clamp(
+            cfg.board_offset_y_px + half_cell,
+            cfg.board_offset_y_px + board_height - half_cell,
+        ),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_line_outlines_go_around_the_line() {
+        let lines = ClearedLines {
+            rows: vec![0],
+            cols: vec![2],
+        };
+
+        let indices = render_line_outlines(&lines, 3, 3, BoardOrigin::TopLeft);
+
+        // 4 vertices per grid row on a 3 x 3 board
+        assert_eq!(indices[..5], [0, 3, 7, 4, 0]);
+        assert_eq!(indices[5..], [2, 3, 15, 14, 2]);
+        // the bottom row when counting from the bottom
+        let flipped = ClearedLines {
+            rows: vec![0],
+            cols: vec![],
+        };
+        assert_eq!(
+            render_line_outlines(&flipped, 3, 3, BoardOrigin::BottomLeft),
+            vec![8, 11, 15, 12, 8]
+        );
+    }
+
     #[test]
     fn test_single_cell() {
         let cells = vec![CellCoord::new(0, 0)]; // Top-left corner
-        let indices = to_index_space(cells, 7);
+        let indices = to_index_space(cells, 7, 0);
 
         assert_eq!(
             indices,
@@ -199,7 +429,7 @@ mod tests {
     #[test]
     fn test_two_adjacent_cells_horizontally() {
         let cells = vec![CellCoord::new(0, 0), CellCoord::new(1, 0)]; // Two side-by-side cells in row 0
-        let indices = to_index_space(cells, 7);
+        let indices = to_index_space(cells, 7, 0);
 
         assert_eq!(
             indices,
@@ -213,7 +443,7 @@ mod tests {
     #[test]
     fn test_two_adjacent_cells_vertically() {
         let cells = vec![CellCoord::new(0, 0), CellCoord::new(0, 1)]; // Two stacked cells
-        let indices = to_index_space(cells, 7);
+        let indices = to_index_space(cells, 7, 0);
 
         assert_eq!(
             indices,
@@ -224,6 +454,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render_crossing_lines_once() {
+        let lines = ClearedLines {
+            rows: vec![0],
+            cols: vec![1],
+        };
+
+        let indices = render_lines(&lines, 2, 2, BoardOrigin::TopLeft);
+
+        // (0,0) (1,0) from the row, (1,1) from the col, (1,0) is shared
+        assert_eq!(
+            indices,
+            vec![
+                0, 3, 4, 0, 4, 1, // (0,0)
+                1, 4, 5, 1, 5, 2, // (1,0)
+                4, 7, 8, 4, 8, 5, // (1,1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_lines_on_a_wide_board() {
+        let lines = ClearedLines {
+            rows: vec![],
+            cols: vec![2],
+        };
+
+        // 3 cols and 2 rows, the col is as long as the board is high
+        let indices = render_lines(&lines, 3, 2, BoardOrigin::TopLeft);
+
+        assert_eq!(
+            indices,
+            vec![
+                2, 6, 7, 2, 7, 3, // (2,0)
+                6, 10, 11, 6, 11, 7, // (2,1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_gaps_are_the_empty_cells_only() {
+        let board = Board::from_ascii("#.\n..\n").unwrap();
+        let lines = ClearedLines {
+            rows: vec![0],
+            cols: vec![0],
+        };
+
+        let indices = render_line_gaps(&board, &lines, BoardOrigin::TopLeft);
+
+        assert_eq!(
+            indices,
+            vec![
+                1, 4, 5, 1, 5, 2, // (1,0)
+                3, 6, 7, 3, 7, 4, // (0,1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_row_0_is_drawn_at_the_origin_row() {
+        let mut board = Board::new(3);
+        board.set_cell(0, 0, Cell::Filled);
+
+        // vertex grid is 4 wide, top row first
+        assert_eq!(
+            board_cell_indices(&board, 0, BoardOrigin::TopLeft),
+            [0, 4, 5, 0, 5, 1]
+        );
+        assert_eq!(
+            board_cell_indices(&board, 0, BoardOrigin::BottomLeft),
+            [8, 12, 13, 8, 13, 9]
+        );
+    }
+
+    #[test]
+    fn test_single_changed_cell_writes_only_its_indices() {
+        let mut board = Board::new(3);
+        let first = board_index_writes(&mut board, BoardOrigin::TopLeft);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].1.len(), 9 * 6);
+
+        board.set_cell(2, 1, Cell::Filled);
+        let writes = board_index_writes(&mut board, BoardOrigin::TopLeft);
+
+        // cell 5 is the 6th slot
+        assert_eq!(writes, vec![(5 * 6, vec![6, 10, 11, 6, 11, 7])]);
+        assert!(board_index_writes(&mut board, BoardOrigin::TopLeft).is_empty());
+    }
+
     #[test]
     fn test_non_contiguous_cells_in_elonagated_grid() {
         let cells = vec![
@@ -231,7 +550,7 @@ mod tests {
             CellCoord::new(2, 1),
             CellCoord::new(5, 2),
         ]; // Scattered cells
-        let indices = to_index_space(cells, 7);
+        let indices = to_index_space(cells, 7, 0);
 
         assert_eq!(
             indices,
@@ -242,4 +561,23 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_cursor_over_the_hud_is_off_the_play_area() {
+        let cfg = UserRenderConfig::default();
+        let hud = XY(850.0, 150.0);
+        let on_board = XY(cfg.board_offset_x_px + 1.0, cfg.board_offset_y_px + 1.0);
+        let on_panel = XY(cfg.panel_offset_x_px + 1.0, cfg.panel_offset_y_px + 1.0);
+
+        assert!(off_play_area(&hud, &cfg));
+        assert!(!off_play_area(&on_board, &cfg));
+        assert!(!off_play_area(&on_panel, &cfg));
+
+        // the nearest board edge is its right one, in the middle of the top row's last cell
+        let snapped = clamp_to_board(&hud, &cfg);
+        assert!(over_board(&snapped, &cfg));
+        let board_right = cfg.board_offset_x_px + cfg.board_size_cols as f32 * cfg.cell_size_px;
+        assert_eq!(snapped.0, board_right - cfg.cell_size_px / 2.0);
+        assert_eq!(snapped.1, 150.0);
+    }
 }
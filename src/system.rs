@@ -1,12 +1,27 @@
+use std::cmp::max;
 use std::collections::VecDeque;
 use std::time::Duration;
 
 use crate::events::Event;
-use crate::events::Event::{SelectedShapePlaced, ShapeSelected};
-use crate::game_entities::{Cell, Game, GameState, Panel, ShapeState};
+use crate::events::Event::{
+    LinesCleared, PlacedShapePickedUp, SelectedShapePlaced, ShapeDeselected, ShapeSelected,
+};
+use crate::game_entities::{
+    Board, ClearedLines, ClearingAnimation, Game, GameState, Panel, PanelRefresh,
+    RotationAnimation, ShapeState, ShapeType,
+};
+use crate::goal;
 use crate::input::Input;
+use crate::leaderboard::LeaderboardEntry;
+use crate::patterns::Pattern;
 use crate::render::render::UserRenderConfig;
-use crate::space_converters::{to_cell_space, within_bounds, CellCoord, OffsetXY, XY};
+use crate::save::SaveHook;
+use crate::solver;
+use crate::space_converters::{
+    aimed_placement, over_board, panel_shape_at, to_cell_space, within_bounds, CellCoord, OffsetXY,
+    XY,
+};
+use strum::IntoEnumIterator;
 
 pub trait System {
     #[allow(unused_variables)]
@@ -33,8 +48,8 @@ impl System for SelectionValidationSystem {
         render_config: &UserRenderConfig,
         oe: Option<&Event>,
     ) {
-        if input.mouse_right_clicked {
-            state.deselect();
+        if input.mouse_right_clicked && state.selected_shape.is_some() {
+            events.push_front(ShapeDeselected);
         }
         if let Some(XY(x, y)) = input.mouse_left_clicked {
             match &state.selected_shape {
@@ -85,28 +100,52 @@ impl System for SelectionValidationSystem {
                                 ))
                             }
                         }
+                    } else if state.config.pick_up_placed_shapes
+                        && over_board(&XY(x, y), render_config)
+                    {
+                        let cell = to_cell_space(
+                            XY(
+                                render_config.board_offset_x_px,
+                                render_config.board_offset_y_px,
+                            ),
+                            render_config.cell_size_px,
+                            &XY(x, y),
+                        );
+                        let row = state
+                            .config
+                            .board_origin
+                            .visual_row(cell.row as usize, state.board.rows);
+                        let cell = CellCoord::new(cell.col, row as i16);
+                        let placed = state
+                            .board
+                            .placed_shape_at(cell.col as usize, cell.row as usize);
+                        if let Some(placed) = placed {
+                            // flipping is its own inverse, so this gives where the shape is on screen
+                            let (_, visual_origin) =
+                                state.board_placement(&placed.shape_type, &placed.origin);
+                            let origin_x = render_config.board_offset_x_px
+                                + visual_origin.col as f32 * render_config.cell_size_px;
+                            let origin_y = render_config.board_offset_y_px
+                                + visual_origin.row as f32 * render_config.cell_size_px;
+                            events.push_front(PlacedShapePickedUp(
+                                cell,
+                                OffsetXY(
+                                    (origin_x - x).floor() as i16,
+                                    (origin_y - y).floor() as i16,
+                                ),
+                            ))
+                        }
                     }
                 }
                 // something was selected, and we try to place shape on the board
                 Some(selected_shape) => {
-                    let placement_xy_0 = XY(x, y).apply_offset(&selected_shape.anchor_offset);
-                    let placement_0_cell = to_cell_space(
-                        XY(
-                            render_config.board_offset_x_px,
-                            render_config.board_offset_y_px,
-                        ),
-                        render_config.cell_size_px,
-                        &placement_xy_0,
-                    );
-
-                    println!("Trying to place in the cell {:?}", &placement_0_cell);
+                    let (board_shape, board_cell) =
+                        aimed_placement(state, selected_shape, &XY(x, y), render_config);
+                    println!("Trying to place in the cell {:?}", &board_cell);
 
-                    // we can always compute if placement is value to show the shadow
-                    if state.is_valid_placement(&selected_shape.shape_type, &placement_0_cell) {
-                        events.push_front(SelectedShapePlaced(
-                            selected_shape.shape_type,
-                            placement_0_cell,
-                        ))
+                    // the shadow is coloured by the same check, see `render::shadow_color`
+                    if state.accepts_placement(&board_shape, &board_cell) {
+                        events.push_front(SelectedShapePlaced(board_shape, board_cell))
                     }
                 }
             }
@@ -127,9 +166,29 @@ impl System for PlacementSystem {
     ) {
         if let Some(SelectedShapePlaced(shape, cell)) = event {
             println!("Placing shape {:?} to {:?}", shape, cell);
+            // panel slot and grab point of the shape, for the sticky selection
+            let placed_from = state.selected_shape.as_ref().and_then(|selected| {
+                selected
+                    .panel_index
+                    .map(|ix| (ix, selected.anchor_offset.clone()))
+            });
             // update board
             state.place_shape(shape, cell);
             state.ui.need_to_update_board = true;
+            state.placements += 1;
+
+            if state.config.soft_drop_scoring {
+                // counted from the top of the shape on the screen, flipping is its own inverse
+                let (_, visual_cell) = state.board_placement(shape, cell);
+                let bonus = soft_drop_bonus(
+                    visual_cell.row,
+                    state.board.rows,
+                    state.config.soft_drop_max_bonus,
+                );
+                state.stats.current_score += bonus;
+                state.stats.total_score += bonus;
+                state.ledger.soft_drop += bonus;
+            }
 
             if state
                 .panel
@@ -137,14 +196,68 @@ impl System for PlacementSystem {
                 .iter()
                 .all(|s| s.state != ShapeState::VISIBLE)
             {
-                state.panel = Panel::generate_for_3();
+                // used up, nothing slides out before the new shapes come in
+                state.panel = Panel::generate(&mut state.rng, &mut state.shape_bag, &state.config);
+                state.ui.panel_refresh = panel_refresh(render_config, vec![]);
 
                 state.ui.need_to_update_panel = true;
+            } else if let (true, Some((placed_ix, anchor_offset))) =
+                (state.config.sticky_selection, placed_from)
+            {
+                let kind = state.panel.shape_choice[placed_ix].kind;
+                if let Some(next) = state
+                    .panel
+                    .shape_choice
+                    .iter()
+                    .position(|s| s.state == ShapeState::VISIBLE && s.kind == kind)
+                {
+                    events.push_front(ShapeSelected(next, anchor_offset));
+                }
             }
         }
     }
 }
 
+// `bonus` for every pattern the shape just placed at `cell` completed. Patterns it didn't touch
+// were complete before and don't count again, neither do ones it completed before.
+fn pattern_bonus(board: &mut Board, shape: &ShapeType, cell: &CellCoord, bonus: i32) -> i32 {
+    if bonus == 0 {
+        return 0;
+    }
+    let placed: Vec<(usize, usize)> = shape
+        .cells()
+        .into_iter()
+        .map(|(dx, dy)| {
+            (
+                (cell.col as usize + dx) % board.cols,
+                (cell.row as usize + dy) % board.rows,
+            )
+        })
+        .collect();
+    let completed: Vec<Pattern> = Pattern::iter()
+        .filter(|pattern| pattern.is_complete(board))
+        .filter(|pattern| {
+            pattern
+                .cells(board)
+                .iter()
+                .any(|pattern_cell| placed.contains(pattern_cell))
+        })
+        .collect();
+    completed
+        .into_iter()
+        .filter(|&pattern| board.score_pattern(pattern))
+        .count() as i32
+        * bonus
+}
+
+// the lower the shape is placed, the more it's worth: 0 on the top row, up to `max_bonus` at the bottom
+fn soft_drop_bonus(row: i16, board_rows: usize, max_bonus: i32) -> i32 {
+    if board_rows < 2 {
+        return 0;
+    }
+    row as i32 * max_bonus / (board_rows as i32 - 1)
+}
+
 // checks the board state after end of turn, that
 // 1. if there's some row or column that is filled (or some other  shape)
 // 2. cleans the board
@@ -160,64 +273,1381 @@ impl System for ScoreCleanupSystem {
         render_config: &UserRenderConfig,
         event: Option<&Event>,
     ) {
-        let size = game.board.size;
-
-        let mut row_counts = vec![0; size];
-        let mut col_counts = vec![0; size];
+        let (board_cols, board_rows) = (game.board.cols, game.board.rows);
+        // only lines crossing the placed shape could have become full this turn
+        let (rows, cols) = match event {
+            Some(SelectedShapePlaced(shape, cell)) => {
+                affected_lines(shape, cell, board_cols, board_rows)
+            }
+            _ => ((0..board_rows).collect(), (0..board_cols).collect()),
+        };
 
-        let mut total_cells = 0;
-        let mut full_cols = 0;
-        let mut full_rows = 0;
+        // before the clear takes the pattern cells away
+        if let Some(SelectedShapePlaced(shape, cell)) = event {
+            let bonus = pattern_bonus(&mut game.board, shape, cell, game.config.pattern_bonus);
+            game.ledger.patterns += bonus;
+            game.stats.current_score += bonus;
+            game.stats.total_score += bonus;
 
-        for row in 0..size {
-            for col in 0..size {
-                if game.board.get(col, row).is_some_and(|x| x == &Cell::Filled) {
-                    row_counts[row] += 1;
-                    col_counts[col] += 1;
+            if let Some(symmetry) = game.config.symmetry {
+                if game.board.is_symmetric(symmetry) {
+                    let bonus = game.config.symmetry_bonus;
+                    println!("Placement kept the board symmetric, +{}", bonus);
+                    game.ledger.symmetry += bonus;
+                    game.stats.current_score += bonus;
+                    game.stats.total_score += bonus;
                 }
             }
         }
 
-        for row in 0..size {
-            if row_counts[row] == size {
-                full_rows += 1;
-                total_cells += size;
-
-                game.clean_row(row);
+        let full = full_lines(&game.board, &rows, &cols);
+        game.last_cleared = full.clone();
+        // only placements count for the combo, not a check over the whole board
+        let placed = matches!(event, Some(SelectedShapePlaced(_, _)));
+        if full.is_empty() {
+            if placed {
+                game.stats.combo = 0;
             }
+            return;
         }
-        for col in 0..size {
-            if col_counts[col] == size {
-                full_cols += 1;
-                total_cells += size;
 
-                game.clean_col(col);
-            }
+        // counted before the clear takes them away, a cell where a row and a col cross once
+        let bonus_cells = game
+            .board
+            .bonus_cells()
+            .into_iter()
+            .filter(|(col, row)| full.rows.contains(row) || full.cols.contains(col))
+            .count() as i32;
+        if bonus_cells > 0 {
+            let bonus = bonus_cells * game.config.bonus_cell_points;
+            println!("Cleared {} bonus cells, +{}", bonus_cells, bonus);
+            game.ledger.bonus_cells += bonus;
+            game.stats.current_score += bonus;
+            game.stats.total_score += bonus;
         }
 
+        let full_rows = full.rows.len();
+        let full_cols = full.cols.len();
+        let total_cells = full_rows * board_cols + full_cols * board_rows;
+        // crossing lines share a cell
+        let distinct_cells = total_cells - full_rows * full_cols;
+
         //todo we can extract the score math in the different system, so we could extend the way score is computed
-        let score = (total_cells + full_cols * full_rows * full_cols * full_rows) as i32;
+        let multi_clear = (full_cols * full_rows * full_cols * full_rows) as i32;
+        // nothing left outside of the cleared lines, they may still be on the board
+        let perfect_clear = if game.board.filled_count() == distinct_cells {
+            game.config.perfect_clear_bonus
+        } else {
+            0
+        };
+        let score = total_cells as i32 + multi_clear + perfect_clear;
+        let combo = if placed && game.config.combo_scoring {
+            score * game.stats.combo as i32
+        } else {
+            0
+        };
+        if placed {
+            game.stats.combo += 1;
+        }
+        if combo > 0 {
+            println!("Clear {} in a row, +{}", game.stats.combo, combo);
+        }
+        let score = score + combo;
+        game.ledger.cells += total_cells as i32;
+        game.ledger.multi_clear += multi_clear;
+        game.ledger.combo += combo;
+        game.ledger.perfect_clear += perfect_clear;
         game.stats.current_score = game.stats.current_score + score;
         game.stats.total_score = game.stats.total_score + score;
+        game.stats.lines_cleared += (full_rows + full_cols) as u32;
+        game.stats.cells_cleared += distinct_cells as u32;
+        events.push_back(LinesCleared(full_rows + full_cols));
+
+        let animated = render_config
+            .clear_animation
+            .filter(|_| !render_config.reduce_motion);
+        if animated.is_some() {
+            game.clearing = Some(ClearingAnimation {
+                lines: full,
+                elapsed: Duration::ZERO,
+            });
+        } else {
+            game.board.clear_lines(&full);
+        }
+    }
+}
+
+// rows and cols covered by the shape placed with its top-left at `cell`, wrapped around the
+// board edges like `Board::fill_shape` does
+fn affected_lines(
+    shape: &ShapeType,
+    cell: &CellCoord,
+    board_cols: usize,
+    board_rows: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut rows = vec![];
+    let mut cols = vec![];
+    for (dx, dy) in shape.cells() {
+        let col = (cell.col as usize + dx) % board_cols;
+        let row = (cell.row as usize + dy) % board_rows;
+        if !rows.contains(&row) {
+            rows.push(row);
+        }
+        if !cols.contains(&col) {
+            cols.push(col);
+        }
+    }
+    rows.sort();
+    cols.sort();
+    (rows, cols)
+}
+
+fn full_lines(board: &Board, rows: &[usize], cols: &[usize]) -> ClearedLines {
+    ClearedLines {
+        rows: rows
+            .iter()
+            .copied()
+            .filter(|&row| board.is_row_full(row))
+            .collect(),
+        cols: cols
+            .iter()
+            .copied()
+            .filter(|&col| board.is_col_full(col))
+            .collect(),
+    }
+}
+
+// takes the cleared lines off the board once their animation is over
+pub struct ClearingSystem;
+impl System for ClearingSystem {
+    fn update_state(
+        &self,
+        _input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        let Some(clearing) = &mut game.clearing else {
+            return;
+        };
+        clearing.elapsed += dt;
+        // the lines blink until they go
+        game.ui.need_to_update_board = true;
+        let done = render_config.reduce_motion
+            || render_config
+                .clear_animation
+                .is_none_or(|duration| clearing.elapsed >= duration);
+        if done {
+            game.finish_clearing();
+        }
+    }
+}
+
+// turns the held shape a quarter clockwise with `GameConfig::rotation`, the drawn one follows over
+// `rotation_animation`
+pub struct RotationSystem;
+impl System for RotationSystem {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        game.since_rotation = game.since_rotation.map(|since| since + dt);
+        if !game.config.rotation || !input.rotate_pressed {
+            return;
+        }
+        // presses this soon after the last rotation are dropped
+        if game
+            .since_rotation
+            .is_some_and(|since| since < game.config.rotation_debounce)
+        {
+            return;
+        }
+        // a shape lifted off the board goes back to its cells when dropped, it has to keep fitting
+        let Some(selected) = game
+            .selected_shape
+            .as_mut()
+            .filter(|selected| selected.picked_up_from.is_none())
+        else {
+            return;
+        };
+        let from = selected.shape_type;
+        selected.shape_type = if input.shift() {
+            from.rotated_ccw()
+        } else {
+            from.rotated_cw()
+        };
+        println!("Rotated {:?} to {:?}", from, selected.shape_type);
+        game.since_rotation = Some(Duration::ZERO);
+        game.ui.rotation_animation = render_config
+            .rotation_animation
+            .filter(|_| !render_config.reduce_motion)
+            .map(|_| RotationAnimation {
+                from,
+                elapsed: Duration::ZERO,
+            });
+    }
+}
+
+pub struct RotationAnimationSystem;
+impl System for RotationAnimationSystem {
+    fn update_state(
+        &self,
+        _input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        if let Some(animation) = &mut game.ui.rotation_animation {
+            animation.elapsed += dt;
+            let done = render_config.reduce_motion
+                || render_config
+                    .rotation_animation
+                    .is_none_or(|duration| animation.elapsed >= duration);
+            // nothing left to turn once the shape is placed or put back
+            if done || game.selected_shape.is_none() {
+                game.ui.rotation_animation = None;
+            }
+        }
+    }
+}
+
+pub struct PanelRefreshSystem;
+impl System for PanelRefreshSystem {
+    fn update_state(
+        &self,
+        _input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        let Some(refresh) = &mut game.ui.panel_refresh else {
+            return;
+        };
+        let running = !render_config.reduce_motion
+            && render_config
+                .panel_refresh_animation
+                .is_some_and(|duration| refresh.advance(dt, duration));
+        if !running {
+            game.ui.panel_refresh = None;
+            game.ui.need_to_update_panel = true;
+        }
+    }
+}
+
+// slows the animations down for a moment after a clear of `slow_motion_min_lines` or more, so
+// the line flash plays as a replay. Counts down in real time when called without an event.
+pub struct SlowMotionSystem;
+impl System for SlowMotionSystem {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
+        match event {
+            Some(LinesCleared(count)) => {
+                if render_config.slow_motion_clears
+                    && !render_config.reduce_motion
+                    && *count >= render_config.slow_motion_min_lines
+                {
+                    game.ui.slow_motion = Some(render_config.slow_motion_duration);
+                }
+            }
+            _ => {
+                game.ui.slow_motion = game
+                    .ui
+                    .slow_motion
+                    .and_then(|left| left.checked_sub(dt))
+                    .filter(|left| !left.is_zero());
+            }
+        }
+    }
+}
+
+// takes points away while nothing gets placed for longer than the grace period. A placement
+// starts the idle time over.
+pub struct DecaySystem;
+impl System for DecaySystem {
+    fn update_state(
+        &self,
+        _input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        _render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
+        if let Some(SelectedShapePlaced(_, _)) = event {
+            game.idle = Duration::ZERO;
+            return;
+        }
+        let Some(grace) = game.config.score_decay_grace else {
+            return;
+        };
+        let every = game.config.score_decay_every;
+        let due = |idle: Duration| match idle.checked_sub(grace) {
+            Some(over) if !every.is_zero() => (over.as_nanos() / every.as_nanos()) as i32,
+            _ => 0,
+        };
+        let speed = game
+            .config
+            .score_decay_ramp
+            .map_or(1.0, |ramp| ramp.factor(game.level_time));
+        let before = due(game.idle);
+        game.idle += dt.mul_f32(speed);
+        let points = (due(game.idle) - before).min(game.stats.current_score);
+        if points <= 0 {
+            return;
+        }
+        game.stats.current_score -= points;
+        let total_before = game.stats.total_score;
+        game.stats.total_score = max(0, game.stats.total_score - points);
+        game.ledger.decay -= total_before - game.stats.total_score;
+    }
+}
+
+// keeps the edge glow pulsing while some line is nearly full
+pub struct EdgeGlowSystem;
+impl System for EdgeGlowSystem {
+    fn update_state(
+        &self,
+        _input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        let glowing = render_config
+            .edge_glow_threshold
+            .is_some_and(|threshold| !game.board.lines_filled_over(threshold).is_empty());
+        // held still with reduced motion, see `background::steady_pulse`
+        let dt = if render_config.reduce_motion {
+            Duration::ZERO
+        } else {
+            dt
+        };
+        game.ui.edge_glow = glowing.then(|| game.ui.edge_glow.unwrap_or_default() + dt);
+    }
+}
+
+// keeps the danger zone pulse going while the board is nearly full
+pub struct DangerZoneSystem;
+impl System for DangerZoneSystem {
+    fn update_state(
+        &self,
+        _input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        let in_danger = render_config.show_danger_zone
+            && game.board.fill_fraction() >= render_config.danger_zone_threshold;
+        game.ui.danger_zone = match (in_danger, game.ui.danger_zone) {
+            (false, _) => None,
+            (true, None) => {
+                println!("Board is nearly full");
+                Some(Duration::ZERO)
+            }
+            (true, Some(_)) if render_config.reduce_motion => Some(Duration::ZERO),
+            (true, Some(elapsed)) => Some(elapsed + dt),
+        };
+    }
+}
+
+// tracks the panel shape under the cursor, for the hover previews
+pub struct PanelHoverSystem;
+impl System for PanelHoverSystem {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
+        let hovered = panel_shape_at(&game.panel, &input.mouse_position, render_config);
+        if hovered != game.ui.hovered_panel_shape {
+            game.ui.hovered_panel_shape = hovered;
+            // nothing on screen changes without the preview
+            if render_config.show_rotation_preview {
+                game.ui.need_to_update_panel = true;
+            }
+        }
     }
 }
 
 pub struct WinOrLoseSystem;
 impl System for WinOrLoseSystem {
-    fn update_state(&self, input: &Input, dt: Duration, game: &mut Game, events: &mut VecDeque<Event>, render_config: &UserRenderConfig, event: Option<&Event>) {
-        if game.stats.total_score >= game.stats.target_score {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
+        // the lines about to be cleared still take up their cells, so both wait for the clear
+        if game.clearing.is_some() {
+            return;
+        }
+        if goal::progress_toward_goal(game) >= 1.0 {
             game.game_state = GameState::MoveToNextLevel;
+        } else if game.game_state != GameState::GameOver && solver::is_game_over(game) {
+            println!("No shape fits, game over");
+            game.game_state = GameState::GameOver;
+            if game.config.keep_leaderboard && !game.on_leaderboard {
+                game.on_leaderboard = true;
+                let entry = LeaderboardEntry::today(game.stats.total_score, game.stats.level);
+                if let Some(place) = game.leaderboard.insert(entry) {
+                    println!("New top score, place {}", place + 1);
+                }
+            }
         }
-        // if can't place shape -> gamover
+    }
+}
 
+// ends the level intro after its time is up, or on any key or click
+pub struct LevelIntroSystem;
+impl System for LevelIntroSystem {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        _events: &mut VecDeque<Event>,
+        _render_config: &UserRenderConfig,
+        _event: Option<&Event>,
+    ) {
+        let skipped =
+            input.key_pressed || input.mouse_left_clicked.is_some() || input.mouse_right_clicked;
+        let left = game
+            .level_intro_left
+            .and_then(|left| left.checked_sub(dt))
+            .filter(|left| !left.is_zero());
+        game.level_intro_left = if skipped { None } else { left };
+        if game.level_intro_left.is_none() {
+            println!("Level {} starts", game.stats.level);
+            game.game_state = GameState::Playing;
+            game.ui.need_to_update_board = true;
+            game.ui.need_to_update_panel = true;
+        }
     }
 }
 
 pub struct NewGameSystem;
 impl System for NewGameSystem {
-    fn update_state(&self, input: &Input, dt: Duration, state: &mut Game, events: &mut VecDeque<Event>, render_config: &UserRenderConfig, event: Option<&Event>) {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        state: &mut Game,
+        events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
         println!("Next level");
+        // the shapes left over from the level slide out before the new panel comes in
+        let outgoing = state.panel.visible_cells();
         state.go_next_level();
+        state.ui.panel_refresh = panel_refresh(render_config, outgoing);
     }
 }
 
+// animation of a panel dealt again, none when it's turned off
+fn panel_refresh(
+    render_config: &UserRenderConfig,
+    outgoing: Vec<CellCoord>,
+) -> Option<PanelRefresh> {
+    (render_config.panel_refresh_animation.is_some() && !render_config.reduce_motion)
+        .then(|| PanelRefresh::new(outgoing))
+}
+
+// keeps the panel from becoming hopeless right after a turn, see `GameConfig::anti_frustration`
+pub struct AntiFrustrationSystem;
+impl System for AntiFrustrationSystem {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
+        if game.config.anti_frustration {
+            game.reroll_unplaceable_shape();
+        }
+    }
+}
+
+// saves the game every `autosave_every` placements, after the lines are cleared
+pub struct AutosaveSystem {
+    pub save_hook: Option<SaveHook>,
+}
+impl System for AutosaveSystem {
+    fn update_state(
+        &self,
+        input: &Input,
+        dt: Duration,
+        game: &mut Game,
+        events: &mut VecDeque<Event>,
+        render_config: &UserRenderConfig,
+        event: Option<&Event>,
+    ) {
+        let (Some(every), Some(save)) = (game.config.autosave_every, &self.save_hook) else {
+            return;
+        };
+        if every > 0 && game.placements > 0 && game.placements.is_multiple_of(every) {
+            save(game);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_config::{BoardOrigin, RampCurve, SpeedRamp};
+    use crate::game_entities::{
+        BaseShapeType, Cell, PanelRefreshPhase, SelectedShape, Shape, ShapeRot,
+    };
+    use crate::space_converters::OffsetXY;
+    use rand::Rng;
+    use winit::event::ElementState;
+    use winit::keyboard::{KeyCode, ModifiersState};
+
+    fn game_with_empty_board(size: usize) -> Game {
+        let mut game = Game::new_level(size, 1, 0);
+        game.board = Board::new(size);
+        game
+    }
+
+    // places the shape and lets its clear play out
+    fn place(game: &mut Game, shape: ShapeType, cell: CellCoord) {
+        let event = SelectedShapePlaced(shape, cell);
+        game.place_shape(&shape, &cell);
+        ScoreCleanupSystem.update_state(
+            &Input::new(),
+            Duration::ZERO,
+            game,
+            &mut VecDeque::new(),
+            &UserRenderConfig::default(),
+            Some(&event),
+        );
+        game.finish_clearing();
+    }
+
+    fn soft_drop_score(row: i16) -> i32 {
+        soft_drop_score_from(BoardOrigin::TopLeft, row)
+    }
+
+    // `row` is where the shape's top-left goes on the board, not on the screen
+    fn soft_drop_score_from(origin: BoardOrigin, row: i16) -> i32 {
+        let mut game = game_with_empty_board(10);
+        game.config.soft_drop_scoring = true;
+        game.config.board_origin = origin;
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+        let (l_shape, _) = game.board_placement(&l_shape, &CellCoord::new(3, 0));
+        PlacementSystem.update_state(
+            &Input::new(),
+            Duration::ZERO,
+            &mut game,
+            &mut VecDeque::new(),
+            &UserRenderConfig::default(),
+            Some(&SelectedShapePlaced(l_shape, CellCoord::new(3, row))),
+        );
+        game.stats.current_score
+    }
+
+    #[test]
+    fn test_soft_drop_placing_lower_scores_more() {
+        let top = soft_drop_score(0);
+        let bottom = soft_drop_score(7);
+
+        assert_eq!(top, 0);
+        assert!(bottom > top);
+        assert_eq!(soft_drop_bonus(9, 10, 3), 3);
+    }
+
+    #[test]
+    fn test_soft_drop_counts_rows_on_the_screen_from_the_bottom_left() {
+        // board row 0 is the bottom row on the screen
+        let bottom = soft_drop_score_from(BoardOrigin::BottomLeft, 0);
+        let top = soft_drop_score_from(BoardOrigin::BottomLeft, 7);
+
+        assert_eq!(bottom, soft_drop_score_from(BoardOrigin::TopLeft, 7));
+        assert_eq!(top, 0);
+    }
+
+    #[test]
+    fn test_affected_lines_of_placement() {
+        let l_shape = ShapeType::new(BaseShapeType::L1, false, ShapeRot::No);
+
+        let (rows, cols) = affected_lines(&l_shape, &CellCoord::new(2, 1), 6, 6);
+
+        assert_eq!(rows, vec![1, 2, 3]);
+        assert_eq!(cols, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_placement_far_from_full_lines_awards_nothing() {
+        let mut game = game_with_empty_board(5);
+        // row 4 is one cell short
+        for col in 0..4 {
+            game.board.set_cell(col, 4, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let (rows, cols) = affected_lines(&single, &CellCoord::new(0, 0), 5, 5);
+        assert_eq!((rows.len(), cols.len()), (1, 1));
+        assert!(full_lines(&game.board, &rows, &cols).is_empty());
+
+        place(&mut game, single, CellCoord::new(0, 0));
+
+        assert_eq!(game.stats.current_score, 0);
+        assert_eq!(game.board.get(0, 0), Some(&Cell::Filled));
+    }
+
+    #[test]
+    fn test_double_clear_reports_cleared_lines() {
+        let mut game = game_with_empty_board(5);
+        for row in 1..3 {
+            for col in 2..5 {
+                game.board.set_cell(col, row, Cell::Filled);
+            }
+        }
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+
+        place(&mut game, square, CellCoord::new(0, 1));
+
+        let expected = ClearedLines {
+            rows: vec![1, 2],
+            cols: vec![],
+        };
+        assert_eq!(game.last_cleared, expected);
+        assert_eq!(game.stats.lines_cleared, 2);
+        assert_eq!(game.stats.cells_cleared, 10);
+    }
+
+    #[test]
+    fn test_placement_completing_row_clears_and_scores() {
+        let mut game = game_with_empty_board(5);
+        for col in 0..4 {
+            game.board.set_cell(col, 4, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        place(&mut game, single, CellCoord::new(4, 4));
+
+        assert_eq!(game.stats.current_score, 5);
+        assert!((0..5).all(|col| game.board.get(col, 4) == Some(&Cell::Empty)));
+    }
+
+    #[test]
+    fn test_wrapped_placement_clears_the_row() {
+        let mut game = game_with_empty_board(5);
+        game.config.wraparound = true;
+        game.board.set_cell(2, 2, Cell::Filled);
+        // covers cols 3 and 4 of row 2, then 0 and 1 after wrapping
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::Cw90);
+        assert!(game.is_valid_placement(&line, &CellCoord::new(3, 2)));
+
+        place(&mut game, line, CellCoord::new(3, 2));
+
+        assert_eq!(game.last_cleared.rows, vec![2]);
+        assert_eq!(game.stats.current_score, 5);
+        assert_eq!(game.board.filled_count(), 0);
+    }
+
+    // places the shape and hands the events of the clear to the slow motion system
+    fn place_with_slow_motion(game: &mut Game, shape: ShapeType, cell: CellCoord) {
+        let render_config = UserRenderConfig::default();
+        let mut events = VecDeque::new();
+        let placed = SelectedShapePlaced(shape, cell);
+        game.place_shape(&shape, &cell);
+        ScoreCleanupSystem.update_state(
+            &Input::new(),
+            Duration::ZERO,
+            game,
+            &mut events,
+            &render_config,
+            Some(&placed),
+        );
+        while let Some(event) = events.pop_front() {
+            SlowMotionSystem.update_state(
+                &Input::new(),
+                Duration::ZERO,
+                game,
+                &mut VecDeque::new(),
+                &render_config,
+                Some(&event),
+            );
+        }
+    }
+
+    #[test]
+    fn test_three_line_clear_starts_slow_motion() {
+        let mut game = game_with_empty_board(5);
+        // rows 0..3 miss only their first cell
+        for row in 0..3 {
+            for col in 1..5 {
+                game.board.set_cell(col, row, Cell::Filled);
+            }
+        }
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+        assert_eq!(line.cells().len(), 4);
+
+        place_with_slow_motion(&mut game, line, CellCoord::new(0, 0));
+
+        assert_eq!(game.last_cleared.rows, vec![0, 1, 2]);
+        assert!(game.ui.slow_motion.is_some());
+        assert!(game.ui.time_scale() < 1.0);
+    }
+
+    #[test]
+    fn test_single_line_clear_plays_at_normal_speed() {
+        let mut game = game_with_empty_board(5);
+        for col in 0..4 {
+            game.board.set_cell(col, 4, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        place_with_slow_motion(&mut game, single, CellCoord::new(4, 4));
+
+        assert_eq!(game.last_cleared.rows, vec![4]);
+        assert!(game.ui.slow_motion.is_none());
+        assert_eq!(game.ui.time_scale(), 1.0);
+    }
+
+    #[test]
+    fn test_ledger_adds_up_to_total_score() {
+        let mut game = game_with_empty_board(5);
+        game.config.perfect_clear_bonus = 7;
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        game.board.set_cell(0, 0, Cell::Filled);
+
+        // a row
+        for col in 0..4 {
+            game.board.set_cell(col, 4, Cell::Filled);
+        }
+        place(&mut game, single, CellCoord::new(4, 4));
+        // a row and a col at once
+        for i in [0, 1, 3, 4] {
+            game.board.set_cell(i, 2, Cell::Filled);
+            game.board.set_cell(2, i, Cell::Filled);
+        }
+        place(&mut game, single, CellCoord::new(2, 2));
+        // the last row empties the board
+        for col in 1..4 {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        place(&mut game, single, CellCoord::new(4, 0));
+
+        assert_eq!(game.ledger.cells, 20);
+        assert_eq!(game.ledger.multi_clear, 1);
+        assert_eq!(game.ledger.perfect_clear, 7);
+        // three clears in a row: 11 once and 12 twice on top
+        assert_eq!(game.ledger.combo, 35);
+        assert_eq!(game.ledger.total(), game.stats.total_score);
+        assert_eq!(game.stats.total_score, 63);
+    }
+
+    #[test]
+    fn test_clears_in_a_row_score_more_each_time() {
+        let mut game = game_with_empty_board(4);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let clear_row = |game: &mut Game, row: usize| {
+            for col in 1..4 {
+                game.board.set_cell(col, row, Cell::Filled);
+            }
+            let before = game.stats.total_score;
+            place(game, single, CellCoord::new(0, row as i16));
+            game.stats.total_score - before
+        };
+
+        assert_eq!(clear_row(&mut game, 0), 4);
+        assert_eq!(clear_row(&mut game, 1), 8);
+        assert_eq!(clear_row(&mut game, 2), 12);
+        assert_eq!(game.stats.combo, 3);
+
+        // a placement clearing nothing ends the combo
+        place(&mut game, single, CellCoord::new(0, 0));
+        assert_eq!(game.stats.combo, 0);
+        assert_eq!(clear_row(&mut game, 3), 4);
+        assert_eq!(game.ledger.total(), game.stats.total_score);
+    }
+
+    #[test]
+    fn test_score_decays_after_the_grace_period() {
+        let mut game = game_with_empty_board(4);
+        game.config.score_decay_grace = Some(Duration::from_secs(5));
+        game.config.score_decay_every = Duration::from_secs(1);
+        game.stats.current_score = 3;
+        game.stats.total_score = 10;
+        let render_config = UserRenderConfig::default();
+        let update = |game: &mut Game, secs: u64, event: Option<&Event>| {
+            DecaySystem.update_state(
+                &Input::new(),
+                Duration::from_secs(secs),
+                game,
+                &mut VecDeque::new(),
+                &render_config,
+                event,
+            )
+        };
+
+        update(&mut game, 5, None);
+        assert_eq!(game.stats.current_score, 3);
+        update(&mut game, 2, None);
+        assert_eq!((game.stats.current_score, game.stats.total_score), (1, 8));
+        assert_eq!(game.ledger.decay, -2);
+
+        // a placement starts the grace period over
+        let shape = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        update(
+            &mut game,
+            0,
+            Some(&SelectedShapePlaced(shape, CellCoord::new(0, 0))),
+        );
+        assert_eq!(game.idle, Duration::ZERO);
+        update(&mut game, 5, None);
+        assert_eq!(game.stats.current_score, 1);
+
+        // never below zero
+        update(&mut game, 30, None);
+        assert_eq!((game.stats.current_score, game.stats.total_score), (0, 7));
+        assert_eq!(game.ledger.total(), -3);
+    }
+
+    #[test]
+    fn test_decay_speeds_up_over_the_level() {
+        let mut game = game_with_empty_board(4);
+        game.config.score_decay_grace = Some(Duration::ZERO);
+        game.config.score_decay_every = Duration::from_secs(1);
+        game.config.score_decay_ramp = Some(SpeedRamp {
+            curve: RampCurve::Linear,
+            ramp_time: Duration::from_secs(10),
+            max_factor: 3.0,
+        });
+        game.stats.current_score = 1000;
+        game.stats.total_score = 1000;
+        let render_config = UserRenderConfig::default();
+        // points lost over the next `secs` seconds of idling
+        let decayed_over = |game: &mut Game, secs: u64| {
+            let before = game.stats.current_score;
+            for _ in 0..secs * 10 {
+                // the game loop keeps the level time
+                game.level_time += Duration::from_millis(100);
+                DecaySystem.update_state(
+                    &Input::new(),
+                    Duration::from_millis(100),
+                    game,
+                    &mut VecDeque::new(),
+                    &render_config,
+                    None,
+                );
+            }
+            before - game.stats.current_score
+        };
+
+        let early = decayed_over(&mut game, 2);
+        let late = decayed_over(&mut game, 2);
+        assert!(late > early, "{} then {}", early, late);
+        // the ramp topped out, 3 points a second from now on
+        decayed_over(&mut game, 10);
+        assert_eq!(decayed_over(&mut game, 2), 6);
+
+        // a new level starts at the normal speed again
+        game.go_next_level();
+        assert_eq!(game.level_time, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_danger_zone_follows_the_fill_threshold() {
+        let mut game = game_with_empty_board(2);
+        let render_config = UserRenderConfig {
+            danger_zone_threshold: 0.75,
+            ..UserRenderConfig::default()
+        };
+        let update = |game: &mut Game, dt: u64| {
+            DangerZoneSystem.update_state(
+                &Input::new(),
+                Duration::from_millis(dt),
+                game,
+                &mut VecDeque::new(),
+                &render_config,
+                None,
+            )
+        };
+
+        game.board.set_cell(0, 0, Cell::Filled);
+        game.board.set_cell(1, 0, Cell::Filled);
+        update(&mut game, 100);
+        assert_eq!(game.ui.danger_zone, None);
+
+        game.board.set_cell(0, 1, Cell::Filled);
+        update(&mut game, 100);
+        assert_eq!(game.ui.danger_zone, Some(Duration::ZERO));
+        update(&mut game, 100);
+        assert_eq!(game.ui.danger_zone, Some(Duration::from_millis(100)));
+
+        game.board.set_cell(0, 0, Cell::Empty);
+        update(&mut game, 100);
+        assert_eq!(game.ui.danger_zone, None);
+    }
+
+    #[test]
+    fn test_clearing_a_bonus_cell_awards_its_points_once() {
+        let mut game = game_with_empty_board(4);
+        game.config.bonus_cell_points = 10;
+        for col in 0..3 {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        game.board.set_cell(1, 1, Cell::Filled);
+        game.board.set_bonus(1, 0);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        place(&mut game, single, CellCoord::new(3, 0));
+
+        // 4 cells and the bonus
+        assert_eq!(game.ledger.bonus_cells, 10);
+        assert_eq!(game.stats.current_score, 14);
+        assert!(game.board.bonus_cells().is_empty());
+
+        // the bonus is gone with the cleared cell, a new line through it pays nothing extra
+        for col in [0, 2] {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        place(&mut game, single, CellCoord::new(3, 0));
+        place(&mut game, single, CellCoord::new(1, 0));
+        assert_eq!(game.ledger.bonus_cells, 10);
+    }
+
+    #[test]
+    fn test_edge_glow_is_off_unless_configured() {
+        let mut game = game_with_empty_board(5);
+        game.board = Board::from_ascii("####.\n.....\n.....\n.....\n.....\n").unwrap();
+        let glow = |game: &mut Game, render_config: &UserRenderConfig| {
+            EdgeGlowSystem.update_state(
+                &Input::new(),
+                Duration::from_millis(100),
+                game,
+                &mut VecDeque::new(),
+                render_config,
+                None,
+            )
+        };
+
+        // nothing keeps the frames coming, see `skip_render`
+        glow(&mut game, &UserRenderConfig::default());
+        assert_eq!(game.ui.edge_glow, None);
+
+        let render_config = UserRenderConfig {
+            edge_glow_threshold: Some(0.8),
+            ..UserRenderConfig::default()
+        };
+        glow(&mut game, &render_config);
+        assert_eq!(game.ui.edge_glow, Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_anti_frustration_rerolls_only_when_configured() {
+        let mut game = Game::new_level(5, 1, 0);
+        game.board = Board::from_ascii("#####\n###.#\n#####\n#####\n#####\n").unwrap();
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![square, square, square]));
+        let update = |game: &mut Game| {
+            AntiFrustrationSystem.update_state(
+                &Input::new(),
+                Duration::ZERO,
+                game,
+                &mut VecDeque::new(),
+                &UserRenderConfig::default(),
+                None,
+            )
+        };
+
+        // off by default
+        update(&mut game);
+        assert!(game.panel.shape_choice.iter().all(|s| s.kind == square));
+
+        game.config.anti_frustration = true;
+        update(&mut game);
+        assert_eq!(
+            game.panel
+                .shape_choice
+                .iter()
+                .filter(|s| solver::has_valid_placement(&game, &s.kind))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_right_click_deselects_before_queued_events() {
+        let mut game = game_with_empty_board(4);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+        let mut events = VecDeque::from([LinesCleared(1)]);
+        let mut input = Input::new();
+        input.mouse_right_clicked = true;
+
+        SelectionValidationSystem.update_state(
+            &input,
+            Duration::ZERO,
+            &mut game,
+            &mut events,
+            &UserRenderConfig::default(),
+            None,
+        );
+
+        assert!(matches!(events.front(), Some(ShapeDeselected)));
+    }
+
+    #[test]
+    fn test_shift_rotates_the_held_shape_counter_clockwise() {
+        let mut game = game_with_empty_board(4);
+        let shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: shape,
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+        let mut input = Input::new();
+        // a second apart, well past the rotation debounce
+        let rotate = |input: &Input, game: &mut Game| {
+            RotationSystem.update_state(
+                input,
+                Duration::from_secs(1),
+                game,
+                &mut VecDeque::new(),
+                &UserRenderConfig::default(),
+                None,
+            )
+        };
+
+        input.update_modifiers(ModifiersState::SHIFT);
+        input.update_kb(&KeyCode::Space, &ElementState::Pressed);
+        let held = |game: &Game| game.selected_shape.as_ref().unwrap().shape_type;
+        // turning is off unless the game allows it
+        rotate(&input, &mut game);
+        assert_eq!(held(&game), shape);
+
+        game.config.rotation = true;
+        rotate(&input, &mut game);
+        assert_eq!(
+            held(&game),
+            ShapeType::new(BaseShapeType::T1, false, ShapeRot::Cw270)
+        );
+
+        input.reset();
+        input.update_modifiers(ModifiersState::empty());
+        input.update_kb(&KeyCode::Space, &ElementState::Pressed);
+        rotate(&input, &mut game);
+        assert_eq!(held(&game), shape);
+    }
+
+    #[test]
+    fn test_rotations_within_the_debounce_are_dropped() {
+        let mut game = game_with_empty_board(4);
+        game.config.rotation = true;
+        game.config.rotation_debounce = Duration::from_millis(100);
+        let shape = ShapeType::new(BaseShapeType::T1, false, ShapeRot::No);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: shape,
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: None,
+        });
+        let mut input = Input::new();
+        input.rotate_pressed = true;
+        let mut rotations = vec![];
+        // rotate pressed every 30ms frame for 300ms
+        for _ in 0..10 {
+            RotationSystem.update_state(
+                &input,
+                Duration::from_millis(30),
+                &mut game,
+                &mut VecDeque::new(),
+                &UserRenderConfig::default(),
+                None,
+            );
+            rotations.push(game.selected_shape.as_ref().unwrap().shape_type);
+        }
+
+        let turns = rotations.windows(2).filter(|w| w[0] != w[1]).count() + 1;
+        // the first frame, then every fourth one: 0, 120, 240ms
+        assert_eq!(turns, 3);
+        assert_eq!(
+            rotations[9],
+            ShapeType::new(BaseShapeType::T1, false, ShapeRot::Cw270)
+        );
+    }
+
+    #[test]
+    fn test_completing_the_diagonal_scores_the_pattern_bonus() {
+        let mut game = game_with_empty_board(4);
+        game.config.pattern_bonus = 5;
+        for i in 0..3 {
+            game.board.set_cell(i, i, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+
+        place(&mut game, single, CellCoord::new(3, 3));
+        assert_eq!(game.ledger.patterns, 5);
+        assert_eq!(game.stats.total_score, 5);
+
+        // lifted off the diagonal and put back on it, the diagonal was already complete
+        assert!(game.board.lift_shape(3, 3).is_some());
+        place(&mut game, single, CellCoord::new(3, 3));
+        assert_eq!(game.ledger.patterns, 5);
+        assert_eq!(game.ledger.total(), game.stats.total_score);
+
+        // a clear breaks it, completing it again scores again
+        game.board.clear_lines(&ClearedLines {
+            rows: vec![0],
+            cols: vec![],
+        });
+        place(&mut game, single, CellCoord::new(0, 0));
+        assert_eq!(game.ledger.patterns, 10);
+    }
+
+    #[test]
+    fn test_shapes_left_at_level_end_slide_out_first() {
+        let render_config = UserRenderConfig::default();
+        let mut game = game_with_empty_board(4);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![single, single]));
+        game.panel.shape_choice[0].set_state(ShapeState::PLACED);
+        let update = |system: &dyn System, game: &mut Game, dt: Duration| {
+            system.update_state(
+                &Input::new(),
+                dt,
+                game,
+                &mut VecDeque::new(),
+                &render_config,
+                None,
+            )
+        };
+
+        update(&NewGameSystem, &mut game, Duration::ZERO);
+
+        let refresh = game.ui.panel_refresh.as_ref().unwrap();
+        assert_eq!(refresh.phase, PanelRefreshPhase::SlideOut);
+        assert_eq!(refresh.outgoing, vec![CellCoord::new(2, 0)]);
+        let phase = render_config.panel_refresh_animation.unwrap();
+        update(&PanelRefreshSystem, &mut game, phase);
+        assert_eq!(
+            game.ui.panel_refresh.as_ref().map(|refresh| refresh.phase),
+            Some(PanelRefreshPhase::SlideIn)
+        );
+        update(&PanelRefreshSystem, &mut game, phase);
+        assert!(game.ui.panel_refresh.is_none());
+    }
+
+    #[test]
+    fn test_jammed_board_is_game_over() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.config.keep_leaderboard = false;
+        game.board = Board::from_ascii("####\n####\n####\n####\n").unwrap();
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![line]));
+        let win_or_lose = |game: &mut Game| {
+            WinOrLoseSystem.update_state(
+                &Input::new(),
+                Duration::ZERO,
+                game,
+                &mut VecDeque::new(),
+                &UserRenderConfig::default(),
+                None,
+            )
+        };
+
+        // nothing left to place, new shapes are about to come
+        game.panel.shape_choice[0].set_state(ShapeState::PLACED);
+        win_or_lose(&mut game);
+        assert_eq!(game.game_state, GameState::Playing);
+
+        game.panel.shape_choice[0].set_state(ShapeState::VISIBLE);
+        win_or_lose(&mut game);
+        assert_eq!(game.game_state, GameState::GameOver);
+    }
+
+    #[test]
+    fn test_game_over_is_declared_once_not_every_frame() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::from_ascii("####\n####\n####\n####\n").unwrap();
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![line]));
+        let frame = |game: &mut Game| {
+            WinOrLoseSystem.update_state(
+                &Input::new(),
+                Duration::ZERO,
+                game,
+                &mut VecDeque::new(),
+                &UserRenderConfig::default(),
+                None,
+            )
+        };
+
+        frame(&mut game);
+        assert_eq!(game.game_state, GameState::GameOver);
+        // forgotten on purpose, a second pass through the game over branch would add it again
+        game.on_leaderboard = false;
+        frame(&mut game);
+
+        assert_eq!(game.game_state, GameState::GameOver);
+        assert_eq!(game.leaderboard.entries().len(), 1);
+    }
+
+    #[test]
+    fn test_retried_run_is_on_the_leaderboard_once() {
+        let mut game = Game::new_level(4, 1, 0);
+        let jam = |game: &mut Game| {
+            game.board = Board::from_ascii("####\n####\n####\n####\n").unwrap();
+            let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+            game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![line]));
+            WinOrLoseSystem.update_state(
+                &Input::new(),
+                Duration::ZERO,
+                game,
+                &mut VecDeque::new(),
+                &UserRenderConfig::default(),
+                None,
+            );
+            assert_eq!(game.game_state, GameState::GameOver);
+        };
+
+        jam(&mut game);
+        assert_eq!(game.leaderboard.entries().len(), 1);
+
+        game.retry_level();
+        jam(&mut game);
+        assert_eq!(game.leaderboard.entries().len(), 1);
+
+        // a new run gets its own entry
+        game.restart();
+        jam(&mut game);
+        assert_eq!(game.leaderboard.entries().len(), 2);
+    }
+
+    #[test]
+    fn test_panel_hover_redraws_only_for_the_preview() {
+        let mut game = game_with_empty_board(4);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![single]));
+        let cell = *game.panel.shapes_in_cell_space.keys().next().unwrap();
+        let mut config = UserRenderConfig::default();
+        let mut input = Input::new();
+        input.mouse_position = XY(
+            config.panel_offset_x_px + (cell.col as f32 + 0.5) * config.cell_size_px,
+            config.panel_offset_y_px + (cell.row as f32 + 0.5) * config.cell_size_px,
+        );
+        let hover = |game: &mut Game, config: &UserRenderConfig| {
+            game.ui.hovered_panel_shape = None;
+            game.ui.need_to_update_panel = false;
+            PanelHoverSystem.update_state(
+                &input,
+                Duration::ZERO,
+                game,
+                &mut VecDeque::new(),
+                config,
+                None,
+            );
+        };
+
+        hover(&mut game, &config);
+        assert_eq!(game.ui.hovered_panel_shape, Some(0));
+        assert!(!game.ui.need_to_update_panel);
+
+        config.show_rotation_preview = true;
+        hover(&mut game, &config);
+        assert!(game.ui.need_to_update_panel);
+    }
+
+    #[test]
+    fn test_undo_takes_back_the_placement_and_its_clear() {
+        let mut game = game_with_empty_board(4);
+        game.board = Board::from_ascii(".###\n....\n.#..\n....\n").unwrap();
+        game.stats.current_score = 7;
+        game.stats.total_score = 20;
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let line = ShapeType::new(BaseShapeType::I1, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![single, line]));
+        game.panel.shape_choice[0].set_state(ShapeState::SELECTED);
+        game.selected_shape = Some(SelectedShape {
+            shape_type: single,
+            anchor_offset: OffsetXY(0, 0),
+            picked_up_from: None,
+            panel_index: Some(0),
+        });
+
+        place(&mut game, single, CellCoord::new(0, 0));
+        assert_eq!(game.board.to_ascii(), "....\n....\n.#..\n....\n");
+        assert_eq!(game.stats.total_score, 24);
+
+        assert!(game.undo());
+        assert_eq!(game.board.to_ascii(), ".###\n....\n.#..\n....\n");
+        assert_eq!(game.stats.current_score, 7);
+        assert_eq!(game.stats.total_score, 20);
+        assert_eq!(game.ledger.total(), 0);
+        assert_eq!(game.panel.shape_choice[0].state, ShapeState::VISIBLE);
+        assert!(game.selected_shape.is_none());
+        assert!(!game.undo());
+    }
+
+    #[test]
+    fn test_undo_puts_the_rng_back() {
+        let mut game = game_with_empty_board(4);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        game.panel = Panel::from_shapes(Shape::lay_out_in_panel(vec![single]));
+        game.panel.shape_choice[0].set_state(ShapeState::SELECTED);
+        let mut before = game.rng.clone();
+
+        place(&mut game, single, CellCoord::new(0, 0));
+        // the emptied panel is dealt again from the rng
+        game.panel = Panel::generate(&mut game.rng, &mut game.shape_bag, &game.config);
+        assert!(game.undo());
+
+        assert_eq!(game.rng.gen::<u64>(), before.gen::<u64>());
+    }
+
+    #[test]
+    fn test_undo_stack_keeps_the_last_ten_placements() {
+        let mut game = game_with_empty_board(8);
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        for col in 0..6 {
+            for row in 0..2 {
+                game.place_shape(&single, &CellCoord::new(col, row));
+            }
+        }
+
+        let undone = std::iter::from_fn(|| game.undo().then_some(())).count();
+
+        assert_eq!(undone, 10);
+        // the two oldest placements stay
+        assert_eq!(game.board.filled_count(), 2);
+    }
+}
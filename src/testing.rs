@@ -0,0 +1,82 @@
+use crate::game_entities::{Cell, Game, GameStats, Panel, Shape};
+
+// views into the game for scripted tests, none of them is needed to play
+impl Game {
+    pub fn board_grid(&self) -> &[Cell] {
+        self.board.cells()
+    }
+
+    pub fn panel_shapes(&self) -> &[Shape] {
+        &self.panel.shape_choice
+    }
+
+    pub fn stats_view(&self) -> &GameStats {
+        &self.stats
+    }
+
+    // shapes are laid out by the caller, see `Shape::lay_out_in_panel`
+    pub fn set_panel_for_test(&mut self, shapes: Vec<Shape>) {
+        self.panel = Panel::from_shapes(shapes);
+        self.selected_shape = None;
+        self.ui.need_to_update_panel = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use crate::events::Event::SelectedShapePlaced;
+    use crate::game_entities::{BaseShapeType, Board, ShapeRot, ShapeState, ShapeType};
+    use crate::input::Input;
+    use crate::render::render::UserRenderConfig;
+    use crate::space_converters::CellCoord;
+    use crate::system::{PlacementSystem, ScoreCleanupSystem, System};
+
+    use super::*;
+
+    #[test]
+    fn test_injected_panel_shape_clears_a_line() {
+        let mut game = Game::new_level(4, 1, 0);
+        game.board = Board::new(4);
+        for col in 0..3 {
+            game.board.set_cell(col, 0, Cell::Filled);
+        }
+        let single = ShapeType::new(BaseShapeType::O, false, ShapeRot::No);
+        let square = ShapeType::new(BaseShapeType::OO, false, ShapeRot::No);
+        game.set_panel_for_test(Shape::lay_out_in_panel(vec![single, square]));
+        assert_eq!(game.panel_shapes().len(), 2);
+
+        // what selecting the first shape and clicking the last cell of the row does
+        game.panel.shape_choice[0].set_state(ShapeState::PLACED);
+        let placed = SelectedShapePlaced(single, CellCoord::new(3, 0));
+        let mut events = VecDeque::new();
+        // the full row goes right away, without a clear animation to play out
+        let config = UserRenderConfig {
+            clear_animation: None,
+            ..UserRenderConfig::default()
+        };
+        PlacementSystem.update_state(
+            &Input::new(),
+            Duration::ZERO,
+            &mut game,
+            &mut events,
+            &config,
+            Some(&placed),
+        );
+        ScoreCleanupSystem.update_state(
+            &Input::new(),
+            Duration::ZERO,
+            &mut game,
+            &mut events,
+            &config,
+            Some(&placed),
+        );
+
+        assert!(game.board_grid().iter().all(|cell| *cell == Cell::Empty));
+        assert_eq!(game.stats_view().lines_cleared, 1);
+        assert_eq!(game.stats_view().current_score, 4);
+        assert_eq!(game.panel_shapes()[1].state, ShapeState::VISIBLE);
+    }
+}
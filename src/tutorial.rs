@@ -0,0 +1,59 @@
+use crate::events::Event;
+
+// first level walkthrough, each step waits for the player to do what its prompt asks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TutorialStep {
+    SelectShape,
+    PlaceShape,
+}
+
+impl TutorialStep {
+    // step after the player did `event`, none once the tutorial is over
+    pub fn after(self, event: &Event) -> Option<TutorialStep> {
+        match (self, event) {
+            (TutorialStep::SelectShape, Event::ShapeSelected(_, _)) => {
+                Some(TutorialStep::PlaceShape)
+            }
+            (TutorialStep::PlaceShape, Event::ShapeDeselected) => Some(TutorialStep::SelectShape),
+            (TutorialStep::PlaceShape, Event::SelectedShapePlaced(_, _)) => None,
+            (step, _) => Some(step),
+        }
+    }
+
+    pub fn prompt(self) -> &'static str {
+        match self {
+            TutorialStep::SelectShape => "↓ Click a shape",
+            TutorialStep::PlaceShape => "↓ Click the board to place it",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_entities::{BaseShapeType, ShapeRot, ShapeType};
+    use crate::space_converters::{CellCoord, OffsetXY};
+
+    #[test]
+    fn test_tutorial_follows_the_player() {
+        let selected = Event::ShapeSelected(0, OffsetXY(0, 0));
+        let placed = Event::SelectedShapePlaced(
+            ShapeType::new(BaseShapeType::O, false, ShapeRot::No),
+            CellCoord::new(0, 0),
+        );
+        let events = [
+            (Event::ShapeDeselected, Some(TutorialStep::SelectShape)),
+            (selected.clone(), Some(TutorialStep::PlaceShape)),
+            (Event::ShapeDeselected, Some(TutorialStep::SelectShape)),
+            (selected, Some(TutorialStep::PlaceShape)),
+            (Event::LinesCleared(0), Some(TutorialStep::PlaceShape)),
+            (placed, None),
+        ];
+
+        let mut step = Some(TutorialStep::SelectShape);
+        for (event, expected) in events {
+            step = step.and_then(|step| step.after(&event));
+            assert_eq!(step, expected, "after {:?}", event);
+        }
+    }
+}
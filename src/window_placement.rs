@@ -0,0 +1,123 @@
+use winit::dpi::PhysicalPosition;
+use winit::monitor::{MonitorHandle, VideoMode};
+use winit::window::{Fullscreen, Window, WindowBuilder};
+
+use crate::render::render::UserRenderConfig;
+
+// where the window shows up at startup, taken from `UserRenderConfig`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowPlacement {
+    pub fullscreen: bool,
+    pub position: Option<PhysicalPosition<i32>>,
+    pub render_before_visible: bool,
+}
+
+impl WindowPlacement {
+    pub fn from_config(render_config: &UserRenderConfig) -> Self {
+        Self {
+            fullscreen: render_config.fullscreen,
+            position: render_config
+                .window_position
+                .map(|(x, y)| PhysicalPosition::new(x, y)),
+            render_before_visible: render_config.render_before_visible,
+        }
+    }
+
+    pub fn configure(&self, builder: WindowBuilder) -> WindowBuilder {
+        match self.position {
+            Some(position) => builder.with_position(position),
+            None => builder,
+        }
+    }
+
+    // needs the built window to know which monitor it is on. Returns true when it went fullscreen,
+    // the surface has to be reconfigured for the new size then.
+    pub fn enter_fullscreen(&self, window: &Window) -> bool {
+        if !self.fullscreen {
+            return false;
+        }
+        let Some(monitor) = window.current_monitor() else {
+            println!("No monitor to go fullscreen on");
+            return false;
+        };
+        let fullscreen = match best_video_mode(&monitor) {
+            Some(mode) => Fullscreen::Exclusive(mode),
+            None => Fullscreen::Borderless(Some(monitor)),
+        };
+        window.set_fullscreen(Some(fullscreen));
+        true
+    }
+
+    // shows the hidden window, with `render_before_visible` after drawing into it. The surface
+    // has to be configured for the final window size by then.
+    pub fn show(&self, render_first_frame: impl FnOnce(), set_visible: impl FnOnce()) {
+        if self.render_before_visible {
+            render_first_frame();
+        }
+        set_visible();
+    }
+}
+
+// the monitor's own resolution at the highest refresh rate it offers
+fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .max_by_key(|mode| (mode.refresh_rate_millihertz(), mode.bit_depth()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use winit::dpi::Position;
+
+    #[test]
+    fn test_placement_follows_config() {
+        let mut config = UserRenderConfig::default();
+        let placement = WindowPlacement::from_config(&config);
+        assert_eq!(placement.position, None);
+        assert!(!placement.fullscreen);
+        assert_eq!(
+            placement
+                .configure(WindowBuilder::new())
+                .window_attributes()
+                .position,
+            None
+        );
+
+        config.fullscreen = true;
+        config.window_position = Some((40, -20));
+        let placement = WindowPlacement::from_config(&config);
+        assert!(placement.fullscreen);
+        assert_eq!(
+            placement
+                .configure(WindowBuilder::new())
+                .window_attributes()
+                .position,
+            Some(Position::Physical(PhysicalPosition::new(40, -20)))
+        );
+    }
+
+    #[test]
+    fn test_first_frame_is_rendered_before_the_window_shows() {
+        let mut config = UserRenderConfig::default();
+        let steps = RefCell::new(vec![]);
+        let placement = WindowPlacement::from_config(&config);
+
+        placement.show(
+            || steps.borrow_mut().push("render"),
+            || steps.borrow_mut().push("visible"),
+        );
+        assert_eq!(*steps.borrow(), vec!["render", "visible"]);
+
+        config.render_before_visible = false;
+        steps.borrow_mut().clear();
+        WindowPlacement::from_config(&config).show(
+            || steps.borrow_mut().push("render"),
+            || steps.borrow_mut().push("visible"),
+        );
+        assert_eq!(*steps.borrow(), vec!["visible"]);
+    }
+}